@@ -5,6 +5,7 @@ pub enum WalletError {
     Io(io::Error),
     UnsupportedVersion(u64),
     InvalidFormat(String),
+    Lightwalletd(String),
 }
 
 impl From<io::Error> for WalletError {
@@ -19,6 +20,7 @@ impl fmt::Display for WalletError {
             WalletError::Io(e) => write!(f, "IO error: {}", e),
             WalletError::UnsupportedVersion(v) => write!(f, "Unsupported wallet version: {}", v),
             WalletError::InvalidFormat(s) => write!(f, "Invalid wallet format: {}", s),
+            WalletError::Lightwalletd(s) => write!(f, "Lightwalletd error: {}", s),
         }
     }
 }
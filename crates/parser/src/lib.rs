@@ -1,3 +1,6 @@
 pub mod error;
+#[cfg(feature = "online")]
+pub mod lightwalletd;
 pub mod reader;
+pub mod writer;
 pub mod zwl;
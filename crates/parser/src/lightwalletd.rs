@@ -0,0 +1,8 @@
+//! # Lightwalletd
+//!
+//! Access to lightwalletd's `CompactTxStreamer` gRPC service, only available with the `online`
+//! feature. [`client`] wraps the generated client, and [`mock`] is a small in-process server
+//! answering from a fixed set of blocks, for tests and for serving a wallet's cached blocks.
+
+pub mod client;
+pub mod mock;
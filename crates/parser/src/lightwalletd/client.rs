@@ -0,0 +1,111 @@
+use tonic::{
+    codec::Streaming,
+    transport::{Channel, ClientTlsConfig, Endpoint},
+};
+use tracing::instrument;
+use zcash_client_backend::proto::{
+    compact_formats::CompactBlock,
+    service::{
+        BlockId, BlockRange, ChainSpec, Empty, LightdInfo, RawTransaction,
+        TransparentAddressBlockFilter, TreeState,
+        compact_tx_streamer_client::CompactTxStreamerClient,
+    },
+};
+
+use crate::error::WalletError;
+
+/// A connection to a lightwalletd server.
+#[derive(Debug, Clone)]
+pub struct LightwalletdClient {
+    inner: CompactTxStreamerClient<Channel>,
+}
+
+impl LightwalletdClient {
+    /// Connects to `uri`, using TLS for `https` endpoints.
+    #[instrument(level = "info", name = "LightwalletdClient::connect", skip_all, fields(uri = %uri), err)]
+    pub async fn connect(uri: &str) -> Result<Self, WalletError> {
+        let mut endpoint = Endpoint::from_shared(uri.to_string()).map_err(rpc_error)?;
+        if uri.starts_with("https") {
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new().with_webpki_roots())
+                .map_err(rpc_error)?;
+        }
+
+        let channel = endpoint.connect().await.map_err(rpc_error)?;
+        Ok(Self {
+            inner: CompactTxStreamerClient::new(channel),
+        })
+    }
+
+    pub async fn lightd_info(&mut self) -> Result<LightdInfo, WalletError> {
+        let response = self.inner.get_lightd_info(Empty {}).await;
+        Ok(response.map_err(rpc_error)?.into_inner())
+    }
+
+    pub async fn tree_state(&mut self, height: u64) -> Result<TreeState, WalletError> {
+        let id = BlockId {
+            height,
+            hash: vec![],
+        };
+        let response = self.inner.get_tree_state(id).await;
+        Ok(response.map_err(rpc_error)?.into_inner())
+    }
+
+    pub async fn latest_block(&mut self) -> Result<BlockId, WalletError> {
+        let response = self.inner.get_latest_block(ChainSpec {}).await;
+        Ok(response.map_err(rpc_error)?.into_inner())
+    }
+
+    /// Streams the compact blocks from `start` to `end`, inclusive.
+    pub async fn block_range(
+        &mut self,
+        start: u64,
+        end: u64,
+    ) -> Result<Streaming<CompactBlock>, WalletError> {
+        let response = self.inner.get_block_range(block_range(start, end)).await;
+        Ok(response.map_err(rpc_error)?.into_inner())
+    }
+
+    /// Returns the transactions involving the transparent `address` mined from `start` to `end`.
+    pub async fn taddress_txids(
+        &mut self,
+        address: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<RawTransaction>, WalletError> {
+        let filter = TransparentAddressBlockFilter {
+            address: address.to_string(),
+            range: Some(block_range(start, end)),
+        };
+
+        let mut stream = self
+            .inner
+            .get_taddress_txids(filter)
+            .await
+            .map_err(rpc_error)?
+            .into_inner();
+
+        let mut txs = vec![];
+        while let Some(tx) = stream.message().await.map_err(rpc_error)? {
+            txs.push(tx);
+        }
+        Ok(txs)
+    }
+}
+
+fn block_range(start: u64, end: u64) -> BlockRange {
+    BlockRange {
+        start: Some(BlockId {
+            height: start,
+            hash: vec![],
+        }),
+        end: Some(BlockId {
+            height: end,
+            hash: vec![],
+        }),
+    }
+}
+
+pub(crate) fn rpc_error(e: impl std::fmt::Display) -> WalletError {
+    WalletError::Lightwalletd(e.to_string())
+}
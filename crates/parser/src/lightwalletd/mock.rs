@@ -0,0 +1,420 @@
+// Handlers return `tonic::Status` errors, like generated service traits do.
+#![allow(clippy::result_large_err)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    future::{Ready, ready},
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use orchard_new::{note::ExtractedNoteCommitment, tree::MerkleHashOrchard};
+use tokio::{net::TcpListener, task::JoinHandle};
+use tokio_stream::{Iter, iter, wrappers::TcpListenerStream};
+use tonic::{
+    Request, Response, Status,
+    body::BoxBody,
+    codec::ProstCodec,
+    codegen::{Body, BoxFuture, Service, StdError, empty_body, http},
+    server::{Grpc, NamedService, ServerStreamingService, UnaryService},
+    transport::Server,
+};
+use zcash_client_backend::proto::{
+    compact_formats::CompactBlock,
+    service::{
+        BlockId, BlockRange, ChainSpec, Empty, LightdInfo, RawTransaction,
+        TransparentAddressBlockFilter, TreeState,
+    },
+};
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId, NetworkUpgrade, Parameters},
+    merkle_tree::write_commitment_tree,
+};
+
+use crate::{
+    error::WalletError,
+    lightwalletd::client::rpc_error,
+    zwl::{ZwlWallet, sapling_witness::decode_sapling_tree, sync::sapling_commitments},
+};
+
+/// The chain served by [`MockLightwalletd`].
+#[derive(Debug, Clone, Default)]
+pub struct MockChain {
+    pub blocks: BTreeMap<u64, CompactBlock>,
+    /// Transactions returned by `GetTaddressTxids`, by address.
+    pub taddress_txs: HashMap<String, Vec<RawTransaction>>,
+    /// Tree states returned by `GetTreeState`, by height.
+    pub tree_states: BTreeMap<u64, TreeState>,
+    /// Returned by `GetLightdInfo`, with `block_height` and `estimated_height` set to the tip.
+    pub lightd_info: LightdInfo,
+}
+
+impl MockChain {
+    pub fn new(blocks: impl IntoIterator<Item = CompactBlock>) -> Self {
+        Self {
+            blocks: blocks.into_iter().map(|b| (b.height, b)).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Builds a chain from the blocks cached in `wallet`.
+    ///
+    /// Tree states are derived from `verified_tree` and the cached blocks above it, so they
+    /// are only available from the height of `verified_tree` up to the latest cached block.
+    pub fn from_wallet(wallet: &ZwlWallet) -> Result<Self, WalletError> {
+        let network = wallet.chain_name.require_network()?;
+
+        let blocks = wallet
+            .blocks
+            .iter()
+            .map(|b| b.compact_block())
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut chain = Self::new(blocks);
+
+        if let Some(tree_state) = &wallet.verified_tree {
+            chain.tree_states = derive_tree_states(tree_state, &chain.blocks)?;
+        }
+
+        let tip = chain.tip().map_or(0, |b| b.height);
+        let branch_id = BranchId::for_height(&network, BlockHeight::from_u32(tip as u32));
+        chain.lightd_info = LightdInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            vendor: "zecwallet-parser mock".to_string(),
+            // Compact blocks don't carry transparent data.
+            taddr_support: false,
+            chain_name: wallet.chain_name.name().unwrap_or_default().to_string(),
+            sapling_activation_height: network
+                .activation_height(NetworkUpgrade::Sapling)
+                .map_or(0, u64::from),
+            consensus_branch_id: format!("{:x}", u32::from(branch_id)),
+            ..Default::default()
+        };
+
+        Ok(chain)
+    }
+
+    pub fn tip(&self) -> Option<&CompactBlock> {
+        self.blocks.values().next_back()
+    }
+
+    fn latest_block(&self, _: ChainSpec) -> Result<BlockId, Status> {
+        let tip = self
+            .tip()
+            .ok_or_else(|| Status::unavailable("The mock chain has no blocks"))?;
+
+        Ok(BlockId {
+            height: tip.height,
+            hash: tip.hash.clone(),
+        })
+    }
+
+    fn lightd_info(&self, _: Empty) -> Result<LightdInfo, Status> {
+        let tip = self.tip().map_or(0, |b| b.height);
+        Ok(LightdInfo {
+            block_height: tip,
+            estimated_height: tip,
+            ..self.lightd_info.clone()
+        })
+    }
+
+    fn tree_state(&self, id: BlockId) -> Result<TreeState, Status> {
+        // Like lightwalletd, look the block up by hash if no height is given.
+        let height = match id.height {
+            0 => self
+                .blocks
+                .values()
+                .find(|b| b.hash == id.hash)
+                .map(|b| b.height)
+                .ok_or_else(|| Status::not_found("Block not found"))?,
+            height => height,
+        };
+
+        self.tree_states
+            .get(&height)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("No tree state at height {}", height)))
+    }
+
+    fn block(&self, id: BlockId) -> Result<CompactBlock, Status> {
+        self.blocks
+            .get(&id.height)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("Block {} not found", id.height)))
+    }
+
+    fn block_range(&self, range: BlockRange) -> Result<Vec<CompactBlock>, Status> {
+        let (start, end) = range_heights(&range)?;
+
+        let tip = self.tip().map_or(0, |b| b.height);
+        if start.max(end) > tip {
+            return Err(Status::out_of_range(format!(
+                "Block range {}-{} is above the tip {}",
+                start, end, tip
+            )));
+        }
+
+        // Like lightwalletd, a range can be requested in descending order.
+        let heights: Vec<u64> = if start <= end {
+            (start..=end).collect()
+        } else {
+            (end..=start).rev().collect()
+        };
+
+        heights
+            .into_iter()
+            .map(|height| {
+                self.blocks
+                    .get(&height)
+                    .cloned()
+                    .ok_or_else(|| Status::not_found(format!("Block {} not found", height)))
+            })
+            .collect()
+    }
+
+    fn taddress_txids(
+        &self,
+        filter: TransparentAddressBlockFilter,
+    ) -> Result<Vec<RawTransaction>, Status> {
+        let (start, end) = match &filter.range {
+            Some(range) => range_heights(range)?,
+            None => (0, u64::MAX),
+        };
+
+        Ok(self
+            .taddress_txs
+            .get(&filter.address)
+            .into_iter()
+            .flatten()
+            .filter(|tx| tx.height >= start && tx.height <= end)
+            .cloned()
+            .collect())
+    }
+}
+
+/// Replays the blocks above `tree_state` to get the tree state at each of their heights.
+fn derive_tree_states(
+    tree_state: &TreeState,
+    blocks: &BTreeMap<u64, CompactBlock>,
+) -> Result<BTreeMap<u64, TreeState>, WalletError> {
+    let invalid = |e: io::Error| WalletError::InvalidFormat(format!("Invalid tree state: {}", e));
+
+    let mut sapling_tree = decode_sapling_tree(tree_state)?;
+    let mut orchard_tree = tree_state.orchard_tree().map_err(invalid)?;
+
+    let mut states = BTreeMap::new();
+    states.insert(tree_state.height, tree_state.clone());
+
+    for height in tree_state.height + 1.. {
+        let block = match blocks.get(&height) {
+            Some(block) => block,
+            None => break,
+        };
+
+        for node in sapling_commitments(block)? {
+            sapling_tree.append(node).map_err(|_| tree_full())?;
+        }
+        for action in block.vtx.iter().flat_map(|tx| &tx.actions) {
+            let cmx = action
+                .cmx
+                .as_slice()
+                .try_into()
+                .ok()
+                .and_then(|cmx| Option::from(ExtractedNoteCommitment::from_bytes(cmx)))
+                .ok_or_else(|| {
+                    WalletError::InvalidFormat(format!("Invalid cmx in block {}", height))
+                })?;
+            orchard_tree
+                .append(MerkleHashOrchard::from_cmx(&cmx))
+                .map_err(|_| tree_full())?;
+        }
+
+        let mut sapling = vec![];
+        write_commitment_tree(&sapling_tree, &mut sapling)?;
+        let mut orchard = vec![];
+        write_commitment_tree(&orchard_tree, &mut orchard)?;
+
+        states.insert(
+            height,
+            TreeState {
+                network: tree_state.network.clone(),
+                height,
+                hash: block.hash().to_string(),
+                time: block.time,
+                sapling_tree: hex::encode(sapling),
+                orchard_tree: hex::encode(orchard),
+            },
+        );
+    }
+
+    Ok(states)
+}
+
+fn tree_full() -> WalletError {
+    WalletError::InvalidFormat("A commitment tree is full".to_string())
+}
+
+fn range_heights(range: &BlockRange) -> Result<(u64, u64), Status> {
+    match (&range.start, &range.end) {
+        (Some(start), Some(end)) => Ok((start.height, end.height)),
+        _ => Err(Status::invalid_argument("Incomplete block range")),
+    }
+}
+
+/// An in-process lightwalletd serving a [`MockChain`].
+///
+/// Implements `GetLatestBlock`, `GetBlock`, `GetBlockRange`, `GetTaddressTxids`, `GetTreeState`
+/// and `GetLightdInfo`. The other methods of `CompactTxStreamer` answer `Unimplemented`.
+#[derive(Debug, Clone)]
+pub struct MockLightwalletd {
+    chain: Arc<MockChain>,
+}
+
+impl MockLightwalletd {
+    pub fn new(chain: MockChain) -> Self {
+        Self {
+            chain: Arc::new(chain),
+        }
+    }
+
+    pub fn chain(&self) -> &MockChain {
+        &self.chain
+    }
+
+    /// Serves the mock on `listener` until the task is dropped.
+    pub async fn serve(self, listener: TcpListener) -> Result<(), WalletError> {
+        Server::builder()
+            .add_service(self)
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .map_err(rpc_error)
+    }
+
+    /// Serves the mock on a random local port in the background, and returns its address.
+    pub async fn spawn(self) -> io::Result<(SocketAddr, JoinHandle<Result<(), WalletError>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        Ok((addr, tokio::spawn(self.serve(listener))))
+    }
+}
+
+impl NamedService for MockLightwalletd {
+    const NAME: &'static str = "cash.z.wallet.sdk.rpc.CompactTxStreamer";
+}
+
+impl<B> Service<http::Request<B>> for MockLightwalletd
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let chain = self.chain.clone();
+        let method = req
+            .uri()
+            .path()
+            .strip_prefix("/cash.z.wallet.sdk.rpc.CompactTxStreamer/")
+            .unwrap_or_default()
+            .to_string();
+
+        match method.as_str() {
+            "GetLatestBlock" => unary(req, move |r| chain.latest_block(r)),
+            "GetBlock" => unary(req, move |r| chain.block(r)),
+            "GetBlockRange" => server_streaming(req, move |r| chain.block_range(r)),
+            "GetTaddressTxids" => server_streaming(req, move |r| chain.taddress_txids(r)),
+            "GetTreeState" => unary(req, move |r| chain.tree_state(r)),
+            "GetLightdInfo" => unary(req, move |r| chain.lightd_info(r)),
+            _ => Box::pin(async move {
+                let mut response = http::Response::new(empty_body());
+                let headers = response.headers_mut();
+                headers.insert(
+                    Status::GRPC_STATUS,
+                    (tonic::Code::Unimplemented as i32).into(),
+                );
+                headers.insert(
+                    http::header::CONTENT_TYPE,
+                    tonic::metadata::GRPC_CONTENT_TYPE,
+                );
+                Ok(response)
+            }),
+        }
+    }
+}
+
+/// Adapts a synchronous handler to a unary gRPC method.
+struct Unary<F>(F);
+
+impl<Req, Res, F> UnaryService<Req> for Unary<F>
+where
+    F: FnMut(Req) -> Result<Res, Status>,
+{
+    type Response = Res;
+    type Future = Ready<Result<Response<Res>, Status>>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        ready((self.0)(request.into_inner()).map(Response::new))
+    }
+}
+
+/// Adapts a synchronous handler to a server-streaming gRPC method.
+struct Streaming<F>(F);
+
+impl<Req, Res, F> ServerStreamingService<Req> for Streaming<F>
+where
+    F: FnMut(Req) -> Result<Vec<Res>, Status>,
+    Res: Send + 'static,
+{
+    type Response = Res;
+    type ResponseStream = Iter<std::vec::IntoIter<Result<Res, Status>>>;
+    type Future = Ready<Result<Response<Self::ResponseStream>, Status>>;
+
+    fn call(&mut self, request: Request<Req>) -> Self::Future {
+        ready((self.0)(request.into_inner()).map(|items| {
+            let items: Vec<_> = items.into_iter().map(Ok).collect();
+            Response::new(iter(items))
+        }))
+    }
+}
+
+fn unary<B, Req, Res, F>(
+    req: http::Request<B>,
+    handler: F,
+) -> BoxFuture<http::Response<BoxBody>, Infallible>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Req: prost::Message + Default + Send + 'static,
+    Res: prost::Message + Send + 'static,
+    F: FnMut(Req) -> Result<Res, Status> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::<Res, Req>::default());
+        Ok(grpc.unary(Unary(handler), req).await)
+    })
+}
+
+fn server_streaming<B, Req, Res, F>(
+    req: http::Request<B>,
+    handler: F,
+) -> BoxFuture<http::Response<BoxBody>, Infallible>
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    Req: prost::Message + Default + Send + 'static,
+    Res: prost::Message + Send + 'static,
+    F: FnMut(Req) -> Result<Vec<Res>, Status> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::<Res, Req>::default());
+        Ok(grpc.server_streaming(Streaming(handler), req).await)
+    })
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use tracing::instrument;
+use zcash_encoding::{Optional, Vector};
+
+use crate::{
+    error::WalletError,
+    reader::WalletReader,
+    zwl::{ZwlWallet, write_string, write_tree},
+};
+
+/// Serializes a [`ZwlWallet`] back into a `zecwallet-light-wallet.dat` file.
+///
+/// Wallets are always written in the latest format understood by [`WalletReader`], regardless
+/// of the version they were read from.
+pub struct WalletWriter;
+
+impl WalletWriter {
+    /// Writes `wallet` to `path`, replacing it atomically.
+    ///
+    /// The wallet is serialized first and written to a `.tmp` file next to `path`, which is only
+    /// renamed over `path` once synced to disk, so a failure never leaves a truncated wallet.
+    #[instrument(level = "info", name = "WalletWriter::write", skip_all, fields(path = %path.as_ref().display()))]
+    pub fn write(path: impl AsRef<Path>, wallet: &ZwlWallet) -> Result<(), WalletError> {
+        let path = path.as_ref();
+        let bytes = Self::to_bytes(wallet)?;
+
+        let mut tmp_name = path
+            .file_name()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} isn't a file path", path.display()),
+                )
+            })?
+            .to_os_string();
+        tmp_name.push(".tmp");
+        let tmp = path.with_file_name(tmp_name);
+
+        let written = File::create(&tmp)
+            .and_then(|mut file| {
+                file.write_all(&bytes)?;
+                file.sync_all()
+            })
+            .and_then(|()| fs::rename(&tmp, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        Ok(written?)
+    }
+
+    #[instrument(level = "info", name = "WalletWriter::write_to_writer", skip_all, err)]
+    pub fn write_to_writer<W: Write>(mut writer: W, wallet: &ZwlWallet) -> Result<(), WalletError> {
+        let chain_name = wallet.chain_name.name().ok_or_else(|| {
+            WalletError::InvalidFormat("Can't write a wallet for an unknown chain".to_string())
+        })?;
+
+        writer.write_u64::<LittleEndian>(WalletReader::max_supported_wallet_version())?;
+
+        wallet.keys.write(&mut writer)?;
+
+        Vector::write(&mut writer, &wallet.blocks, |w, b| b.write(w))?;
+
+        wallet.transactions.write(&mut writer)?;
+
+        write_string(&mut writer, chain_name)?;
+
+        wallet.wallet_options.write(&mut writer)?;
+
+        writer.write_u64::<LittleEndian>(wallet.birthday)?;
+
+        Optional::write(&mut writer, wallet.verified_tree.as_ref(), |w, t| {
+            use prost::Message;
+            Vector::write(w, &t.encode_to_vec(), |w, b| w.write_u8(*b))
+        })?;
+
+        wallet.price_info.write(&mut writer)?;
+
+        Optional::write(&mut writer, wallet.orchard_witnesses.as_ref(), |w, t| {
+            write_tree(w, t)
+        })?;
+
+        Ok(())
+    }
+
+    /// Serializes `wallet` into a byte vector.
+    pub fn to_bytes(wallet: &ZwlWallet) -> Result<Vec<u8>, WalletError> {
+        let mut buf = vec![];
+        Self::write_to_writer(&mut buf, wallet)?;
+        Ok(buf)
+    }
+
+    /// Writes `wallet` and checks that the result reads back, without touching the disk.
+    pub fn verify_roundtrip(wallet: &ZwlWallet) -> Result<ZwlWallet, WalletError> {
+        let bytes = Self::to_bytes(wallet)?;
+        WalletReader::read_from_reader(io::Cursor::new(bytes))
+    }
+}
@@ -30,13 +30,36 @@
 //!     2. The second account containing only Sapling and Transparent keys.
 //!
 
+pub mod addresses;
+pub mod anonymize;
+pub mod balances;
+pub mod birthday;
 pub mod block;
 pub mod data;
+pub mod diff;
+pub mod doctor;
+pub mod edit;
+pub mod graph;
+pub mod history;
+pub mod import;
 pub mod keys;
+pub mod merge;
+#[cfg(feature = "online")]
+pub mod online;
 pub mod orchard_data;
+pub mod orchard_tree;
+pub mod owner;
+pub mod privacy;
+pub mod repair;
+pub mod rewind;
 pub mod sapling_data;
+pub mod sapling_witness;
+pub mod scan;
+pub mod sync;
 pub mod transactions;
+pub mod trim;
 pub mod wallet_txns;
+pub mod watch_only;
 
 use bip0039::{English, Mnemonic};
 
@@ -53,9 +76,12 @@ use tracing::instrument;
 use zcash_client_backend::proto::service::TreeState;
 use zcash_encoding::{Optional, Vector};
 use zcash_keys::keys::{UnifiedFullViewingKey, UnifiedSpendingKey};
-use zcash_primitives::{consensus::MainNetwork, zip32::AccountId};
+use zcash_primitives::{
+    consensus::{MainNetwork, NetworkConstants},
+    zip32::AccountId,
+};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::{
     collections::BTreeMap,
     fmt::Display,
@@ -64,9 +90,14 @@ use std::{
 
 use orchard_data::{HashSer, MERKLE_DEPTH, SER_V1};
 
+use crate::error::WalletError;
+use crate::reader::WalletReader;
 use crate::zwl::{
     data::ChainType,
     keys::{orchard::WalletOKey, sapling::WalletZKey, transparent::WalletTKey},
+    orchard_tree::orchard_tree_from_frontier,
+    sapling_witness::decode_sapling_tree,
+    transactions::MAX_REORG,
     wallet_txns::WalletTxns,
 };
 
@@ -87,6 +118,14 @@ pub struct ZwlWallet {
 
 // TODO@dorianvp: double check impl block
 impl ZwlWallet {
+    /// Height of the latest synced block, from the cached blocks or the verified tree.
+    pub fn latest_height(&self) -> Option<u64> {
+        self.blocks
+            .first()
+            .map(|b| b.height)
+            .or_else(|| self.verified_tree.as_ref().map(|t| t.height))
+    }
+
     pub fn get_wallet_keys(&self, idx: usize) -> io::Result<Keys> {
         // construct a WalletTKey assosiated with hd index `idx`
         let tkeys: Vec<WalletTKey> = self
@@ -143,134 +182,73 @@ impl ZwlWallet {
         Ok(ufvk)
     }
 
-    // #[allow(deprecated)]
-    // pub fn from_seed_phrase(phrase: &str, num_addr: u32) -> io::Result<Wallet> {
-    //     let mnemonic = <Mnemonic<English>>::from_phrase(phrase).expect("Invalid mnemonic phrase");
-    //     let seed = mnemonic.to_seed("");
-
-    //     let mut accounts = vec![];
-
-    //     // derive sapling addresses
-    //     for hdkey_num in 0..num_addr {
-    //         // derive extsk
-    //         let extsk = ExtendedSpendingKey::master(&seed);
-
-    //         let (_, addr) = extsk
-    //             .clone()
-    //             .derive_child(ChildIndex::hardened(32))
-    //             .derive_child(ChildIndex::hardened(133))
-    //             .derive_child(ChildIndex::hardened(hdkey_num))
-    //             .default_address();
-
-    //         let fvk = extsk.to_extended_full_viewing_key();
-    //         let z_address = encode_payment_address(HRP_SAPLING_PAYMENT_ADDRESS, &addr);
-
-    //         let zkeys = WalletZKey {
-    //             extsk: Some(extsk),
-    //             extfvk: fvk,
-    //             keytype: WalletZKeyType::HdKey,
-    //             hdkey_num: Some(hdkey_num),
-    //             zaddress: addr,
-
-    //             locked: false,
-    //             enc_key: None,
-    //             nonce: None,
-    //         };
-
-    //         // derive orchard addresses
-    //         let sk = SpendingKey::from_zip32_seed(
-    //             &seed,
-    //             133,
-    //             AccountId::try_from(hdkey_num)
-    //                 .expect("invalid account id")
-    //                 .into(),
-    //         )
-    //         .expect("invalid zip32 seed");
-    //         let fvk = orchard_old::keys::FullViewingKey::from(&sk);
-
-    //         // TODO: Revisit if it's possible to not do this
-    //         let old_address: orchard_old::Address =
-    //             fvk.address_at(0u64, orchard_old::keys::Scope::External);
-
-    //         let new_address = NewAddress::from_old(old_address);
-    //         let o_address = UnifiedAddress::from_receivers(Some(new_address), None, None)
-    //             .expect("Invalud unified address");
-
-    //         let okeys = WalletOKey {
-    //             sk: Some(sk),
-    //             fvk: fvk,
-    //             keytype: WalletOKeyType::HdKey,
-    //             hdkey_num: Some(hdkey_num),
-    //             unified_address: o_address,
-
-    //             locked: false,
-    //             enc_key: None,
-    //             nonce: None,
-    //         };
-
-    //         // Derive transparent addresses
-    //         let priv_key = AccountPrivKey::from_seed(
-    //             &MainNetwork,
-    //             &seed,
-    //             AccountId::try_from(0).expect("invalid account id"),
-    //         )
-    //         .expect("Invalid zip32 seed");
-
-    //         let pk = priv_key
-    //             .derive_external_secret_key(
-    //                 NonHardenedChildIndex::from_index(hdkey_num).expect("Invalid index"),
-    //             )
-    //             .expect("Invalid secret key");
-
-    //         let taddy = priv_key
-    //             .to_account_pubkey()
-    //             .derive_external_ivk()
-    //             .expect("Invalid pubkey")
-    //             .derive_address(
-    //                 NonHardenedChildIndex::from_index(hdkey_num).expect("Invalid index"),
-    //             )
-    //             .expect("Invalid transparent address.");
-
-    //         let t_address = encode_transparent_address(
-    //             &B58_PUBKEY_ADDRESS_PREFIX,
-    //             &B58_SCRIPT_ADDRESS_PREFIX,
-    //             &taddy,
-    //         );
-
-    //         let tkeys = WalletTKey {
-    //             pk: Some(pk),
-    //             keytype: WalletTKeyType::HdKey,
-    //             hdkey_num: Some(hdkey_num),
-    //             address: t_address,
-
-    //             locked: false,
-    //             enc_key: None,
-    //             nonce: None,
-    //         };
-
-    //         accounts.push(WalletAccount {
-    //             name: format!("Account {}", hdkey_num + 1),
-    //             seed: Some(seed.to_vec()),
-    //             birthday: BlockHeight::from_u32(0),
-    //             keys: Keys {
-    //                 tkeys: tkeys,
-    //                 zkeys: zkeys,
-    //                 okeys: okeys,
-
-    //                 encrypted: false,
-    //                 enc_seed: [0u8; 48],
-    //                 nonce: Vec::new(),
-    //                 seed: [0u8; 32],
-    //             },
-    //         })
-    //     }
-
-    //     Ok(Wallet {
-    //         wallet_name: "ZwlWallet".to_string(),
-    //         version: 25,
-    //         accounts,
-    //     })
-    // }
+    /// Creates a new wallet from a 24-word seed phrase, with `num_addresses` HD keys in each
+    /// pool.
+    ///
+    /// With a `tree_state`, the wallet starts syncing from the block after it, so it must be the
+    /// tree state of the block just below the birthday.
+    pub fn from_seed_phrase(
+        phrase: &str,
+        num_addresses: u32,
+        chain_name: ChainType,
+        birthday: u64,
+        tree_state: Option<TreeState>,
+    ) -> Result<Self, WalletError> {
+        let network = chain_name.require_network()?;
+
+        let mnemonic = <Mnemonic<English>>::from_phrase(phrase)
+            .map_err(|e| WalletError::InvalidFormat(format!("Invalid seed phrase: {}", e)))?;
+        let seed: [u8; 32] = mnemonic.entropy().try_into().map_err(|_| {
+            WalletError::InvalidFormat("ZecWallet Lite seed phrases have 24 words".to_string())
+        })?;
+        let bip39_seed = mnemonic.to_seed("");
+        let coin_type = network.coin_type();
+
+        let mut keys = Keys {
+            encrypted: false,
+            enc_seed: [0u8; 48],
+            nonce: vec![],
+            seed,
+            zkeys: vec![],
+            tkeys: vec![],
+            okeys: vec![],
+        };
+        for hdkey_num in 0..num_addresses {
+            keys.zkeys
+                .push(WalletZKey::new_hdkey(&bip39_seed, coin_type, hdkey_num));
+            keys.okeys
+                .push(WalletOKey::new_hdkey(&bip39_seed, coin_type, hdkey_num)?);
+            keys.tkeys
+                .push(WalletTKey::new_hdkey(&network, &bip39_seed, hdkey_num)?);
+        }
+
+        let orchard_witnesses = match &tree_state {
+            Some(tree_state) if tree_state.height + 1 != birthday => {
+                return Err(WalletError::InvalidFormat(format!(
+                    "The tree state at {} isn't the one of the block below the birthday {}",
+                    tree_state.height, birthday
+                )));
+            }
+            Some(tree_state) => {
+                decode_sapling_tree(tree_state)?;
+                Some(orchard_tree_from_frontier(tree_state, MAX_REORG)?)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            version: WalletReader::max_supported_wallet_version(),
+            keys,
+            blocks: vec![],
+            transactions: WalletTxns::new(),
+            chain_name,
+            wallet_options: WalletOptions::default(),
+            birthday,
+            verified_tree: tree_state,
+            orchard_witnesses,
+            price_info: WalletZecPriceInfo::new(),
+        })
+    }
 }
 
 impl Display for ZwlWallet {
@@ -364,6 +342,12 @@ pub fn read_string<R: ReadBytesExt>(mut reader: R) -> io::Result<String> {
     Ok(str)
 }
 
+pub fn write_string<W: WriteBytesExt>(mut writer: W, s: &str) -> io::Result<()> {
+    // Strings are written as <littleendian> len + bytes
+    writer.write_u64::<LittleEndian>(s.len() as u64)?;
+    writer.write_all(s.as_bytes())
+}
+
 /// Reads a [`BridgeTree`] value from its serialized form.
 ///
 /// [`BridgeTree`] values are expected to have been serialized with a leading version byte. Parsing
@@ -513,3 +497,106 @@ pub fn read_nonempty_frontier_v1<H: HashSer + Clone, R: ReadBytesExt>(
         )
     })
 }
+
+/// Writes a [`BridgeTree`] in the form expected by [`read_tree`].
+///
+/// The leading version is ignored when reading; ZecWallet Lite writes the wallet's own serialized
+/// version there, so we do the same.
+pub fn write_tree<W: WriteBytesExt>(
+    mut writer: W,
+    tree: &BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>,
+) -> io::Result<()> {
+    writer.write_u64::<LittleEndian>(WalletReader::max_supported_wallet_version())?;
+
+    Vector::write(&mut writer, tree.prior_bridges(), |w, b| write_bridge(w, b))?;
+    Optional::write(&mut writer, tree.current_bridge().as_ref(), |w, b| {
+        write_bridge(w, b)
+    })?;
+    Vector::write_sized(
+        &mut writer,
+        tree.witnessed_indices().iter(),
+        |mut w, (pos, i)| {
+            write_position(&mut w, *pos)?;
+            write_usize_leu64(&mut w, *i)
+        },
+    )?;
+    Vector::write(&mut writer, tree.checkpoints(), |w, c| {
+        write_checkpoint_v2(w, c)
+    })?;
+    write_usize_leu64(&mut writer, tree.max_checkpoints())
+}
+
+pub fn write_bridge<H: HashSer + Ord, W: WriteBytesExt>(
+    mut writer: W,
+    bridge: &MerkleBridge<H>,
+) -> io::Result<()> {
+    writer.write_u8(SER_V1)?;
+    Optional::write(&mut writer, bridge.prior_position(), |w, p| {
+        write_position(w, p)
+    })?;
+    Vector::write_sized(
+        &mut writer,
+        bridge.auth_fragments().iter(),
+        |mut w, (pos, a)| {
+            write_position(&mut w, *pos)?;
+            write_auth_fragment_v1(w, a)
+        },
+    )?;
+    write_nonempty_frontier_v1(&mut writer, bridge.frontier())
+}
+
+pub fn write_position<W: WriteBytesExt>(mut writer: W, position: Position) -> io::Result<()> {
+    write_usize_leu64(&mut writer, position.into())
+}
+
+/// Writes a usize value encoded as a u64 in little-endian order. See [`read_leu64_usize`].
+pub fn write_usize_leu64<W: WriteBytesExt>(mut writer: W, value: usize) -> io::Result<()> {
+    // Panic if we get a usize value that can't fit into a u64.
+    writer.write_u64::<LittleEndian>(value.try_into().unwrap())
+}
+
+pub fn write_auth_fragment_v1<H: HashSer, W: WriteBytesExt>(
+    mut writer: W,
+    fragment: &AuthFragment<H>,
+) -> io::Result<()> {
+    write_position(&mut writer, fragment.position())?;
+    write_usize_leu64(&mut writer, fragment.altitudes_observed())?;
+    Vector::write(&mut writer, fragment.values(), |w, a| a.write(w))
+}
+
+pub fn write_checkpoint_v2<W: WriteBytesExt>(
+    mut writer: W,
+    checkpoint: &Checkpoint,
+) -> io::Result<()> {
+    write_usize_leu64(&mut writer, checkpoint.bridges_len())?;
+    writer.write_u8(if checkpoint.is_witnessed() { 1 } else { 0 })?;
+    Vector::write_sized(&mut writer, checkpoint.witnessed().iter(), |w, p| {
+        write_position(w, *p)
+    })?;
+    Vector::write_sized(
+        &mut writer,
+        checkpoint.forgotten().iter(),
+        |mut w, (pos, idx)| {
+            write_position(&mut w, *pos)?;
+            write_usize_leu64(&mut w, *idx)
+        },
+    )
+}
+
+pub fn write_nonempty_frontier_v1<H: HashSer, W: WriteBytesExt>(
+    mut writer: W,
+    frontier: &NonEmptyFrontier<H>,
+) -> io::Result<()> {
+    write_position(&mut writer, frontier.position())?;
+    match frontier.leaf() {
+        Leaf::Left(a) => {
+            a.write(&mut writer)?;
+            Optional::write(&mut writer, None, |w, n: &H| n.write(w))?;
+        }
+        Leaf::Right(a, b) => {
+            a.write(&mut writer)?;
+            Optional::write(&mut writer, Some(b), |w, n| n.write(w))?;
+        }
+    }
+    Vector::write(&mut writer, frontier.ommers(), |w, e| e.write(w))
+}
@@ -0,0 +1,183 @@
+//! # Enumerating addresses
+//!
+//! ZecWallet Lite stores a single address per key, but funds may have been sent to other
+//! addresses of the same keys. [`ZwlWallet::enumerate_addresses`] derives the next addresses of
+//! each key:
+//!
+//! - the next valid Sapling diversifiers after the default address of each `extfvk`,
+//! - the Orchard addresses at the next indices of each `fvk`,
+//! - the transparent addresses after the highest HD key number, which need the seed, and so the
+//!   password of an encrypted wallet.
+//!
+//! Each address is flagged as used if a note or Utxo of the wallet was received at it, as
+//! attributed by [`ZwlWallet::address_balances`].
+
+use std::{collections::BTreeSet, fmt};
+
+use bip0039::{English, Mnemonic};
+use orchard_old::keys::Scope;
+use zcash_keys::encoding::encode_payment_address_p;
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        import::decrypt_seed,
+        keys::{orchard::orchard_unified_address, transparent::WalletTKey},
+        scan::Pool,
+    },
+};
+
+/// An address derived from one of the wallet's keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedAddress {
+    pub pool: Pool,
+    /// The index of the key in its pool's keys, or `None` for transparent addresses, which are
+    /// derived from the seed.
+    pub key: Option<usize>,
+    /// The diversifier index of a shielded address, or the HD key number of a transparent one.
+    pub index: u128,
+    pub address: String,
+    pub used: bool,
+}
+
+impl fmt::Display for DerivedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.key {
+            Some(key) => write!(f, "{} key {} #{}", self.pool, key, self.index)?,
+            None => write!(f, "{} #{}", self.pool, self.index)?,
+        }
+        write!(f, " {}", self.address)?;
+        if self.used {
+            write!(f, " (used)")?;
+        }
+        Ok(())
+    }
+}
+
+/// What [`ZwlWallet::enumerate_addresses`] derived.
+#[derive(Debug, Clone, Default)]
+pub struct AddressEnumeration {
+    pub addresses: Vec<DerivedAddress>,
+    /// Why the transparent addresses weren't derived, if they weren't.
+    pub transparent_skipped: Option<String>,
+}
+
+impl AddressEnumeration {
+    pub fn used(&self) -> impl Iterator<Item = &DerivedAddress> {
+        self.addresses.iter().filter(|a| a.used)
+    }
+}
+
+impl fmt::Display for AddressEnumeration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for address in &self.addresses {
+            writeln!(f, "- {}", address)?;
+        }
+        if let Some(reason) = &self.transparent_skipped {
+            writeln!(f, "! Transparent addresses skipped: {}", reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Derives the next `count` addresses of each shielded key, and `count` transparent
+    /// addresses after the highest HD key number. `password` is only needed for the transparent
+    /// addresses of an encrypted wallet.
+    pub fn enumerate_addresses(
+        &self,
+        count: u32,
+        password: Option<&str>,
+    ) -> Result<AddressEnumeration, WalletError> {
+        let network = self.chain_name.require_network()?;
+        let used: BTreeSet<String> = self
+            .address_balances()
+            .into_iter()
+            .filter(|b| b.notes > 0)
+            .map(|b| b.address)
+            .collect();
+        let mut enumeration = AddressEnumeration::default();
+        let mut push = |pool: Pool, key: Option<usize>, index: u128, address: String| {
+            enumeration.addresses.push(DerivedAddress {
+                pool,
+                key,
+                index,
+                used: used.contains(&address),
+                address,
+            });
+        };
+
+        for (i, zkey) in self.keys.zkeys.iter().enumerate() {
+            let dfvk = zkey.extfvk.to_diversifiable_full_viewing_key();
+            let mut index = dfvk.default_address().0;
+            for _ in 0..count {
+                if index.increment().is_err() {
+                    break;
+                }
+                let Some((found, address)) = dfvk.find_address(index) else {
+                    break;
+                };
+                index = found;
+                push(
+                    Pool::Sapling,
+                    Some(i),
+                    u128::from(found),
+                    encode_payment_address_p(&network, &address),
+                );
+            }
+        }
+
+        for (i, okey) in self.keys.okeys.iter().enumerate() {
+            for index in 1..=u64::from(count) {
+                let address = okey.fvk.address_at(index, Scope::External);
+                push(
+                    Pool::Orchard,
+                    Some(i),
+                    index.into(),
+                    orchard_unified_address(address).encode(&network),
+                );
+            }
+        }
+
+        match self.seed(password)? {
+            Some(seed) => {
+                let bip39_seed = <Mnemonic<English>>::from_entropy(seed)
+                    .map_err(|e| WalletError::InvalidFormat(e.to_string()))?
+                    .to_seed("");
+                let first = self
+                    .keys
+                    .tkeys
+                    .iter()
+                    .filter_map(|tkey| tkey.hdkey_num)
+                    .max()
+                    .map_or(0, |n| n + 1);
+                for n in first..first.saturating_add(count) {
+                    let tkey = WalletTKey::new_hdkey(&network, &bip39_seed, n)?;
+                    push(Pool::Transparent, None, n.into(), tkey.address);
+                }
+            }
+            None => {
+                enumeration.transparent_skipped = Some(if self.keys.encrypted {
+                    "the wallet is encrypted, its password is needed".to_string()
+                } else {
+                    "the wallet has no seed".to_string()
+                });
+            }
+        }
+
+        Ok(enumeration)
+    }
+
+    /// The seed entropy, decrypted with `password` if the wallet is encrypted and locked.
+    fn seed(&self, password: Option<&str>) -> Result<Option<[u8; 32]>, WalletError> {
+        if self.keys.seed != [0u8; 32] {
+            return Ok(Some(self.keys.seed));
+        }
+        if !self.keys.encrypted || password.is_none() {
+            return Ok(None);
+        }
+
+        decrypt_seed(&self.keys, password).map(|(_, seed)| Some(seed))
+    }
+}
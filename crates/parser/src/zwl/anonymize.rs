@@ -0,0 +1,919 @@
+//! # Anonymized wallets
+//!
+//! [`ZwlWallet::anonymize`] produces a copy of a wallet that can be attached to a bug report. It
+//! keeps every structural property of the file (the version, the number of keys and their types,
+//! the length of every vector, which optional fields are present and the shape of the commitment
+//! trees) and replaces what identifies the user:
+//!
+//! - the seed and every key are replaced with random valid keys, HD keys being derived from the
+//!   new seed so that they still match it,
+//! - addresses are replaced with the addresses of the new keys,
+//! - amounts get random noise, zero staying zero,
+//! - memos are replaced with filler of the same length,
+//! - txids, nullifiers, note commitments, block hashes and tree hashes are replaced with random
+//!   values, consistently across the file, the cached blocks and the verified tree included,
+//! - the ciphertexts of the cached blocks are replaced with random bytes, and their headers are
+//!   dropped.
+//!
+//! Heights, dates, the shape of the cached blocks and the positions of notes in the commitment
+//! trees are kept, as they are needed to reproduce most bugs. The hashes no longer match the
+//! chain, so witness validation fails on an anonymized wallet, and the cached blocks can't be
+//! scanned. Keeping the cached blocks and the verified tree as they are is opt-in, since they
+//! link the wallet's notes to the chain.
+//!
+//! The [`AnonymizationMap`] links the anonymized addresses, txids and amounts back to the
+//! original ones, to make sense of what the bug report refers to. It's meant to stay with the
+//! user.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use bip0039::{English, Mnemonic};
+use incrementalmerkletree::bridgetree::{
+    AuthFragment, BridgeTree, Leaf, MerkleBridge, NonEmptyFrontier,
+};
+use orchard_old::{
+    keys::{FullViewingKey, Scope as OrchardScope},
+    note::{Nullifier as OrchardNullifier, RandomSeed},
+    tree::MerkleHashOrchard,
+    value::NoteValue as OrchardNoteValue,
+};
+use prost::Message;
+use rand::{CryptoRng, Rng, RngCore, rngs::OsRng};
+use sapling_crypto::{
+    CommitmentTree, IncrementalWitness, Node, Rseed, value::NoteValue,
+    zip32::ExtendedFullViewingKey,
+};
+use zcash_client_backend::proto::{
+    compact_formats::{
+        CompactBlock, CompactOrchardAction, CompactSaplingOutput, CompactSaplingSpend, CompactTx,
+    },
+    service::TreeState,
+};
+use zcash_keys::encoding::{decode_transparent_address, encode_payment_address_p};
+use zcash_primitives::{
+    consensus::{Network, NetworkConstants},
+    memo::{Memo, MemoBytes},
+    merkle_tree::write_commitment_tree,
+    transaction::TxId,
+    zip32::Scope,
+};
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        block::CompactBlockData,
+        keys::{Keys, orchard::WalletOKey, sapling::WalletZKey, transparent::WalletTKey},
+        orchard_data::{MERKLE_DEPTH, OrchardNoteData},
+        orchard_tree::{encode_orchard_frontier, orchard_tree_from_frontier},
+        sapling_data::SaplingNoteData,
+        sapling_witness::decode_sapling_tree,
+        transactions::{OutgoingTxMetadata, Utxo, WalletTx, WitnessCache},
+        wallet_txns::WalletTxns,
+    },
+};
+
+/// An amount that got noise, and where it is in the anonymized wallet.
+#[derive(Debug, Clone)]
+pub struct ValueChange {
+    pub txid: TxId,
+    pub what: String,
+    pub original: u64,
+    pub anonymized: u64,
+}
+
+/// Links an anonymized wallet back to the original one.
+#[derive(Debug, Clone, Default)]
+pub struct AnonymizationMap {
+    /// Original address to anonymized address.
+    pub addresses: BTreeMap<String, String>,
+    /// Original txid to anonymized txid.
+    pub txids: BTreeMap<TxId, TxId>,
+    pub values: Vec<ValueChange>,
+}
+
+impl fmt::Display for AnonymizationMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Keep this file private: it links the anonymized wallet to the original one."
+        )?;
+
+        writeln!(f, "\nAddresses (original -> anonymized):")?;
+        for (original, anonymized) in &self.addresses {
+            writeln!(f, "  {} -> {}", original, anonymized)?;
+        }
+
+        writeln!(f, "\nTransactions (original -> anonymized):")?;
+        for (original, anonymized) in &self.txids {
+            writeln!(f, "  {} -> {}", original, anonymized)?;
+        }
+
+        writeln!(f, "\nAmounts (original -> anonymized):")?;
+        for change in &self.values {
+            writeln!(
+                f,
+                "  {} {}: {} -> {}",
+                change.txid, change.what, change.original, change.anonymized
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Returns an anonymized copy of the wallet, and the map back to this one. With
+    /// `keep_blocks`, the cached blocks and the verified tree are copied as they are.
+    pub fn anonymize(
+        &self,
+        keep_blocks: bool,
+    ) -> Result<(ZwlWallet, AnonymizationMap), WalletError> {
+        self.anonymize_with_rng(keep_blocks, &mut OsRng)
+    }
+
+    /// Like [`ZwlWallet::anonymize`], drawing the random values from `rng`.
+    pub fn anonymize_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        keep_blocks: bool,
+        rng: &mut R,
+    ) -> Result<(ZwlWallet, AnonymizationMap), WalletError> {
+        let network = self.chain_name.network().unwrap_or(Network::MainNetwork);
+        let mut anonymizer = Anonymizer::new(rng, network);
+
+        // Keys go first, so that the notes find the new keys.
+        let keys = anonymizer.keys(&self.keys)?;
+
+        let txns = &self.transactions;
+        let mut current = HashMap::new();
+        for wtx in txns.current.values() {
+            let wtx = anonymizer.wallet_tx(wtx)?;
+            current.insert(wtx.txid, wtx);
+        }
+        let mut mempool = HashMap::new();
+        for wtx in txns.mempool.values() {
+            let wtx = anonymizer.wallet_tx(wtx)?;
+            mempool.insert(wtx.txid, wtx);
+        }
+        let transactions = WalletTxns {
+            current,
+            last_txid: txns.last_txid.map(|txid| anonymizer.txid(&txid)),
+            mempool,
+        };
+
+        let orchard_witnesses = self
+            .orchard_witnesses
+            .as_ref()
+            .map(|tree| anonymizer.orchard_tree(tree))
+            .transpose()?;
+
+        let (blocks, verified_tree) = if keep_blocks {
+            (self.blocks.clone(), self.verified_tree.clone())
+        } else {
+            let mut blocks = vec![];
+            for block in &self.blocks {
+                blocks.push(anonymizer.block(block)?);
+            }
+            let verified_tree = self
+                .verified_tree
+                .as_ref()
+                .map(|tree_state| anonymizer.tree_state(tree_state))
+                .transpose()?;
+            (blocks, verified_tree)
+        };
+
+        let wallet = ZwlWallet {
+            keys,
+            blocks,
+            transactions,
+            verified_tree,
+            orchard_witnesses,
+            ..self.clone()
+        };
+
+        Ok((wallet, anonymizer.map))
+    }
+}
+
+struct Anonymizer<'a, R> {
+    rng: &'a mut R,
+    network: Network,
+    map: AnonymizationMap,
+    sapling_keys: Vec<(ExtendedFullViewingKey, ExtendedFullViewingKey)>,
+    orchard_keys: Vec<(FullViewingKey, FullViewingKey)>,
+    sapling_nullifiers: HashMap<[u8; 32], sapling_crypto::Nullifier>,
+    orchard_nullifiers: HashMap<[u8; 32], OrchardNullifier>,
+    sapling_nodes: HashMap<[u8; 32], Node>,
+    orchard_hashes: HashMap<[u8; 32], MerkleHashOrchard>,
+    block_hashes: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl<'a, R: RngCore + CryptoRng> Anonymizer<'a, R> {
+    fn new(rng: &'a mut R, network: Network) -> Self {
+        Self {
+            rng,
+            network,
+            map: AnonymizationMap::default(),
+            sapling_keys: vec![],
+            orchard_keys: vec![],
+            sapling_nullifiers: HashMap::new(),
+            orchard_nullifiers: HashMap::new(),
+            sapling_nodes: HashMap::new(),
+            orchard_hashes: HashMap::new(),
+            block_hashes: HashMap::new(),
+        }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut bytes = [0u8; N];
+        self.rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn random_vec(&mut self, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        self.rng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    /// Random bytes that encode a Pallas or BLS12-381 scalar field element: both fields are
+    /// larger than 2^254.
+    fn field_bytes(&mut self) -> [u8; 32] {
+        let mut bytes: [u8; 32] = self.bytes();
+        bytes[31] &= 0x3f;
+        bytes
+    }
+
+    fn value(&mut self, txid: TxId, what: String, original: u64) -> u64 {
+        if original == 0 {
+            return 0;
+        }
+        let anonymized = ((original as f64 * self.rng.gen_range(0.5..1.5)) as u64).max(1);
+        self.map.values.push(ValueChange {
+            txid,
+            what,
+            original,
+            anonymized,
+        });
+        anonymized
+    }
+
+    fn txid(&mut self, txid: &TxId) -> TxId {
+        if let Some(anonymized) = self.map.txids.get(txid) {
+            return *anonymized;
+        }
+        let anonymized = TxId::from_bytes(self.bytes());
+        self.map.txids.insert(*txid, anonymized);
+        anonymized
+    }
+
+    fn spent(&mut self, spent: &Option<(TxId, u32)>) -> Option<(TxId, u32)> {
+        spent.map(|(txid, height)| (self.txid(&txid), height))
+    }
+
+    fn sapling_nullifier(&mut self, nf: &sapling_crypto::Nullifier) -> sapling_crypto::Nullifier {
+        if let Some(anonymized) = self.sapling_nullifiers.get(&nf.0) {
+            return *anonymized;
+        }
+        let anonymized = sapling_crypto::Nullifier(self.bytes());
+        self.sapling_nullifiers.insert(nf.0, anonymized);
+        anonymized
+    }
+
+    fn orchard_nullifier(&mut self, nf: &OrchardNullifier) -> OrchardNullifier {
+        let bytes = nf.to_bytes();
+        if let Some(anonymized) = self.orchard_nullifiers.get(&bytes) {
+            return *anonymized;
+        }
+        let anonymized = loop {
+            if let Some(nf) = OrchardNullifier::from_bytes(&self.field_bytes()).into() {
+                break nf;
+            }
+        };
+        self.orchard_nullifiers.insert(bytes, anonymized);
+        anonymized
+    }
+
+    fn sapling_node(&mut self, node: &Node) -> Node {
+        let bytes = node.to_bytes();
+        if let Some(anonymized) = self.sapling_nodes.get(&bytes) {
+            return *anonymized;
+        }
+        let anonymized = loop {
+            if let Some(node) = Node::from_bytes(self.field_bytes()).into() {
+                break node;
+            }
+        };
+        self.sapling_nodes.insert(bytes, anonymized);
+        anonymized
+    }
+
+    fn orchard_hash(&mut self, hash: &MerkleHashOrchard) -> MerkleHashOrchard {
+        let bytes = hash.to_bytes();
+        if let Some(anonymized) = self.orchard_hashes.get(&bytes) {
+            return *anonymized;
+        }
+        let anonymized = loop {
+            if let Some(hash) = MerkleHashOrchard::from_bytes(&self.field_bytes()).into() {
+                break hash;
+            }
+        };
+        self.orchard_hashes.insert(bytes, anonymized);
+        anonymized
+    }
+
+    fn record_address(&mut self, original: String, anonymized: String) {
+        self.map.addresses.insert(original, anonymized);
+    }
+
+    /// Replaces a transparent address that is not one of the wallet's with a random one.
+    fn taddress(&mut self, address: &str) -> Result<String, WalletError> {
+        if let Some(anonymized) = self.map.addresses.get(address) {
+            return Ok(anonymized.clone());
+        }
+        let seed: [u8; 64] = self.bytes();
+        let anonymized = WalletTKey::new_hdkey(&self.network, &seed, 0)?.address;
+        self.record_address(address.to_string(), anonymized.clone());
+        Ok(anonymized)
+    }
+
+    /// Replaces an address we can't derive, keeping its prefix and length.
+    fn other_address(&mut self, address: &str) -> String {
+        if let Some(anonymized) = self.map.addresses.get(address) {
+            return anonymized.clone();
+        }
+        const CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+        let prefix: String = address.chars().take(2).collect();
+        let filler: String = (prefix.chars().count()..address.chars().count())
+            .map(|_| CHARSET[self.rng.gen_range(0..CHARSET.len())] as char)
+            .collect();
+        let anonymized = prefix + &filler;
+        self.record_address(address.to_string(), anonymized.clone());
+        anonymized
+    }
+
+    fn memo(&mut self, memo: &Memo) -> Result<Memo, WalletError> {
+        let invalid = |e: &dyn fmt::Display| {
+            WalletError::InvalidFormat(format!("Couldn't replace a memo: {}", e))
+        };
+        Ok(match memo {
+            Memo::Empty => Memo::Empty,
+            Memo::Text(text) => "x".repeat(text.len()).parse().map_err(|e| invalid(&e))?,
+            Memo::Arbitrary(_) => Memo::Arbitrary(Box::new(self.bytes())),
+            Memo::Future(bytes) => {
+                let mut filler: [u8; 512] = self.bytes();
+                filler[0] = bytes.as_slice()[0];
+                Memo::Future(MemoBytes::from_bytes(&filler).map_err(|e| invalid(&e))?)
+            }
+        })
+    }
+
+    fn keys(&mut self, keys: &Keys) -> Result<Keys, WalletError> {
+        let coin_type = self.network.coin_type();
+
+        let has_seed = keys.seed != [0u8; 32];
+        let seed: [u8; 32] = if has_seed { self.bytes() } else { [0u8; 32] };
+        // Without a seed in the file, HD keys are derived from a seed that isn't kept.
+        let entropy: [u8; 32] = if has_seed { seed } else { self.bytes() };
+        let bip39_seed = <Mnemonic<English>>::from_entropy(entropy)
+            .map_err(|e| WalletError::InvalidFormat(e.to_string()))?
+            .to_seed("");
+
+        let enc_seed = if keys.enc_seed != [0u8; 48] {
+            self.bytes()
+        } else {
+            [0u8; 48]
+        };
+        let nonce = self.random_vec(keys.nonce.len());
+
+        let mut zkeys = vec![];
+        for zkey in &keys.zkeys {
+            let mut anonymized = match zkey.hdkey_num {
+                Some(n) => WalletZKey::new_hdkey(&bip39_seed, coin_type, n),
+                None => WalletZKey::new_hdkey(&self.bytes::<64>(), coin_type, 0),
+            };
+            anonymized.keytype = zkey.keytype.clone();
+            anonymized.locked = zkey.locked;
+            anonymized.hdkey_num = zkey.hdkey_num;
+            if zkey.extsk.is_none() {
+                anonymized.extsk = None;
+            }
+            anonymized.enc_key = zkey.enc_key.as_ref().map(|k| self.random_vec(k.len()));
+            anonymized.nonce = zkey.nonce.as_ref().map(|n| self.random_vec(n.len()));
+
+            self.sapling_keys
+                .push((zkey.extfvk.clone(), anonymized.extfvk.clone()));
+            self.record_address(
+                encode_payment_address_p(&self.network, &zkey.zaddress),
+                encode_payment_address_p(&self.network, &anonymized.zaddress),
+            );
+            zkeys.push(anonymized);
+        }
+
+        let mut okeys = vec![];
+        for okey in &keys.okeys {
+            let mut anonymized = match okey.hdkey_num {
+                Some(n) => WalletOKey::new_hdkey(&bip39_seed, coin_type, n)?,
+                None => WalletOKey::new_hdkey(&self.bytes::<64>(), coin_type, 0)?,
+            };
+            anonymized.keytype = okey.keytype.clone();
+            anonymized.locked = okey.locked;
+            anonymized.hdkey_num = okey.hdkey_num;
+            if okey.sk.is_none() {
+                anonymized.sk = None;
+            }
+            anonymized.enc_key = okey.enc_key.as_ref().map(|k| self.random_vec(k.len()));
+            anonymized.nonce = okey.nonce.as_ref().map(|n| self.random_vec(n.len()));
+
+            self.orchard_keys
+                .push((okey.fvk.clone(), anonymized.fvk.clone()));
+            self.record_address(
+                okey.unified_address.encode(&self.network),
+                anonymized.unified_address.encode(&self.network),
+            );
+            okeys.push(anonymized);
+        }
+
+        let mut tkeys = vec![];
+        for tkey in &keys.tkeys {
+            let mut anonymized = match tkey.hdkey_num {
+                Some(n) => WalletTKey::new_hdkey(&self.network, &bip39_seed, n)?,
+                None => {
+                    let seed: [u8; 64] = self.bytes();
+                    WalletTKey::new_hdkey(&self.network, &seed, 0)?
+                }
+            };
+            anonymized.keytype = tkey.keytype;
+            anonymized.locked = tkey.locked;
+            anonymized.hdkey_num = tkey.hdkey_num;
+            if tkey.pk.is_none() {
+                anonymized.pk = None;
+            }
+            anonymized.enc_key = tkey.enc_key.as_ref().map(|k| self.random_vec(k.len()));
+            anonymized.nonce = tkey.nonce.as_ref().map(|n| self.random_vec(n.len()));
+
+            self.record_address(tkey.address.clone(), anonymized.address.clone());
+            tkeys.push(anonymized);
+        }
+
+        Ok(Keys {
+            encrypted: keys.encrypted,
+            enc_seed,
+            nonce,
+            seed,
+            zkeys,
+            tkeys,
+            okeys,
+        })
+    }
+
+    /// The new key for a Sapling viewing key, or a random one if it isn't in the wallet.
+    fn sapling_key(&mut self, extfvk: &ExtendedFullViewingKey) -> ExtendedFullViewingKey {
+        if let Some((_, anonymized)) = self.sapling_keys.iter().find(|(k, _)| k == extfvk) {
+            return anonymized.clone();
+        }
+        let anonymized = WalletZKey::new_hdkey(&self.bytes::<64>(), 0, 0).extfvk;
+        self.sapling_keys.push((extfvk.clone(), anonymized.clone()));
+        anonymized
+    }
+
+    /// The new key for an Orchard viewing key, or a random one if it isn't in the wallet.
+    fn orchard_key(&mut self, fvk: &FullViewingKey) -> Result<FullViewingKey, WalletError> {
+        if let Some((_, anonymized)) = self.orchard_keys.iter().find(|(k, _)| k == fvk) {
+            return Ok(anonymized.clone());
+        }
+        let anonymized = WalletOKey::new_hdkey(&self.bytes::<64>(), 0, 0)?.fvk;
+        self.orchard_keys.push((fvk.clone(), anonymized.clone()));
+        Ok(anonymized)
+    }
+
+    fn wallet_tx(&mut self, wtx: &WalletTx) -> Result<WalletTx, WalletError> {
+        let txid = self.txid(&wtx.txid);
+
+        let s_spent_nullifiers = wtx
+            .s_spent_nullifiers
+            .iter()
+            .map(|nf| self.sapling_nullifier(nf))
+            .collect();
+        let o_spent_nullifiers = wtx
+            .o_spent_nullifiers
+            .iter()
+            .map(|nf| self.orchard_nullifier(nf))
+            .collect();
+
+        let mut sapling_notes = vec![];
+        for (i, nd) in wtx.sapling_notes.iter().enumerate() {
+            sapling_notes.push(self.sapling_note(nd, txid, i)?);
+        }
+        let mut orchard_notes = vec![];
+        for (i, nd) in wtx.orchard_notes.iter().enumerate() {
+            orchard_notes.push(self.orchard_note(nd, txid, i)?);
+        }
+        let mut utxos = vec![];
+        for utxo in &wtx.utxos {
+            utxos.push(self.utxo(utxo, txid)?);
+        }
+        let mut outgoing_metadata = vec![];
+        for (i, om) in wtx.outgoing_metadata.iter().enumerate() {
+            outgoing_metadata.push(OutgoingTxMetadata {
+                address: self.other_address(&om.address),
+                value: self.value(txid, format!("outgoing {}", i), om.value),
+                memo: self.memo(&om.memo)?,
+            });
+        }
+
+        Ok(WalletTx {
+            txid,
+            s_spent_nullifiers,
+            o_spent_nullifiers,
+            sapling_notes,
+            orchard_notes,
+            utxos,
+            total_orchard_value_spent: self.value(
+                txid,
+                "Orchard spent".to_string(),
+                wtx.total_orchard_value_spent,
+            ),
+            total_sapling_value_spent: self.value(
+                txid,
+                "Sapling spent".to_string(),
+                wtx.total_sapling_value_spent,
+            ),
+            total_transparent_value_spent: self.value(
+                txid,
+                "transparent spent".to_string(),
+                wtx.total_transparent_value_spent,
+            ),
+            outgoing_metadata,
+            ..wtx.clone()
+        })
+    }
+
+    fn sapling_note(
+        &mut self,
+        nd: &SaplingNoteData,
+        txid: TxId,
+        index: usize,
+    ) -> Result<SaplingNoteData, WalletError> {
+        let extfvk = self.sapling_key(&nd.extfvk);
+
+        // Diversified addresses map to the address at the same index of the new key.
+        let dfvk = extfvk.to_diversifiable_full_viewing_key();
+        let diversifier = match nd
+            .extfvk
+            .to_diversifiable_full_viewing_key()
+            .decrypt_diversifier(&nd.note.recipient())
+        {
+            Some((j, Scope::External)) => dfvk.find_address(j),
+            _ => None,
+        }
+        .map_or_else(
+            || *extfvk.default_address().1.diversifier(),
+            |(_, address)| *address.diversifier(),
+        );
+        let address = extfvk
+            .fvk
+            .vk
+            .to_payment_address(diversifier)
+            .expect("The diversifier of an address is valid");
+
+        let value = self.value(
+            txid,
+            format!("Sapling note {}", index),
+            nd.note.value().inner(),
+        );
+        let rseed = match nd.note.rseed() {
+            Rseed::BeforeZip212(_) => {
+                Rseed::BeforeZip212(jubjub::Fr::from_bytes_wide(&self.bytes()))
+            }
+            Rseed::AfterZip212(_) => Rseed::AfterZip212(self.bytes()),
+        };
+
+        Ok(SaplingNoteData {
+            extfvk,
+            diversifier,
+            note: address.create_note(NoteValue::from_raw(value), rseed),
+            witnesses: self.witness_cache(&nd.witnesses),
+            nullifier: self.sapling_nullifier(&nd.nullifier),
+            spent: self.spent(&nd.spent),
+            unconfirmed_spent: self.spent(&nd.unconfirmed_spent),
+            memo: nd.memo.as_ref().map(|memo| self.memo(memo)).transpose()?,
+            is_change: nd.is_change,
+            have_spending_key: nd.have_spending_key,
+        })
+    }
+
+    fn orchard_note(
+        &mut self,
+        nd: &OrchardNoteData,
+        txid: TxId,
+        index: usize,
+    ) -> Result<OrchardNoteData, WalletError> {
+        let fvk = self.orchard_key(&nd.fvk)?;
+
+        // Diversified addresses map to the address at the same index of the new key.
+        let recipient = nd.note.recipient();
+        let address = nd
+            .fvk
+            .scope_for_address(&recipient)
+            .and_then(|scope| {
+                let j = nd.fvk.to_ivk(scope).diversifier_index(&recipient)?;
+                Some(fvk.address_at(j, scope))
+            })
+            .unwrap_or_else(|| fvk.address_at(0u64, OrchardScope::External));
+
+        let value = OrchardNoteValue::from_raw(self.value(
+            txid,
+            format!("Orchard note {}", index),
+            nd.note.value().inner(),
+        ));
+        let rho = self.orchard_nullifier(&nd.note.rho());
+        let note = loop {
+            let Some(rseed) = RandomSeed::from_bytes(self.bytes(), &rho).into() else {
+                continue;
+            };
+            if let Some(note) = orchard_old::Note::from_parts(address, value, rho, rseed).into() {
+                break note;
+            }
+        };
+
+        Ok(OrchardNoteData {
+            fvk,
+            note,
+            spent: self.spent(&nd.spent),
+            unconfirmed_spent: self.spent(&nd.unconfirmed_spent),
+            memo: nd.memo.as_ref().map(|memo| self.memo(memo)).transpose()?,
+            ..nd.clone()
+        })
+    }
+
+    fn utxo(&mut self, utxo: &Utxo, txid: TxId) -> Result<Utxo, WalletError> {
+        let address = self.taddress(&utxo.address)?;
+
+        let script = decode_transparent_address(
+            &self.network.b58_pubkey_address_prefix(),
+            &self.network.b58_script_address_prefix(),
+            &address,
+        )
+        .ok()
+        .flatten()
+        .map(|taddr| taddr.script().0)
+        .filter(|script| script.len() == utxo.script.len())
+        .unwrap_or_else(|| self.random_vec(utxo.script.len()));
+
+        Ok(Utxo {
+            address,
+            txid,
+            script,
+            value: self.value(
+                txid,
+                format!("transparent output {}", utxo.output_index),
+                utxo.value,
+            ),
+            spent: utxo.spent.map(|txid| self.txid(&txid)),
+            unconfirmed_spent: self.spent(&utxo.unconfirmed_spent),
+            ..utxo.clone()
+        })
+    }
+
+    /// Replaces a block hash in its internal byte order.
+    fn block_hash(&mut self, hash: &[u8]) -> Vec<u8> {
+        if hash.is_empty() {
+            return vec![];
+        }
+        if let Some(anonymized) = self.block_hashes.get(hash) {
+            return anonymized.clone();
+        }
+        let anonymized = self.random_vec(hash.len());
+        self.block_hashes.insert(hash.to_vec(), anonymized.clone());
+        anonymized
+    }
+
+    /// Replaces a block hash hex-encoded in display order, as the wallet stores them.
+    fn display_hash(&mut self, hash: &str) -> Result<String, WalletError> {
+        let mut bytes = hex::decode(hash)
+            .map_err(|e| WalletError::InvalidFormat(format!("Invalid block hash: {}", e)))?;
+        bytes.reverse();
+        let mut anonymized = self.block_hash(&bytes);
+        anonymized.reverse();
+        Ok(hex::encode(anonymized))
+    }
+
+    fn block(&mut self, block: &CompactBlockData) -> Result<CompactBlockData, WalletError> {
+        let ecb = match block.compact_block() {
+            Ok(cb) => self.compact_block(&cb).encode_to_vec(),
+            Err(_) => self.random_vec(block.ecb.len()),
+        };
+
+        Ok(CompactBlockData {
+            ecb,
+            height: block.height,
+            hash: self.display_hash(&block.hash)?,
+            tree: block.tree.as_ref().map(|tree| self.commitment_tree(tree)),
+        })
+    }
+
+    fn compact_block(&mut self, cb: &CompactBlock) -> CompactBlock {
+        CompactBlock {
+            hash: self.block_hash(&cb.hash),
+            prev_hash: self.block_hash(&cb.prev_hash),
+            // The header holds the real Merkle roots, and would give the real hash back.
+            header: vec![],
+            vtx: cb.vtx.iter().map(|tx| self.compact_tx(tx)).collect(),
+            ..cb.clone()
+        }
+    }
+
+    fn compact_tx(&mut self, tx: &CompactTx) -> CompactTx {
+        let hash = match <[u8; 32]>::try_from(&tx.hash[..]) {
+            Ok(hash) => self.txid(&TxId::from_bytes(hash)).as_ref().to_vec(),
+            Err(_) => self.random_vec(tx.hash.len()),
+        };
+        let spends = tx
+            .spends
+            .iter()
+            .map(|spend| CompactSaplingSpend {
+                nf: match <[u8; 32]>::try_from(&spend.nf[..]) {
+                    Ok(nf) => self
+                        .sapling_nullifier(&sapling_crypto::Nullifier(nf))
+                        .0
+                        .to_vec(),
+                    Err(_) => self.random_vec(spend.nf.len()),
+                },
+            })
+            .collect();
+        let outputs = tx
+            .outputs
+            .iter()
+            .map(|output| CompactSaplingOutput {
+                cmu: match <[u8; 32]>::try_from(&output.cmu[..])
+                    .ok()
+                    .and_then(|cmu| Option::from(Node::from_bytes(cmu)))
+                {
+                    Some(node) => self.sapling_node(&node).to_bytes().to_vec(),
+                    None => self.random_vec(output.cmu.len()),
+                },
+                ephemeral_key: self.random_vec(output.ephemeral_key.len()),
+                ciphertext: self.random_vec(output.ciphertext.len()),
+            })
+            .collect();
+        let actions = tx
+            .actions
+            .iter()
+            .map(|action| CompactOrchardAction {
+                nullifier: match <[u8; 32]>::try_from(&action.nullifier[..])
+                    .ok()
+                    .and_then(|nf| Option::from(OrchardNullifier::from_bytes(&nf)))
+                {
+                    Some(nf) => self.orchard_nullifier(&nf).to_bytes().to_vec(),
+                    None => self.random_vec(action.nullifier.len()),
+                },
+                // The leaves of the Orchard tree are the note commitments.
+                cmx: match <[u8; 32]>::try_from(&action.cmx[..])
+                    .ok()
+                    .and_then(|cmx| Option::from(MerkleHashOrchard::from_bytes(&cmx)))
+                {
+                    Some(hash) => self.orchard_hash(&hash).to_bytes().to_vec(),
+                    None => self.random_vec(action.cmx.len()),
+                },
+                ephemeral_key: self.random_vec(action.ephemeral_key.len()),
+                ciphertext: self.random_vec(action.ciphertext.len()),
+            })
+            .collect();
+
+        CompactTx {
+            hash,
+            spends,
+            outputs,
+            actions,
+            ..tx.clone()
+        }
+    }
+
+    fn tree_state(&mut self, tree_state: &TreeState) -> Result<TreeState, WalletError> {
+        let sapling_tree = if tree_state.sapling_tree.is_empty() {
+            String::new()
+        } else {
+            let tree = self.commitment_tree(&decode_sapling_tree(tree_state)?);
+            let mut bytes = vec![];
+            write_commitment_tree(&tree, &mut bytes)?;
+            hex::encode(bytes)
+        };
+        let orchard_tree = if tree_state.orchard_tree.is_empty() {
+            String::new()
+        } else {
+            let tree = self.orchard_tree(&orchard_tree_from_frontier(tree_state, 1)?)?;
+            hex::encode(encode_orchard_frontier(&tree))
+        };
+
+        Ok(TreeState {
+            hash: self.display_hash(&tree_state.hash)?,
+            sapling_tree,
+            orchard_tree,
+            ..tree_state.clone()
+        })
+    }
+
+    fn witness_cache(&mut self, cache: &WitnessCache) -> WitnessCache {
+        let witnesses = cache
+            .witnesses
+            .iter()
+            .map(|witness| {
+                let tree = self.commitment_tree(witness.tree());
+                let filled = witness
+                    .filled()
+                    .iter()
+                    .map(|node| self.sapling_node(node))
+                    .collect();
+                let cursor = witness
+                    .cursor()
+                    .as_ref()
+                    .map(|tree| self.commitment_tree(tree));
+                IncrementalWitness::from_parts(tree, filled, cursor)
+            })
+            .collect();
+
+        WitnessCache::new(witnesses, cache.top_height)
+    }
+
+    fn commitment_tree(&mut self, tree: &CommitmentTree) -> CommitmentTree {
+        let left = tree.left().map(|node| self.sapling_node(&node));
+        let right = tree.right().map(|node| self.sapling_node(&node));
+        let parents = tree
+            .parents()
+            .iter()
+            .map(|parent| parent.map(|node| self.sapling_node(&node)))
+            .collect();
+
+        CommitmentTree::from_parts(left, right, parents)
+            .expect("The tree has as many parents as the original")
+    }
+
+    fn orchard_tree(
+        &mut self,
+        tree: &BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>,
+    ) -> Result<BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>, WalletError> {
+        let mut prior_bridges = vec![];
+        for bridge in tree.prior_bridges() {
+            prior_bridges.push(self.bridge(bridge)?);
+        }
+        let current_bridge = tree
+            .current_bridge()
+            .as_ref()
+            .map(|bridge| self.bridge(bridge))
+            .transpose()?;
+
+        BridgeTree::from_parts(
+            prior_bridges,
+            current_bridge,
+            tree.witnessed_indices().clone(),
+            tree.checkpoints().to_vec(),
+            tree.max_checkpoints(),
+        )
+        .map_err(|e| WalletError::InvalidFormat(format!("{:?}", e)))
+    }
+
+    fn bridge(
+        &mut self,
+        bridge: &MerkleBridge<MerkleHashOrchard>,
+    ) -> Result<MerkleBridge<MerkleHashOrchard>, WalletError> {
+        let mut auth_fragments = BTreeMap::new();
+        for (position, fragment) in bridge.auth_fragments() {
+            let values = fragment
+                .values()
+                .iter()
+                .map(|hash| self.orchard_hash(hash))
+                .collect();
+            auth_fragments.insert(
+                *position,
+                AuthFragment::from_parts(
+                    fragment.position(),
+                    fragment.altitudes_observed(),
+                    values,
+                ),
+            );
+        }
+
+        let frontier = bridge.frontier();
+        let leaf = match frontier.leaf() {
+            Leaf::Left(a) => Leaf::Left(self.orchard_hash(a)),
+            Leaf::Right(a, b) => Leaf::Right(self.orchard_hash(a), self.orchard_hash(b)),
+        };
+        let ommers = frontier
+            .ommers()
+            .iter()
+            .map(|hash| self.orchard_hash(hash))
+            .collect();
+        let frontier = NonEmptyFrontier::from_parts(frontier.position(), leaf, ommers)
+            .map_err(|e| WalletError::InvalidFormat(format!("{:?}", e)))?;
+
+        Ok(MerkleBridge::from_parts(
+            bridge.prior_position(),
+            auth_fragments,
+            frontier,
+        ))
+    }
+}
@@ -0,0 +1,178 @@
+//! # Per-address balances
+//!
+//! [`ZwlWallet::address_balances`] attributes every note and Utxo to the address that received
+//! it, the way ZecWallet Lite users think of their funds:
+//!
+//! - a Sapling note to the address of its `diversifier` under its `extfvk`, which may be a
+//!   diversified address that isn't in the keys,
+//! - an Orchard note to its recipient, as an Orchard-only unified address,
+//! - a Utxo to its `address`.
+//!
+//! The default addresses of the keys are always listed, even without any activity.
+
+use std::{collections::BTreeMap, fmt};
+
+use zcash_keys::encoding::encode_payment_address_p;
+use zcash_primitives::consensus::Network;
+
+use crate::zwl::{
+    ZwlWallet, keys::orchard::orchard_unified_address, scan::Pool, transactions::WalletTx,
+};
+
+/// What an address received and spent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressBalance {
+    pub pool: Pool,
+    pub address: String,
+    /// Whether the address is the default address of one of the keys.
+    pub in_keys: bool,
+    pub notes: usize,
+    pub received: u64,
+    /// The value of the notes spent, confirmed or not.
+    pub spent: u64,
+    /// The heights of the first and last transactions that paid to or spent from the address.
+    pub first_activity: Option<u64>,
+    pub last_activity: Option<u64>,
+}
+
+impl AddressBalance {
+    fn new(pool: Pool, address: String) -> Self {
+        AddressBalance {
+            pool,
+            address,
+            in_keys: false,
+            notes: 0,
+            received: 0,
+            spent: 0,
+            first_activity: None,
+            last_activity: None,
+        }
+    }
+
+    /// What the address still holds.
+    pub fn balance(&self) -> u64 {
+        self.received - self.spent
+    }
+
+    /// Records a note received at `received_at`, and spent at `spent_at` if `spent` and the
+    /// height of the spend is known.
+    fn record(&mut self, value: u64, received_at: u64, spent: bool, spent_at: Option<u64>) {
+        self.notes += 1;
+        self.received += value;
+        if spent {
+            self.spent += value;
+        }
+        for height in [Some(received_at), spent_at].into_iter().flatten() {
+            self.first_activity = Some(self.first_activity.map_or(height, |h| h.min(height)));
+            self.last_activity = Some(self.last_activity.map_or(height, |h| h.max(height)));
+        }
+    }
+}
+
+impl fmt::Display for AddressBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{}: {} zats ({} received, {} spent in {} notes)",
+            self.pool,
+            self.address,
+            if self.in_keys { "" } else { " (not in keys)" },
+            self.balance(),
+            self.received,
+            self.spent,
+            self.notes
+        )?;
+        if let (Some(first), Some(last)) = (self.first_activity, self.last_activity) {
+            write!(f, ", active {}..={}", first, last)?;
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Lists the addresses of the keys and those that received notes or Utxos, by pool and
+    /// address.
+    pub fn address_balances(&self) -> Vec<AddressBalance> {
+        let network = self.chain_name.network().unwrap_or(Network::MainNetwork);
+        let mut balances: BTreeMap<(Pool, String), AddressBalance> = BTreeMap::new();
+
+        for tkey in &self.keys.tkeys {
+            entry(&mut balances, Pool::Transparent, tkey.address.clone()).in_keys = true;
+        }
+        for zkey in &self.keys.zkeys {
+            entry(
+                &mut balances,
+                Pool::Sapling,
+                encode_payment_address_p(&network, &zkey.zaddress),
+            )
+            .in_keys = true;
+        }
+        for okey in &self.keys.okeys {
+            entry(
+                &mut balances,
+                Pool::Orchard,
+                okey.unified_address.encode(&network),
+            )
+            .in_keys = true;
+        }
+
+        let mut wtxs: Vec<&WalletTx> = self.transactions.current.values().collect();
+        wtxs.sort_by_key(|wtx| (u32::from(wtx.block), wtx.txid));
+        for wtx in wtxs {
+            let height = u64::from(u32::from(wtx.block));
+            for nd in &wtx.sapling_notes {
+                let address = nd
+                    .extfvk
+                    .fvk
+                    .vk
+                    .to_payment_address(nd.diversifier)
+                    .unwrap_or_else(|| nd.note.recipient());
+                let spent_at = nd.spent.or(nd.unconfirmed_spent).map(|(_, h)| h.into());
+                entry(
+                    &mut balances,
+                    Pool::Sapling,
+                    encode_payment_address_p(&network, &address),
+                )
+                .record(
+                    nd.note.value().inner(),
+                    height,
+                    spent_at.is_some(),
+                    spent_at,
+                );
+            }
+            for nd in &wtx.orchard_notes {
+                let address = orchard_unified_address(nd.note.recipient()).encode(&network);
+                let spent_at = nd.spent.or(nd.unconfirmed_spent).map(|(_, h)| h.into());
+                entry(&mut balances, Pool::Orchard, address).record(
+                    nd.note.value().inner(),
+                    height,
+                    spent_at.is_some(),
+                    spent_at,
+                );
+            }
+            for utxo in &wtx.utxos {
+                // Old wallets don't always have the height of a spend.
+                let spent_at = match (utxo.spent, utxo.unconfirmed_spent) {
+                    (Some(_), _) => utxo.spent_at_height.and_then(|h| u64::try_from(h).ok()),
+                    (None, Some((_, h))) => Some(h.into()),
+                    (None, None) => None,
+                };
+                let spent = utxo.spent.is_some() || utxo.unconfirmed_spent.is_some();
+                entry(&mut balances, Pool::Transparent, utxo.address.clone())
+                    .record(utxo.value, height, spent, spent_at);
+            }
+        }
+
+        balances.into_values().collect()
+    }
+}
+
+fn entry(
+    balances: &mut BTreeMap<(Pool, String), AddressBalance>,
+    pool: Pool,
+    address: String,
+) -> &mut AddressBalance {
+    balances
+        .entry((pool, address.clone()))
+        .or_insert_with(|| AddressBalance::new(pool, address))
+}
@@ -0,0 +1,204 @@
+//! # Birthday estimation
+//!
+//! ZecWallet Lite rescans from the wallet birthday, and many old wallets store 0 or the Sapling
+//! activation height there, which makes every rescan go through years of blocks.
+//! [`ZwlWallet::estimate_birthday`] looks at the heights the wallet itself records:
+//!
+//! - the block of its earliest transaction,
+//! - the height of its earliest Sapling witness,
+//! - the lowest cached block,
+//! - the height of the verified tree.
+//!
+//! Each of them is at or after the point the wallet's history starts, so the lowest one is a
+//! birthday that finds every transaction of the HD keys. Imported keys can have history from
+//! before the wallet existed, which the file knows nothing about, so with imported keys the
+//! estimate never goes above the stored birthday, or the Sapling activation if there is none.
+
+use std::fmt;
+
+use zcash_primitives::consensus::{NetworkUpgrade, Parameters};
+
+use crate::zwl::{
+    ZwlWallet,
+    keys::{orchard::WalletOKeyType, sapling::WalletZKeyType, transparent::WalletTKeyType},
+};
+
+/// Where a height the birthday is estimated from comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BirthdaySource {
+    FirstTransaction,
+    FirstSaplingWitness,
+    LowestCachedBlock,
+    VerifiedTree,
+}
+
+impl fmt::Display for BirthdaySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BirthdaySource::FirstTransaction => write!(f, "earliest transaction"),
+            BirthdaySource::FirstSaplingWitness => write!(f, "earliest Sapling witness"),
+            BirthdaySource::LowestCachedBlock => write!(f, "lowest cached block"),
+            BirthdaySource::VerifiedTree => write!(f, "verified tree"),
+        }
+    }
+}
+
+/// A suggested birthday, and how it was found.
+#[derive(Debug, Clone)]
+pub struct BirthdayEstimate {
+    /// The birthday stored in the wallet.
+    pub stored: u64,
+    pub suggested: u64,
+    /// The lowest height ZecWallet Lite scans from, if the chain is known.
+    pub sapling_activation: Option<u64>,
+    pub evidence: Vec<(BirthdaySource, u64)>,
+    pub imported_keys: usize,
+    pub explanation: String,
+}
+
+impl BirthdayEstimate {
+    /// Whether the stored birthday is missing, that is 0 or not above the Sapling activation.
+    pub fn stored_is_missing(&self) -> bool {
+        self.stored <= self.sapling_activation.unwrap_or(0)
+    }
+
+    pub fn changes_birthday(&self) -> bool {
+        self.suggested != self.stored
+    }
+}
+
+impl fmt::Display for BirthdayEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Stored birthday: {}", self.stored)?;
+        writeln!(f, "Suggested birthday: {}", self.suggested)?;
+        for (source, height) in &self.evidence {
+            writeln!(f, "- {}: {}", source, height)?;
+        }
+        write!(f, "{}", self.explanation)
+    }
+}
+
+impl ZwlWallet {
+    /// Suggests a birthday from the heights recorded in the wallet.
+    pub fn estimate_birthday(&self) -> BirthdayEstimate {
+        let sapling_activation = self
+            .chain_name
+            .network()
+            .and_then(|network| network.activation_height(NetworkUpgrade::Sapling))
+            .map(u64::from);
+
+        let mut evidence = vec![];
+        if let Some(height) = self
+            .transactions
+            .current
+            .values()
+            .map(|wtx| u64::from(wtx.block))
+            .min()
+        {
+            evidence.push((BirthdaySource::FirstTransaction, height));
+        }
+        // The cache has a witness per block up to `top_height`.
+        if let Some(height) = self
+            .transactions
+            .current
+            .values()
+            .flat_map(|wtx| &wtx.sapling_notes)
+            .filter(|nd| !nd.witnesses.witnesses.is_empty())
+            .map(|nd| {
+                (nd.witnesses.top_height + 1).saturating_sub(nd.witnesses.witnesses.len() as u64)
+            })
+            .min()
+        {
+            evidence.push((BirthdaySource::FirstSaplingWitness, height));
+        }
+        if let Some(height) = self.blocks.iter().map(|b| b.height).min() {
+            evidence.push((BirthdaySource::LowestCachedBlock, height));
+        }
+        if let Some(tree) = &self.verified_tree {
+            evidence.push((BirthdaySource::VerifiedTree, tree.height));
+        }
+
+        let keys = &self.keys;
+        let imported_keys = keys
+            .zkeys
+            .iter()
+            .filter(|k| k.keytype != WalletZKeyType::HdKey)
+            .count()
+            + keys
+                .okeys
+                .iter()
+                .filter(|k| k.keytype != WalletOKeyType::HdKey)
+                .count()
+            + keys
+                .tkeys
+                .iter()
+                .filter(|k| k.keytype != WalletTKeyType::HdKey)
+                .count();
+
+        let mut estimate = BirthdayEstimate {
+            stored: self.birthday,
+            suggested: self.birthday,
+            sapling_activation,
+            evidence,
+            imported_keys,
+            explanation: String::new(),
+        };
+        let floor = sapling_activation.unwrap_or(0);
+        let lowest = estimate
+            .evidence
+            .iter()
+            .min_by_key(|(_, height)| *height)
+            .copied();
+
+        let missing = estimate.stored_is_missing();
+        (estimate.suggested, estimate.explanation) = match lowest {
+            Some((source, height)) if !missing && height < self.birthday => (
+                height.max(floor),
+                format!(
+                    "The {} is below the stored birthday, which is too high.",
+                    source
+                ),
+            ),
+            _ if imported_keys > 0 && missing => (
+                floor,
+                format!(
+                    "The wallet has {} imported keys whose history may predate the wallet, so \
+                     without a stored birthday it has to rescan from the Sapling activation.",
+                    imported_keys
+                ),
+            ),
+            _ if imported_keys > 0 => (
+                self.birthday,
+                format!(
+                    "The wallet has {} imported keys whose history may predate the wallet, so \
+                     the stored birthday is kept.",
+                    imported_keys
+                ),
+            ),
+            Some((source, height)) if missing => (
+                height.max(floor),
+                format!(
+                    "The stored birthday is missing, and the wallet's history starts at its {} \
+                     at the latest.",
+                    source
+                ),
+            ),
+            Some(_) => (
+                self.birthday,
+                "The stored birthday is below everything the wallet recorded.".to_string(),
+            ),
+            None if missing => (
+                floor,
+                "The stored birthday is missing and the wallet records no heights, so it has to \
+                 rescan from the Sapling activation."
+                    .to_string(),
+            ),
+            None => (
+                self.birthday,
+                "The wallet records no heights to check the stored birthday against.".to_string(),
+            ),
+        };
+
+        estimate
+    }
+}
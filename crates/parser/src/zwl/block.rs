@@ -1,18 +1,29 @@
 use std::{
     fmt,
-    io::{self, Read},
+    io::{self, Read, Write},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use prost::Message;
 use sapling_crypto::CommitmentTree;
 use tracing::instrument;
+use zcash_client_backend::proto::compact_formats::CompactBlock;
 use zcash_encoding::Vector;
-use zcash_primitives::merkle_tree::read_commitment_tree;
+use zcash_primitives::merkle_tree::{read_commitment_tree, write_commitment_tree};
+
+use crate::zwl::ZwlWallet;
 
 #[derive(Clone, Debug)]
 pub struct CompactBlockData {
     pub ecb: Vec<u8>,
     pub height: u64,
+
+    // Block hash, hex-encoded in display (reversed) byte order
+    pub hash: String,
+
+    // Sapling commitment tree at this block. Only kept by very old wallets, newer ones write
+    // an empty tree here.
+    pub tree: Option<CommitmentTree>,
 }
 
 impl CompactBlockData {
@@ -29,12 +40,12 @@ impl CompactBlockData {
         let mut hash_bytes = [0; 32];
         reader.read_exact(&mut hash_bytes)?;
         hash_bytes.reverse();
-        let _hash = hex::encode(hash_bytes);
+        let hash = hex::encode(hash_bytes);
 
         // We don't need this, but because of a quirk, the version is stored later, so we can't actually
         // detect the version here. So we write an empty tree and read it back here
         let tree: CommitmentTree = read_commitment_tree(&mut reader)?;
-        let _tree = if tree.size() == 0 { None } else { Some(tree) };
+        let tree = if tree.size() == 0 { None } else { Some(tree) };
 
         // read version
         let _version = reader.read_u64::<LittleEndian>()?;
@@ -42,15 +53,165 @@ impl CompactBlockData {
         // read "ecb" (encoded compact block?)
         let ecb = Vector::read(&mut reader, |r| r.read_u8()).unwrap_or_default();
 
-        Ok(Self { ecb, height })
+        Ok(Self {
+            ecb,
+            height,
+            hash,
+            tree,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_i32::<LittleEndian>(self.height as i32)?;
+
+        let mut hash_bytes = hex::decode(&self.hash)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        hash_bytes.reverse();
+        writer.write_all(&hash_bytes[..])?;
+
+        match &self.tree {
+            Some(tree) => write_commitment_tree(tree, &mut writer)?,
+            None => write_commitment_tree(&CommitmentTree::empty(), &mut writer)?,
+        }
+
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        // Write the ecb as well
+        Vector::write(&mut writer, &self.ecb, |w, b| w.write_u8(*b))?;
+
+        Ok(())
+    }
+
+    /// Decodes `ecb` into the lightwalletd [`CompactBlock`] it was serialized from.
+    pub fn compact_block(&self) -> io::Result<CompactBlock> {
+        CompactBlock::decode(&self.ecb[..]).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Couldn't decode compact block {}: {}", self.height, e),
+            )
+        })
     }
 }
 
 impl fmt::Display for CompactBlockData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Block height: {}", self.height).unwrap();
+        writeln!(f, "Block hash: {}", self.hash).unwrap();
 
         writeln!(f, "ECB size in bytes: {}", self.ecb.len()).unwrap();
         Ok(())
     }
 }
+
+/// A break in the chain of cached blocks, reported against the higher of the two blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainBreak {
+    /// `ecb` couldn't be decoded into a [`CompactBlock`].
+    Undecodable { height: u64, error: String },
+    /// The decoded block doesn't match the height or hash stored next to it.
+    HeaderMismatch { height: u64, reason: String },
+    /// The next lower cached block isn't at `height - 1`.
+    Gap { height: u64, lower_height: u64 },
+    /// `prev_hash` doesn't point at the next lower cached block.
+    PrevHashMismatch {
+        height: u64,
+        prev_hash: String,
+        lower_hash: String,
+    },
+}
+
+impl fmt::Display for ChainBreak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainBreak::Undecodable { height, error } => {
+                write!(f, "block {} can't be decoded: {}", height, error)
+            }
+            ChainBreak::HeaderMismatch { height, reason } => {
+                write!(f, "block {} doesn't match its header: {}", height, reason)
+            }
+            ChainBreak::Gap {
+                height,
+                lower_height,
+            } => write!(
+                f,
+                "block {} is followed by block {} in the cache",
+                height, lower_height
+            ),
+            ChainBreak::PrevHashMismatch {
+                height,
+                prev_hash,
+                lower_hash,
+            } => write!(
+                f,
+                "block {} has prev_hash {}, but the cached block below it has hash {}",
+                height, prev_hash, lower_hash
+            ),
+        }
+    }
+}
+
+/// Checks that every cached block links to the next lower cached block.
+///
+/// `blocks` are expected highest-first, the order in which ZecWallet Lite stores them.
+pub fn check_chain_continuity(blocks: &[CompactBlockData]) -> Vec<ChainBreak> {
+    let mut breaks = vec![];
+
+    let decoded: Vec<Option<CompactBlock>> = blocks
+        .iter()
+        .map(|b| match b.compact_block() {
+            Ok(cb) => {
+                if cb.height != b.height {
+                    breaks.push(ChainBreak::HeaderMismatch {
+                        height: b.height,
+                        reason: format!("decoded height is {}", cb.height),
+                    });
+                } else if cb.hash().to_string() != b.hash {
+                    breaks.push(ChainBreak::HeaderMismatch {
+                        height: b.height,
+                        reason: format!("decoded hash is {}", cb.hash()),
+                    });
+                }
+                Some(cb)
+            }
+            Err(e) => {
+                breaks.push(ChainBreak::Undecodable {
+                    height: b.height,
+                    error: e.to_string(),
+                });
+                None
+            }
+        })
+        .collect();
+
+    for (i, pair) in blocks.windows(2).enumerate() {
+        let (higher, lower) = (&pair[0], &pair[1]);
+
+        if higher.height != lower.height + 1 {
+            breaks.push(ChainBreak::Gap {
+                height: higher.height,
+                lower_height: lower.height,
+            });
+            continue;
+        }
+
+        if let Some(cb) = &decoded[i] {
+            let prev_hash = cb.prev_hash().to_string();
+            if prev_hash != lower.hash {
+                breaks.push(ChainBreak::PrevHashMismatch {
+                    height: higher.height,
+                    prev_hash,
+                    lower_hash: lower.hash.clone(),
+                });
+            }
+        }
+    }
+
+    breaks
+}
+
+impl ZwlWallet {
+    /// Checks the continuity of the cached compact blocks. See [`check_chain_continuity`].
+    pub fn check_block_continuity(&self) -> Vec<ChainBreak> {
+        check_chain_continuity(&self.blocks)
+    }
+}
@@ -2,9 +2,13 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::{
     fmt::{self, Display},
     io::{self, Read},
+    str::FromStr,
 };
 use tracing::instrument;
 use zcash_encoding::Optional;
+use zcash_primitives::consensus::Network;
+
+use crate::error::WalletError;
 
 // Struct that tracks the latest and historical price of ZEC in the wallet
 #[derive(Clone, Debug)]
@@ -102,6 +106,22 @@ impl fmt::Display for MemoDownloadOption {
     }
 }
 
+impl FromStr for MemoDownloadOption {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "nomemos" | "none" => Ok(MemoDownloadOption::NoMemos),
+            "walletmemos" | "wallet" => Ok(MemoDownloadOption::WalletMemos),
+            "allmemos" | "all" => Ok(MemoDownloadOption::AllMemos),
+            _ => Err(WalletError::InvalidFormat(format!(
+                "Unknown memo download option {}, expected none, wallet or all",
+                s
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct WalletOptions {
     pub(crate) download_memos: MemoDownloadOption,
@@ -122,6 +142,31 @@ impl WalletOptions {
         2
     }
 
+    pub fn download_memos(&self) -> MemoDownloadOption {
+        self.download_memos
+    }
+
+    pub fn set_download_memos(&mut self, download_memos: MemoDownloadOption) {
+        self.download_memos = download_memos;
+    }
+
+    /// Transactions with more outputs than this are treated as spam, and their memos aren't
+    /// downloaded. -1 turns the filter off.
+    pub fn spam_threshold(&self) -> i64 {
+        self.spam_threshold
+    }
+
+    pub fn set_spam_threshold(&mut self, spam_threshold: i64) -> Result<(), WalletError> {
+        if spam_threshold != -1 && spam_threshold <= 0 {
+            return Err(WalletError::InvalidFormat(format!(
+                "Invalid spam threshold {}, expected -1 or a positive number of outputs",
+                spam_threshold
+            )));
+        }
+        self.spam_threshold = spam_threshold;
+        Ok(())
+    }
+
     #[instrument(level = "info", name = "WalletOptions::read", skip_all, err)]
     pub fn read<R: ReadBytesExt>(mut reader: R) -> io::Result<Self> {
         let version = reader.read_u64::<LittleEndian>()?;
@@ -178,6 +223,42 @@ pub enum ChainType {
     Unknown,
 }
 
+impl ChainType {
+    /// The chain name as ZecWallet Lite writes it, if known.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            ChainType::Mainnet => Some("main"),
+            ChainType::Testnet => Some("test"),
+            ChainType::Regtest => Some("regtest"),
+            ChainType::Unknown => None,
+        }
+    }
+
+    /// Consensus parameters for this chain, if they are known.
+    ///
+    /// Regtest has none: its activation heights are set by the local node, and aren't stored in
+    /// the wallet. Use [`ChainType::require_network`] to report that as an error.
+    pub fn network(&self) -> Option<Network> {
+        match self {
+            ChainType::Mainnet => Some(Network::MainNetwork),
+            ChainType::Testnet => Some(Network::TestNetwork),
+            ChainType::Regtest | ChainType::Unknown => None,
+        }
+    }
+
+    /// Consensus parameters for this chain, or an error explaining why they aren't known.
+    pub fn require_network(&self) -> Result<Network, WalletError> {
+        self.network().ok_or_else(|| {
+            WalletError::InvalidFormat(match self {
+                ChainType::Regtest => "Regtest wallets aren't supported: their activation \
+                                       heights are set by the local node"
+                    .to_string(),
+                _ => "The wallet's chain is unknown".to_string(),
+            })
+        })
+    }
+}
+
 impl From<String> for ChainType {
     fn from(s: String) -> Self {
         match s.as_str() {
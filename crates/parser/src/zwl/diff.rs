@@ -0,0 +1,487 @@
+//! # Comparing wallets
+//!
+//! [`ZwlWallet::diff`] compares two wallets, typically two backups of the same one, and lists
+//! what changed from the first to the second:
+//!
+//! - the version, chain, birthday, encryption, options and sync heights,
+//! - the keys added or removed, matched by viewing key or address,
+//! - the transactions added, removed or changed, matched by txid,
+//! - the spend status of the notes and Utxos of the transactions in both.
+//!
+//! Only the public parts are compared. The seed and the spending keys are left out, so a wallet
+//! can be compared to an encrypted or locked copy of itself; a key only counts as changed if it
+//! gained or lost its spending key altogether.
+
+use std::fmt;
+
+use orchard_old::note::ExtractedNoteCommitment;
+use zcash_keys::encoding::encode_payment_address_p;
+use zcash_primitives::{consensus::Network, transaction::TxId};
+
+use crate::zwl::{
+    ZwlWallet,
+    keys::Keys,
+    scan::Pool,
+    transactions::{Utxo, WalletTx},
+};
+
+/// Whether something is only in the second wallet, only in the first, or in both but different.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl fmt::Display for DiffKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffKind::Added => write!(f, "+"),
+            DiffKind::Removed => write!(f, "-"),
+            DiffKind::Changed => write!(f, "~"),
+        }
+    }
+}
+
+/// A value that differs between the wallets. `None` when a wallet doesn't have it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "none".to_string());
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// A key added, removed, or that gained or lost its spending key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChange {
+    pub kind: DiffKind,
+    pub pool: Pool,
+    pub address: String,
+    /// Whether the key can spend, in the second wallet unless it was removed.
+    pub spending: bool,
+}
+
+/// A transaction added, removed or changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxChange {
+    pub kind: DiffKind,
+    pub txid: TxId,
+    /// Height of the transaction, in the second wallet unless it was removed.
+    pub height: u64,
+    /// What differs, for changed transactions.
+    pub fields: Vec<FieldChange>,
+}
+
+/// Whether a note or Utxo is spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendStatus {
+    Unspent,
+    /// Spent by a transaction that isn't mined yet.
+    Pending(TxId, u32),
+    /// Spent by a mined transaction, at a height old wallets don't always have for Utxos.
+    Spent(TxId, Option<u32>),
+}
+
+impl fmt::Display for SpendStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpendStatus::Unspent => write!(f, "unspent"),
+            SpendStatus::Pending(txid, height) => write!(f, "pending in {} at {}", txid, height),
+            SpendStatus::Spent(txid, Some(height)) => {
+                write!(f, "spent in {} at {}", txid, height)
+            }
+            SpendStatus::Spent(txid, None) => write!(f, "spent in {}", txid),
+        }
+    }
+}
+
+impl SpendStatus {
+    pub(crate) fn new(spent: Option<(TxId, u32)>, unconfirmed_spent: Option<(TxId, u32)>) -> Self {
+        match (spent, unconfirmed_spent) {
+            (Some((txid, height)), _) => SpendStatus::Spent(txid, Some(height)),
+            (None, Some((txid, height))) => SpendStatus::Pending(txid, height),
+            (None, None) => SpendStatus::Unspent,
+        }
+    }
+
+    pub(crate) fn of_utxo(utxo: &Utxo) -> Self {
+        match (utxo.spent, utxo.unconfirmed_spent) {
+            (Some(txid), _) => {
+                let height = utxo.spent_at_height.and_then(|h| u32::try_from(h).ok());
+                SpendStatus::Spent(txid, height)
+            }
+            (None, Some((txid, height))) => SpendStatus::Pending(txid, height),
+            (None, None) => SpendStatus::Unspent,
+        }
+    }
+}
+
+/// A note or Utxo whose spend status changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteChange {
+    /// Transaction that received the note or Utxo.
+    pub txid: TxId,
+    pub pool: Pool,
+    /// Index of the note in its pool, or of the Utxo, within the transaction of the second wallet.
+    pub index: usize,
+    pub value: u64,
+    pub old: SpendStatus,
+    pub new: SpendStatus,
+}
+
+/// What changed from one wallet to another.
+#[derive(Debug, Clone, Default)]
+pub struct WalletDiff {
+    pub fields: Vec<FieldChange>,
+    pub keys: Vec<KeyChange>,
+    pub transactions: Vec<TxChange>,
+    pub notes: Vec<NoteChange>,
+}
+
+impl WalletDiff {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.keys.is_empty()
+            && self.transactions.is_empty()
+            && self.notes.is_empty()
+    }
+}
+
+impl fmt::Display for WalletDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for field in &self.fields {
+            writeln!(f, "~ {}", field)?;
+        }
+        for key in &self.keys {
+            let spending = if key.spending { "spending" } else { "viewing" };
+            writeln!(
+                f,
+                "{} {} key {} ({})",
+                key.kind, key.pool, key.address, spending
+            )?;
+        }
+        for tx in &self.transactions {
+            writeln!(f, "{} tx {} at {}", tx.kind, tx.txid, tx.height)?;
+            for field in &tx.fields {
+                writeln!(f, "    {}", field)?;
+            }
+        }
+        for note in &self.notes {
+            writeln!(
+                f,
+                "~ {} {} #{} ({} zats): {} -> {}",
+                note.pool, note.txid, note.index, note.value, note.old, note.new
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Lists what changed from this wallet to `other`.
+    pub fn diff(&self, other: &ZwlWallet) -> WalletDiff {
+        let mut diff = WalletDiff::default();
+
+        let mut field = |field: &'static str, old: Option<String>, new: Option<String>| {
+            if old != new {
+                diff.fields.push(FieldChange { field, old, new });
+            }
+        };
+        field(
+            "version",
+            Some(self.version.to_string()),
+            Some(other.version.to_string()),
+        );
+        field(
+            "chain",
+            Some(self.chain_name.to_string()),
+            Some(other.chain_name.to_string()),
+        );
+        field(
+            "birthday",
+            Some(self.birthday.to_string()),
+            Some(other.birthday.to_string()),
+        );
+        field(
+            "encrypted",
+            Some(self.keys.encrypted.to_string()),
+            Some(other.keys.encrypted.to_string()),
+        );
+        field(
+            "download_memos",
+            Some(self.wallet_options.download_memos().to_string()),
+            Some(other.wallet_options.download_memos().to_string()),
+        );
+        field(
+            "spam_threshold",
+            Some(self.wallet_options.spam_threshold().to_string()),
+            Some(other.wallet_options.spam_threshold().to_string()),
+        );
+        field(
+            "latest block",
+            self.latest_height().map(|h| h.to_string()),
+            other.latest_height().map(|h| h.to_string()),
+        );
+        field(
+            "verified tree",
+            self.verified_tree.as_ref().map(|t| t.height.to_string()),
+            other.verified_tree.as_ref().map(|t| t.height.to_string()),
+        );
+
+        // Addresses only display the keys, which are matched by viewing key. A wallet for an
+        // unknown chain has its keys shown as mainnet ones.
+        let network = self
+            .chain_name
+            .network()
+            .or(other.chain_name.network())
+            .unwrap_or(Network::MainNetwork);
+        diff.keys = diff_keys(&self.keys, &other.keys, &network);
+
+        let mut txids: Vec<&TxId> = self
+            .transactions
+            .current
+            .keys()
+            .chain(other.transactions.current.keys())
+            .collect();
+        txids.sort_by_key(|txid| {
+            let wtx = other
+                .transactions
+                .current
+                .get(*txid)
+                .or(self.transactions.current.get(*txid));
+            (wtx.map(|wtx| u32::from(wtx.block)), **txid)
+        });
+        txids.dedup();
+
+        for txid in txids {
+            let old = self.transactions.current.get(txid);
+            let new = other.transactions.current.get(txid);
+            match (old, new) {
+                (Some(old), None) => diff.transactions.push(TxChange {
+                    kind: DiffKind::Removed,
+                    txid: *txid,
+                    height: u32::from(old.block).into(),
+                    fields: vec![],
+                }),
+                (None, Some(new)) => diff.transactions.push(TxChange {
+                    kind: DiffKind::Added,
+                    txid: *txid,
+                    height: u32::from(new.block).into(),
+                    fields: vec![],
+                }),
+                (Some(old), Some(new)) => {
+                    let fields = diff_tx(old, new);
+                    if !fields.is_empty() {
+                        diff.transactions.push(TxChange {
+                            kind: DiffKind::Changed,
+                            txid: *txid,
+                            height: u32::from(new.block).into(),
+                            fields,
+                        });
+                    }
+                    diff.notes.extend(diff_spends(old, new));
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        diff
+    }
+}
+
+fn diff_keys(old: &Keys, new: &Keys, network: &Network) -> Vec<KeyChange> {
+    let mut changes = vec![];
+    let mut compare =
+        |pool: Pool, address: String, old_spending: Option<bool>, new_spending: Option<bool>| {
+            let (kind, spending) = match (old_spending, new_spending) {
+                (Some(spending), None) => (DiffKind::Removed, spending),
+                (None, Some(spending)) => (DiffKind::Added, spending),
+                (Some(old), Some(new)) if old != new => (DiffKind::Changed, new),
+                _ => return,
+            };
+            changes.push(KeyChange {
+                kind,
+                pool,
+                address,
+                spending,
+            });
+        };
+
+    for (i, zkey) in old.zkeys.iter().chain(&new.zkeys).enumerate() {
+        let in_old = old.zkeys.iter().find(|k| k.extfvk == zkey.extfvk);
+        let in_new = new.zkeys.iter().find(|k| k.extfvk == zkey.extfvk);
+        // Keys in both wallets are only compared once, from the first one.
+        if i >= old.zkeys.len() && in_old.is_some() {
+            continue;
+        }
+        compare(
+            Pool::Sapling,
+            encode_payment_address_p(network, &zkey.zaddress),
+            in_old.map(|k| k.have_spending_key()),
+            in_new.map(|k| k.have_spending_key()),
+        );
+    }
+    for (i, okey) in old.okeys.iter().chain(&new.okeys).enumerate() {
+        let in_old = old.okeys.iter().find(|k| k.fvk == okey.fvk);
+        let in_new = new.okeys.iter().find(|k| k.fvk == okey.fvk);
+        if i >= old.okeys.len() && in_old.is_some() {
+            continue;
+        }
+        compare(
+            Pool::Orchard,
+            okey.unified_address.encode(network),
+            in_old.map(|k| k.have_spending_key()),
+            in_new.map(|k| k.have_spending_key()),
+        );
+    }
+    for (i, tkey) in old.tkeys.iter().chain(&new.tkeys).enumerate() {
+        let in_old = old.tkeys.iter().find(|k| k.address == tkey.address);
+        let in_new = new.tkeys.iter().find(|k| k.address == tkey.address);
+        if i >= old.tkeys.len() && in_old.is_some() {
+            continue;
+        }
+        compare(
+            Pool::Transparent,
+            tkey.address.clone(),
+            in_old.map(|k| k.have_spending_key()),
+            in_new.map(|k| k.have_spending_key()),
+        );
+    }
+
+    changes
+}
+
+fn diff_tx(old: &WalletTx, new: &WalletTx) -> Vec<FieldChange> {
+    let mut fields = vec![];
+    let mut field = |field: &'static str, old: String, new: String| {
+        if old != new {
+            fields.push(FieldChange {
+                field,
+                old: Some(old),
+                new: Some(new),
+            });
+        }
+    };
+    field(
+        "block",
+        u32::from(old.block).to_string(),
+        u32::from(new.block).to_string(),
+    );
+    field(
+        "unconfirmed",
+        old.unconfirmed.to_string(),
+        new.unconfirmed.to_string(),
+    );
+    field(
+        "Sapling notes",
+        old.sapling_notes.len().to_string(),
+        new.sapling_notes.len().to_string(),
+    );
+    field(
+        "Orchard notes",
+        old.orchard_notes.len().to_string(),
+        new.orchard_notes.len().to_string(),
+    );
+    field(
+        "Utxos",
+        old.utxos.len().to_string(),
+        new.utxos.len().to_string(),
+    );
+    field(
+        "outgoing",
+        old.outgoing_metadata.len().to_string(),
+        new.outgoing_metadata.len().to_string(),
+    );
+    field(
+        "value spent",
+        old.total_funds_spent().to_string(),
+        new.total_funds_spent().to_string(),
+    );
+    field(
+        "full_tx_scanned",
+        old.full_tx_scanned.to_string(),
+        new.full_tx_scanned.to_string(),
+    );
+
+    fields
+}
+
+/// The notes and Utxos of a transaction in both wallets whose spend status changed. Sapling
+/// notes are matched by nullifier, Orchard notes by note commitment and Utxos by output.
+fn diff_spends(old_tx: &WalletTx, new_tx: &WalletTx) -> Vec<NoteChange> {
+    let mut changes = vec![];
+    let mut compare = |pool: Pool, index: usize, value: u64, old, new| {
+        if old != new {
+            changes.push(NoteChange {
+                txid: new_tx.txid,
+                pool,
+                index,
+                value,
+                old,
+                new,
+            });
+        }
+    };
+
+    for (index, nd) in new_tx.sapling_notes.iter().enumerate() {
+        if let Some(old) = old_tx
+            .sapling_notes
+            .iter()
+            .find(|o| o.nullifier == nd.nullifier)
+        {
+            compare(
+                Pool::Sapling,
+                index,
+                nd.note.value().inner(),
+                SpendStatus::new(old.spent, old.unconfirmed_spent),
+                SpendStatus::new(nd.spent, nd.unconfirmed_spent),
+            );
+        }
+    }
+    for (index, nd) in new_tx.orchard_notes.iter().enumerate() {
+        let cmx = ExtractedNoteCommitment::from(nd.note.commitment());
+        if let Some(old) = old_tx
+            .orchard_notes
+            .iter()
+            .find(|o| ExtractedNoteCommitment::from(o.note.commitment()) == cmx)
+        {
+            compare(
+                Pool::Orchard,
+                index,
+                nd.note.value().inner(),
+                SpendStatus::new(old.spent, old.unconfirmed_spent),
+                SpendStatus::new(nd.spent, nd.unconfirmed_spent),
+            );
+        }
+    }
+    for (index, utxo) in new_tx.utxos.iter().enumerate() {
+        if let Some(old) = old_tx
+            .utxos
+            .iter()
+            .find(|o| o.output_index == utxo.output_index)
+        {
+            compare(
+                Pool::Transparent,
+                index,
+                utxo.value,
+                SpendStatus::of_utxo(old),
+                SpendStatus::of_utxo(utxo),
+            );
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,428 @@
+//! # Wallet health checks
+//!
+//! [`ZwlWallet::doctor`] runs a set of [`Rule`]s over the wallet and collects what they find.
+//! Each [`Finding`] has a severity, an explanation and a suggested fix. The built-in rules are
+//! listed by [`builtin_rules`]; other rules only have to implement [`Rule`] and can be run with
+//! [`ZwlWallet::run_rules`].
+
+use std::{cmp::Reverse, fmt, str::FromStr};
+
+use zcash_keys::encoding::encode_payment_address_p;
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        data::ChainType,
+        keys::{orchard::WalletOKeyType, sapling::WalletZKeyType, transparent::WalletTKeyType},
+    },
+};
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Worth knowing, nothing to fix.
+    Info,
+    /// ZecWallet Lite copes with it, but something is off.
+    Warning,
+    /// Secrets or funds are at risk, or ZecWallet Lite can't use that part of the wallet.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(WalletError::InvalidFormat(format!(
+                "Unknown severity {}, expected info, warning or error",
+                s
+            ))),
+        }
+    }
+}
+
+/// Something a [`Rule`] found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Id of the rule that found it.
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// The key, transaction or note concerned, if any.
+    pub subject: Option<String>,
+    pub message: String,
+    pub fix: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.severity, self.rule)?;
+        if let Some(subject) = &self.subject {
+            write!(f, " {}", subject)?;
+        }
+        write!(f, ": {}\n  fix: {}", self.message, self.fix)
+    }
+}
+
+/// A check of the wallet.
+pub trait Rule {
+    /// A short kebab-case id.
+    fn id(&self) -> &'static str;
+    /// What the rule checks, in one sentence.
+    fn description(&self) -> &'static str;
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding>;
+}
+
+/// The findings of a set of rules.
+#[derive(Debug, Clone, Default)]
+pub struct DoctorReport {
+    pub findings: Vec<Finding>,
+}
+
+impl DoctorReport {
+    /// The severity of the most serious finding, if there is any.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|f| f.severity).max()
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+/// The rules [`ZwlWallet::doctor`] runs.
+pub fn builtin_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(UnknownChain),
+        Box::new(EncryptedSeedInClear),
+        Box::new(LockedKeyWithoutEncryptedKey),
+        Box::new(DuplicateKey),
+        Box::new(NoteWithoutKey),
+        Box::new(LastTxid),
+        Box::new(UtxoSpentBeforeMined),
+    ]
+}
+
+impl ZwlWallet {
+    /// Runs the built-in rules.
+    pub fn doctor(&self) -> DoctorReport {
+        self.run_rules(&builtin_rules())
+    }
+
+    /// Runs `rules`, and sorts the findings from the most serious.
+    pub fn run_rules(&self, rules: &[Box<dyn Rule>]) -> DoctorReport {
+        let mut findings: Vec<Finding> = rules.iter().flat_map(|rule| rule.check(self)).collect();
+        findings.sort_by_key(|f| Reverse(f.severity));
+
+        DoctorReport { findings }
+    }
+}
+
+/// The chain name isn't one ZecWallet Lite knows.
+pub struct UnknownChain;
+
+impl Rule for UnknownChain {
+    fn id(&self) -> &'static str {
+        "unknown-chain"
+    }
+
+    fn description(&self) -> &'static str {
+        "The wallet's chain name is one ZecWallet Lite knows."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        match wallet.chain_name {
+            ChainType::Unknown => vec![Finding {
+                rule: self.id(),
+                severity: Severity::Error,
+                subject: None,
+                message: "The chain name is unknown, so the addresses and heights of the wallet \
+                          can't be checked"
+                    .to_string(),
+                fix: "Set the chain the keys were made for with `edit --chain`".to_string(),
+            }],
+            _ => vec![],
+        }
+    }
+}
+
+/// An encrypted wallet whose seed is stored in the clear.
+pub struct EncryptedSeedInClear;
+
+impl Rule for EncryptedSeedInClear {
+    fn id(&self) -> &'static str {
+        "encrypted-seed-in-clear"
+    }
+
+    fn description(&self) -> &'static str {
+        "An encrypted wallet doesn't store its seed in the clear."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        let keys = &wallet.keys;
+        if !keys.encrypted || keys.seed == [0u8; 32] {
+            return vec![];
+        }
+
+        vec![Finding {
+            rule: self.id(),
+            severity: Severity::Error,
+            subject: None,
+            message: "The wallet is encrypted, but was saved unlocked and its seed is readable \
+                      without the password"
+                .to_string(),
+            fix: "Lock the wallet in ZecWallet Lite before it saves, and delete the copies of \
+                  this file"
+                .to_string(),
+        }]
+    }
+}
+
+/// A locked imported key without the encrypted key to unlock it with.
+pub struct LockedKeyWithoutEncryptedKey;
+
+impl Rule for LockedKeyWithoutEncryptedKey {
+    fn id(&self) -> &'static str {
+        "locked-key-without-enc-key"
+    }
+
+    fn description(&self) -> &'static str {
+        "Every locked imported spending key has its encrypted key and nonce."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        let keys = &wallet.keys;
+        let network = wallet.chain_name.network();
+        // HD keys are derived again from the seed when unlocking, so only imported ones need
+        // their encrypted key.
+        let mut locked = vec![];
+        for zkey in &keys.zkeys {
+            if zkey.locked && zkey.keytype == WalletZKeyType::ImportedSpendingKey {
+                let address = network.map(|n| encode_payment_address_p(&n, &zkey.zaddress));
+                locked.push((address, zkey.enc_key.is_some(), zkey.nonce.is_some()));
+            }
+        }
+        for okey in &keys.okeys {
+            if okey.locked && okey.keytype == WalletOKeyType::ImportedSpendingKey {
+                let address = network.map(|n| okey.unified_address.encode(&n));
+                locked.push((address, okey.enc_key.is_some(), okey.nonce.is_some()));
+            }
+        }
+        for tkey in &keys.tkeys {
+            if tkey.locked && tkey.keytype == WalletTKeyType::ImportedKey {
+                locked.push((
+                    Some(tkey.address.clone()),
+                    tkey.enc_key.is_some(),
+                    tkey.nonce.is_some(),
+                ));
+            }
+        }
+
+        locked
+            .into_iter()
+            .filter(|(_, enc_key, nonce)| !enc_key || !nonce)
+            .map(|(address, enc_key, _)| Finding {
+                rule: self.id(),
+                severity: Severity::Error,
+                subject: address,
+                message: format!(
+                    "The key is locked but has no {}, so it can never be unlocked",
+                    if enc_key { "nonce" } else { "encrypted key" }
+                ),
+                fix: "Import the key again from a backup of it".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// The same key stored twice.
+pub struct DuplicateKey;
+
+impl Rule for DuplicateKey {
+    fn id(&self) -> &'static str {
+        "duplicate-key"
+    }
+
+    fn description(&self) -> &'static str {
+        "Every key is stored once."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        let keys = &wallet.keys;
+        let network = wallet.chain_name.network();
+        let mut duplicates = vec![];
+        for (i, zkey) in keys.zkeys.iter().enumerate() {
+            if keys.zkeys[..i].iter().any(|k| k.extfvk == zkey.extfvk) {
+                duplicates.push(network.map(|n| encode_payment_address_p(&n, &zkey.zaddress)));
+            }
+        }
+        for (i, okey) in keys.okeys.iter().enumerate() {
+            if keys.okeys[..i].iter().any(|k| k.fvk == okey.fvk) {
+                duplicates.push(network.map(|n| okey.unified_address.encode(&n)));
+            }
+        }
+        for (i, tkey) in keys.tkeys.iter().enumerate() {
+            if keys.tkeys[..i].iter().any(|k| k.address == tkey.address) {
+                duplicates.push(Some(tkey.address.clone()));
+            }
+        }
+
+        duplicates
+            .into_iter()
+            .map(|address| Finding {
+                rule: self.id(),
+                severity: Severity::Warning,
+                subject: address,
+                message: "The key is stored more than once, so its notes are counted twice"
+                    .to_string(),
+                fix: "Restore the wallet from its seed and import the key once".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Notes received by a viewing key the wallet doesn't have.
+pub struct NoteWithoutKey;
+
+impl Rule for NoteWithoutKey {
+    fn id(&self) -> &'static str {
+        "note-without-key"
+    }
+
+    fn description(&self) -> &'static str {
+        "Every note belongs to a key of the wallet."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        let keys = &wallet.keys;
+        let mut findings = vec![];
+        for wtx in wallet.transactions.current.values() {
+            for (index, nd) in wtx.sapling_notes.iter().enumerate() {
+                if !keys.zkeys.iter().any(|k| k.extfvk == nd.extfvk) {
+                    findings.push((wtx.txid, "Sapling", index));
+                }
+            }
+            for (index, nd) in wtx.orchard_notes.iter().enumerate() {
+                if !keys.okeys.iter().any(|k| k.fvk == nd.fvk) {
+                    findings.push((wtx.txid, "Orchard", index));
+                }
+            }
+        }
+        findings.sort();
+
+        findings
+            .into_iter()
+            .map(|(txid, pool, index)| Finding {
+                rule: self.id(),
+                severity: Severity::Warning,
+                subject: Some(format!("{} {} #{}", pool, txid, index)),
+                message: "The note's viewing key isn't in the wallet, so ZecWallet Lite can't \
+                          spend it or keep its witness"
+                    .to_string(),
+                fix: "Import the key that received the note, or rescan the wallet".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// `last_txid` pointing to a transaction the wallet doesn't have.
+pub struct LastTxid;
+
+impl Rule for LastTxid {
+    fn id(&self) -> &'static str {
+        "last-txid"
+    }
+
+    fn description(&self) -> &'static str {
+        "The last transaction id is one of the wallet's transactions."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        let txns = &wallet.transactions;
+        match txns.last_txid {
+            Some(txid)
+                if !txns.current.contains_key(&txid) && !txns.mempool.contains_key(&txid) =>
+            {
+                vec![Finding {
+                    rule: self.id(),
+                    severity: Severity::Warning,
+                    subject: Some(txid.to_string()),
+                    message: "The last transaction id isn't one of the wallet's transactions"
+                        .to_string(),
+                    fix: "Rescan the wallet".to_string(),
+                }]
+            }
+            None if !txns.current.is_empty() => vec![Finding {
+                rule: self.id(),
+                severity: Severity::Info,
+                subject: None,
+                message: "The wallet has transactions but no last transaction id".to_string(),
+                fix: "Nothing to do, ZecWallet Lite sets it on the next transaction".to_string(),
+            }],
+            _ => vec![],
+        }
+    }
+}
+
+/// Utxos spent below the height they were mined at.
+pub struct UtxoSpentBeforeMined;
+
+impl Rule for UtxoSpentBeforeMined {
+    fn id(&self) -> &'static str {
+        "utxo-spent-before-mined"
+    }
+
+    fn description(&self) -> &'static str {
+        "No Utxo is spent below the height it was mined at."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        let mut findings = vec![];
+        for wtx in wallet.transactions.current.values() {
+            for utxo in &wtx.utxos {
+                if let Some(spent_at) = utxo.spent_at_height
+                    && spent_at < utxo.height
+                {
+                    findings.push(Finding {
+                        rule: self.id(),
+                        severity: Severity::Error,
+                        subject: Some(format!("{}:{}", utxo.txid, utxo.output_index)),
+                        message: format!(
+                            "The Utxo was mined at {} but spent at {}",
+                            utxo.height, spent_at
+                        ),
+                        fix: "Rescan the wallet".to_string(),
+                    });
+                }
+            }
+        }
+        findings.sort_by(|a, b| a.subject.cmp(&b.subject));
+
+        findings
+    }
+}
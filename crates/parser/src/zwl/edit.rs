@@ -0,0 +1,88 @@
+//! # Editing wallet settings
+//!
+//! Setters for the parts of a wallet support most often has to fix by hand: the birthday a rescan
+//! starts from and the chain the wallet is for. The wallet options have their own setters on
+//! [`WalletOptions`](crate::zwl::data::WalletOptions).
+
+use zcash_primitives::consensus::{NetworkUpgrade, Parameters};
+
+use crate::{
+    error::WalletError,
+    zwl::{ZwlWallet, data::ChainType},
+};
+
+impl ZwlWallet {
+    /// Sets the height rescans start from.
+    ///
+    /// The birthday can't be below the Sapling activation, where ZecWallet Lite starts scanning,
+    /// nor above a transaction of the wallet or its latest synced block.
+    pub fn set_birthday(&mut self, birthday: u64) -> Result<(), WalletError> {
+        let invalid = |reason: String| {
+            WalletError::InvalidFormat(format!("Invalid birthday {}: {}", birthday, reason))
+        };
+
+        if let Some(sapling) = self
+            .chain_name
+            .network()
+            .and_then(|network| network.activation_height(NetworkUpgrade::Sapling))
+            && birthday < u64::from(sapling)
+        {
+            return Err(invalid(format!(
+                "below the Sapling activation at {}",
+                sapling
+            )));
+        }
+        if let Some(earliest) = self
+            .transactions
+            .current
+            .values()
+            .map(|wtx| u64::from(wtx.block))
+            .min()
+            && birthday > earliest
+        {
+            return Err(invalid(format!(
+                "above the wallet's first transaction at {}",
+                earliest
+            )));
+        }
+        if let Some(latest) = self.latest_height()
+            && birthday > latest
+        {
+            return Err(invalid(format!("above the latest synced block {}", latest)));
+        }
+
+        self.birthday = birthday;
+        Ok(())
+    }
+
+    /// Sets the chain the wallet is for.
+    ///
+    /// The addresses of the keys and the cached blocks belong to a chain, so switching a wallet
+    /// that has any of them needs `force`. An unknown chain is never accepted.
+    pub fn set_chain_name(
+        &mut self,
+        chain_name: ChainType,
+        force: bool,
+    ) -> Result<(), WalletError> {
+        if chain_name.name().is_none() {
+            return Err(WalletError::InvalidFormat(
+                "Can't set the chain to an unknown chain".to_string(),
+            ));
+        }
+
+        let keys = &self.keys;
+        let has_chain_data = !keys.zkeys.is_empty()
+            || !keys.tkeys.is_empty()
+            || !keys.okeys.is_empty()
+            || !self.blocks.is_empty();
+        if chain_name.name() != self.chain_name.name() && has_chain_data && !force {
+            return Err(WalletError::InvalidFormat(format!(
+                "The wallet's keys and blocks are for {}, switching to {} needs to be forced",
+                self.chain_name, chain_name
+            )));
+        }
+
+        self.chain_name = chain_name;
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+//! # Note-flow graph
+//!
+//! [`ZwlWallet::note_graph`] turns the wallet's history into a graph of how funds moved, for
+//! investigating disputed balances:
+//!
+//! - every transaction of the wallet is a node,
+//! - every Sapling or Orchard note and Utxo is an edge from the transaction that created it to
+//!   the one that spent it, or to a single `unspent` node,
+//! - every recipient of the outgoing metadata is a node, with an edge from the transaction that
+//!   paid it.
+//!
+//! Spending transactions missing from the wallet still get a node, flagged as unknown.
+//! [`NoteGraph::to_dot`] renders the graph for Graphviz.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use zcash_primitives::transaction::TxId;
+
+use crate::zwl::{ZwlWallet, scan::Pool, transactions::WalletTx};
+
+/// Id of the node of unspent notes.
+pub const UNSPENT: &str = "unspent";
+
+/// What a node stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Transaction {
+        /// Unknown for a spender only known from a Utxo without the height of the spend.
+        height: Option<u64>,
+        unconfirmed: bool,
+        /// Whether the transaction is in the wallet, rather than only known as a spender.
+        known: bool,
+    },
+    /// An address the wallet sent to.
+    Recipient,
+    Unspent,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// The txid, the address of a recipient, or [`UNSPENT`].
+    pub id: String,
+    pub kind: NodeKind,
+}
+
+/// What an edge stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    Note {
+        pool: Pool,
+        is_change: bool,
+        /// Spent by a transaction that isn't mined yet.
+        pending: bool,
+    },
+    Outgoing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    pub value: u64,
+    pub kind: EdgeKind,
+}
+
+/// The funds flowing through the wallet's transactions.
+#[derive(Debug, Clone, Default)]
+pub struct NoteGraph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl NoteGraph {
+    /// Renders the graph in the DOT language. Change is dashed, pending spends dotted, and
+    /// payments to recipients red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph notes {\n    rankdir=LR;\n");
+        for node in &self.nodes {
+            let attributes = match &node.kind {
+                NodeKind::Transaction {
+                    height,
+                    unconfirmed,
+                    known,
+                } => {
+                    let label = match height {
+                        Some(height) => format!("{}…\\n{}", &node.id[..16], height),
+                        None => format!("{}…", &node.id[..16]),
+                    };
+                    match (known, unconfirmed) {
+                        (false, _) => format!("label=\"{}\\nunknown\", style=dashed", label),
+                        (true, true) => format!("label=\"{}\\nunconfirmed\", style=dotted", label),
+                        (true, false) => format!("label=\"{}\"", label),
+                    }
+                }
+                NodeKind::Recipient => {
+                    format!("label=\"{}\", shape=box, color=red", node.id)
+                }
+                NodeKind::Unspent => "label=\"unspent\", shape=doublecircle".to_string(),
+            };
+            let _ = writeln!(dot, "    \"{}\" [{}];", node.id, attributes);
+        }
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::Note {
+                    pool,
+                    is_change,
+                    pending,
+                } => {
+                    let color = match pool {
+                        Pool::Transparent => "gray",
+                        Pool::Sapling => "blue",
+                        Pool::Orchard => "darkgreen",
+                    };
+                    let style = match (is_change, pending) {
+                        (_, true) => "dotted",
+                        (true, false) => "dashed",
+                        (false, false) => "solid",
+                    };
+                    format!(
+                        "label=\"{} {}\", color={}, style={}",
+                        pool, edge.value, color, style
+                    )
+                }
+                EdgeKind::Outgoing => format!("label=\"{}\", color=red", edge.value),
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [{}];",
+                edge.source, edge.target, attributes
+            );
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+impl ZwlWallet {
+    /// Builds the graph of the wallet's notes, Utxos and payments.
+    pub fn note_graph(&self) -> NoteGraph {
+        let mut wtxs: Vec<&WalletTx> = self.transactions.current.values().collect();
+        wtxs.sort_by_key(|wtx| (u32::from(wtx.block), wtx.txid));
+
+        let mut graph = NoteGraph::default();
+        // Spenders missing from the wallet, and the recipients, by id.
+        let mut unknown: BTreeMap<String, Option<u32>> = BTreeMap::new();
+        let mut recipients: Vec<String> = vec![];
+        let mut has_unspent = false;
+
+        for wtx in &wtxs {
+            graph.nodes.push(Node {
+                id: wtx.txid.to_string(),
+                kind: NodeKind::Transaction {
+                    height: Some(u32::from(wtx.block).into()),
+                    unconfirmed: wtx.unconfirmed,
+                    known: true,
+                },
+            });
+        }
+
+        let mut add_note = |graph: &mut NoteGraph,
+                            wtx: &WalletTx,
+                            pool: Pool,
+                            value: u64,
+                            is_change: bool,
+                            spent: Option<(TxId, Option<u32>)>,
+                            unconfirmed_spent: Option<(TxId, u32)>| {
+            let (target, pending) = match (spent, unconfirmed_spent) {
+                (Some(spent), _) => (Some(spent), false),
+                (None, Some((txid, height))) => (Some((txid, Some(height))), true),
+                (None, None) => (None, false),
+            };
+            let target = match target {
+                Some((txid, height)) => {
+                    if !self.transactions.current.contains_key(&txid) {
+                        let known = unknown.entry(txid.to_string()).or_default();
+                        *known = known.or(height);
+                    }
+                    txid.to_string()
+                }
+                None => {
+                    has_unspent = true;
+                    UNSPENT.to_string()
+                }
+            };
+            graph.edges.push(Edge {
+                source: wtx.txid.to_string(),
+                target,
+                value,
+                kind: EdgeKind::Note {
+                    pool,
+                    is_change,
+                    pending,
+                },
+            });
+        };
+
+        for wtx in &wtxs {
+            for nd in &wtx.sapling_notes {
+                add_note(
+                    &mut graph,
+                    wtx,
+                    Pool::Sapling,
+                    nd.note.value().inner(),
+                    nd.is_change,
+                    nd.spent.map(|(txid, height)| (txid, Some(height))),
+                    nd.unconfirmed_spent,
+                );
+            }
+            for nd in &wtx.orchard_notes {
+                add_note(
+                    &mut graph,
+                    wtx,
+                    Pool::Orchard,
+                    nd.note.value().inner(),
+                    nd.is_change,
+                    nd.spent.map(|(txid, height)| (txid, Some(height))),
+                    nd.unconfirmed_spent,
+                );
+            }
+            for utxo in &wtx.utxos {
+                // Old wallets don't always have the height of a spend.
+                let spent = utxo.spent.map(|txid| {
+                    let height = utxo.spent_at_height.and_then(|h| u32::try_from(h).ok());
+                    (txid, height)
+                });
+                add_note(
+                    &mut graph,
+                    wtx,
+                    Pool::Transparent,
+                    utxo.value,
+                    false,
+                    spent,
+                    utxo.unconfirmed_spent,
+                );
+            }
+            for metadata in &wtx.outgoing_metadata {
+                if !recipients.contains(&metadata.address) {
+                    recipients.push(metadata.address.clone());
+                }
+                graph.edges.push(Edge {
+                    source: wtx.txid.to_string(),
+                    target: metadata.address.clone(),
+                    value: metadata.value,
+                    kind: EdgeKind::Outgoing,
+                });
+            }
+        }
+
+        for (id, height) in unknown {
+            graph.nodes.push(Node {
+                id,
+                kind: NodeKind::Transaction {
+                    height: height.map(u64::from),
+                    unconfirmed: false,
+                    known: false,
+                },
+            });
+        }
+        for id in recipients {
+            graph.nodes.push(Node {
+                id,
+                kind: NodeKind::Recipient,
+            });
+        }
+        if has_unspent {
+            graph.nodes.push(Node {
+                id: UNSPENT.to_string(),
+                kind: NodeKind::Unspent,
+            });
+        }
+
+        graph
+    }
+}
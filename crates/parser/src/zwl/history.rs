@@ -0,0 +1,109 @@
+//! # Transaction history
+//!
+//! Lists the wallet's transactions, oldest first, with what each one received and spent.
+//!
+//! Transactions that were still in the mempool when a version <= 20 file was saved are listed
+//! as pending, and flagged expired once the latest block is past their expiry height. Newer
+//! versions keep unmined transactions with the others, flagged `unconfirmed`.
+
+use std::fmt;
+
+use zcash_primitives::transaction::TxId;
+
+use crate::zwl::{ZwlWallet, transactions::WalletTx};
+
+/// Whether a transaction has been mined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    Confirmed,
+    /// Not mined yet, but can still be.
+    Pending,
+    /// Not mined, and past its expiry height relative to the latest block.
+    Expired,
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TxStatus::Confirmed => write!(f, "confirmed"),
+            TxStatus::Pending => write!(f, "pending"),
+            TxStatus::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// A transaction of the wallet's history.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub txid: TxId,
+    pub height: u64,
+    pub datetime: u64,
+    pub status: TxStatus,
+    /// Value of the notes and Utxos received, change included.
+    pub received: u64,
+    /// Value of the wallet's notes and Utxos spent.
+    pub spent: u64,
+    /// Whether the transaction comes from the legacy mempool section.
+    pub from_mempool: bool,
+}
+
+impl HistoryEntry {
+    fn new(wtx: &WalletTx, latest_height: Option<u64>, from_mempool: bool) -> Self {
+        let status = if !wtx.unconfirmed {
+            TxStatus::Confirmed
+        } else if latest_height.is_some_and(|h| wtx.is_expired(h)) {
+            TxStatus::Expired
+        } else {
+            TxStatus::Pending
+        };
+
+        let received = wtx
+            .sapling_notes
+            .iter()
+            .map(|nd| nd.note.value().inner())
+            .chain(wtx.orchard_notes.iter().map(|nd| nd.note.value().inner()))
+            .chain(wtx.utxos.iter().map(|u| u.value))
+            .sum();
+
+        Self {
+            txid: wtx.txid,
+            height: u64::from(wtx.block),
+            datetime: wtx.datetime,
+            status,
+            received,
+            spent: wtx.total_sapling_value_spent
+                + wtx.total_orchard_value_spent
+                + wtx.total_transparent_value_spent,
+            from_mempool,
+        }
+    }
+
+    /// Change in the wallet's balance, in zatoshis.
+    pub fn net(&self) -> i64 {
+        self.received as i64 - self.spent as i64
+    }
+}
+
+impl ZwlWallet {
+    /// Returns the wallet's transactions, legacy mempool ones included, sorted by height and
+    /// time.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        let latest_height = self.latest_height();
+        let txns = &self.transactions;
+
+        let mut entries: Vec<HistoryEntry> = txns
+            .current
+            .values()
+            .map(|wtx| HistoryEntry::new(wtx, latest_height, false))
+            .chain(
+                txns.mempool
+                    .iter()
+                    .filter(|(txid, _)| !txns.current.contains_key(*txid))
+                    .map(|(_, wtx)| HistoryEntry::new(wtx, latest_height, true)),
+            )
+            .collect();
+
+        entries.sort_by_key(|e| (e.height, e.datetime, e.txid));
+        entries
+    }
+}
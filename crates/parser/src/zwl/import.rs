@@ -0,0 +1,304 @@
+//! # Importing keys
+//!
+//! [`ZwlWallet::import_key`] adds an imported key to the wallet, like the import dialog of
+//! ZecWallet Lite. [`ImportedKey::decode`] reads the usual encodings of the keys:
+//!
+//! - Sapling extended spending keys (`secret-extended-key-main1…`) and extended full viewing keys
+//!   (`zxviews1…`),
+//! - Orchard spending keys as 64 hex characters, and unified full viewing keys (`uview1…`) with
+//!   an Orchard part,
+//! - transparent private keys in WIF.
+//!
+//! When the wallet is encrypted, the spending key is encrypted with the wallet password the way
+//! ZecWallet Lite does it: a secretbox (XSalsa20-Poly1305) keyed with the double SHA-256 of the
+//! password. If the wallet is also locked, that is, its seed isn't stored in the clear, the new
+//! key is locked too and only its encrypted form is kept.
+//!
+//! Imported keys only see their transactions once the wallet rescans.
+
+use orchard_old::keys::{FullViewingKey, SpendingKey};
+use rand::{RngCore, rngs::OsRng};
+use sapling_crypto::zip32::{ExtendedFullViewingKey, ExtendedSpendingKey};
+use secp256k1::SecretKey;
+use sha2::{Digest, Sha256};
+use xsalsa20poly1305::{KeyInit, Nonce, XSalsa20Poly1305, aead::Aead};
+use zcash_keys::{
+    encoding::{
+        decode_extended_full_viewing_key, decode_extended_spending_key, encode_payment_address_p,
+    },
+    keys::UnifiedFullViewingKey,
+};
+use zcash_primitives::consensus::{Network, NetworkConstants};
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        keys::{Keys, orchard::WalletOKey, sapling::WalletZKey, transparent::WalletTKey},
+    },
+};
+
+/// A key to import into a wallet.
+#[derive(Clone)]
+pub enum ImportedKey {
+    SaplingSpendingKey(ExtendedSpendingKey),
+    SaplingViewingKey(ExtendedFullViewingKey),
+    OrchardSpendingKey(SpendingKey),
+    OrchardViewingKey(FullViewingKey),
+    TransparentKey(SecretKey),
+}
+
+impl ImportedKey {
+    /// Decodes a key for `network`.
+    pub fn decode(network: &Network, encoded: &str) -> Result<Self, WalletError> {
+        let encoded = encoded.trim();
+        let invalid = |what: &str, e: &dyn std::fmt::Debug| {
+            WalletError::InvalidFormat(format!("Invalid {}: {:?}", what, e))
+        };
+
+        let sk_hrp = network.hrp_sapling_extended_spending_key();
+        if encoded.starts_with(sk_hrp) {
+            return decode_extended_spending_key(sk_hrp, encoded)
+                .map(ImportedKey::SaplingSpendingKey)
+                .map_err(|e| invalid("Sapling spending key", &e));
+        }
+
+        let fvk_hrp = network.hrp_sapling_extended_full_viewing_key();
+        if encoded.starts_with(fvk_hrp) {
+            return decode_extended_full_viewing_key(fvk_hrp, encoded)
+                .map(ImportedKey::SaplingViewingKey)
+                .map_err(|e| invalid("Sapling viewing key", &e));
+        }
+
+        if encoded.starts_with("uview") {
+            let ufvk = UnifiedFullViewingKey::decode(network, encoded)
+                .map_err(|e| invalid("unified full viewing key", &e))?;
+            let orchard = ufvk.orchard().ok_or_else(|| {
+                WalletError::InvalidFormat(
+                    "The unified full viewing key has no Orchard part".to_string(),
+                )
+            })?;
+            return FullViewingKey::from_bytes(&orchard.to_bytes())
+                .map(ImportedKey::OrchardViewingKey)
+                .ok_or_else(|| invalid("Orchard viewing key", &encoded));
+        }
+
+        if let Ok(bytes) = <[u8; 32]>::try_from(hex::decode(encoded).unwrap_or_default()) {
+            return Option::from(SpendingKey::from_bytes(bytes))
+                .map(ImportedKey::OrchardSpendingKey)
+                .ok_or_else(|| invalid("Orchard spending key", &encoded));
+        }
+
+        if let Ok(bytes) = bs58::decode(encoded).with_check(None).into_vec() {
+            let prefix = match network {
+                Network::MainNetwork => 0x80,
+                Network::TestNetwork => 0xef,
+            };
+            // The private key can be followed by a flag for compressed public keys.
+            return match bytes.as_slice() {
+                [p, key @ ..]
+                    if *p == prefix && (key.len() == 32 || (key.len() == 33 && key[32] == 1)) =>
+                {
+                    SecretKey::from_slice(&key[..32])
+                        .map(ImportedKey::TransparentKey)
+                        .map_err(|e| invalid("transparent private key", &e))
+                }
+                _ => Err(WalletError::InvalidFormat(format!(
+                    "Not a WIF private key for {:?}",
+                    network
+                ))),
+            };
+        }
+
+        Err(WalletError::InvalidFormat(
+            "Unrecognized key encoding".to_string(),
+        ))
+    }
+}
+
+impl ZwlWallet {
+    /// Adds an imported key to the wallet and returns its address.
+    ///
+    /// `password` is needed if the wallet is encrypted. A key whose viewing key or address is
+    /// already in the wallet is rejected.
+    pub fn import_key(
+        &mut self,
+        key: ImportedKey,
+        password: Option<&str>,
+    ) -> Result<String, WalletError> {
+        let network = self.chain_name.require_network()?;
+        let encryption = if self.keys.encrypted {
+            Some(Encryption {
+                cipher: decrypt_seed(&self.keys, password)?.0,
+                locked: self.keys.seed == [0u8; 32],
+            })
+        } else {
+            None
+        };
+
+        match key {
+            ImportedKey::SaplingSpendingKey(extsk) => {
+                self.import_zkey(WalletZKey::new_imported_sk(extsk), &network, encryption)
+            }
+            ImportedKey::SaplingViewingKey(extfvk) => self.import_zkey(
+                WalletZKey::new_imported_viewkey(extfvk),
+                &network,
+                encryption,
+            ),
+            ImportedKey::OrchardSpendingKey(sk) => {
+                self.import_okey(WalletOKey::new_imported_osk(sk), &network, encryption)
+            }
+            ImportedKey::OrchardViewingKey(fvk) => self.import_okey(
+                WalletOKey::new_imported_fullviewkey(fvk),
+                &network,
+                encryption,
+            ),
+            ImportedKey::TransparentKey(pk) => {
+                self.import_tkey(WalletTKey::new_imported(&network, pk), encryption)
+            }
+        }
+    }
+
+    fn import_zkey(
+        &mut self,
+        mut zkey: WalletZKey,
+        network: &Network,
+        encryption: Option<Encryption>,
+    ) -> Result<String, WalletError> {
+        let address = encode_payment_address_p(network, &zkey.zaddress);
+        if self
+            .keys
+            .zkeys
+            .iter()
+            .any(|k| k.extfvk == zkey.extfvk || k.zaddress == zkey.zaddress)
+        {
+            return Err(duplicate(&address));
+        }
+
+        if let Some(encryption) = encryption {
+            if let Some(extsk) = &zkey.extsk {
+                let (enc_key, nonce) = encryption.seal(&extsk.to_bytes());
+                zkey.enc_key = Some(enc_key);
+                zkey.nonce = Some(nonce);
+            }
+            if encryption.locked {
+                zkey.extsk = None;
+                zkey.locked = true;
+            }
+        }
+        self.keys.zkeys.push(zkey);
+
+        Ok(address)
+    }
+
+    fn import_okey(
+        &mut self,
+        mut okey: WalletOKey,
+        network: &Network,
+        encryption: Option<Encryption>,
+    ) -> Result<String, WalletError> {
+        let address = okey.unified_address.encode(network);
+        if self
+            .keys
+            .okeys
+            .iter()
+            .any(|k| k.fvk == okey.fvk || k.unified_address.encode(network) == address)
+        {
+            return Err(duplicate(&address));
+        }
+
+        if let Some(encryption) = encryption {
+            if let Some(sk) = &okey.sk {
+                let (enc_key, nonce) = encryption.seal(sk.to_bytes());
+                okey.enc_key = Some(enc_key);
+                okey.nonce = Some(nonce);
+            }
+            if encryption.locked {
+                okey.sk = None;
+                okey.locked = true;
+            }
+        }
+        self.keys.okeys.push(okey);
+
+        Ok(address)
+    }
+
+    fn import_tkey(
+        &mut self,
+        mut tkey: WalletTKey,
+        encryption: Option<Encryption>,
+    ) -> Result<String, WalletError> {
+        let address = tkey.address.clone();
+        if self.keys.tkeys.iter().any(|k| k.address == address) {
+            return Err(duplicate(&address));
+        }
+
+        if let Some(encryption) = encryption {
+            if let Some(pk) = &tkey.pk {
+                let (enc_key, nonce) = encryption.seal(&pk.secret_bytes());
+                tkey.enc_key = Some(enc_key);
+                tkey.nonce = Some(nonce);
+            }
+            if encryption.locked {
+                tkey.pk = None;
+                tkey.locked = true;
+            }
+        }
+        self.keys.tkeys.push(tkey);
+
+        Ok(address)
+    }
+}
+
+fn duplicate(address: &str) -> WalletError {
+    WalletError::InvalidFormat(format!("{} is already in the wallet", address))
+}
+
+/// How the keys of an encrypted wallet are stored.
+struct Encryption {
+    cipher: XSalsa20Poly1305,
+    // Locked wallets only keep the encrypted spending keys.
+    locked: bool,
+}
+
+impl Encryption {
+    /// Encrypts `plaintext` with a new nonce, and returns the ciphertext and the nonce.
+    fn seal(&self, plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut nonce = [0u8; 24];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .expect("Encrypting in memory doesn't fail");
+
+        (ciphertext, nonce.to_vec())
+    }
+}
+
+/// Decrypts the seed of `keys` with `password`, and returns the cipher ZecWallet Lite encrypts
+/// the keys with and the seed entropy.
+pub fn decrypt_seed(
+    keys: &Keys,
+    password: Option<&str>,
+) -> Result<(XSalsa20Poly1305, [u8; 32]), WalletError> {
+    let password = password.ok_or_else(|| {
+        WalletError::InvalidFormat("The wallet is encrypted, its password is needed".to_string())
+    })?;
+    if keys.nonce.len() != 24 {
+        return Err(WalletError::InvalidFormat(format!(
+            "Invalid wallet nonce of {} bytes",
+            keys.nonce.len()
+        )));
+    }
+
+    let key = Sha256::digest(Sha256::digest(password.as_bytes()));
+    let cipher = XSalsa20Poly1305::new(&key);
+    let seed = cipher
+        .decrypt(Nonce::from_slice(&keys.nonce), &keys.enc_seed[..])
+        .map_err(|_| WalletError::InvalidFormat("Wrong password".to_string()))?;
+    let seed = seed
+        .try_into()
+        .map_err(|_| WalletError::InvalidFormat("Invalid encrypted seed".to_string()))?;
+
+    Ok((cipher, seed))
+}
@@ -2,10 +2,10 @@ pub mod orchard;
 pub mod sapling;
 pub mod transparent;
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use sapling_crypto::zip32::ExtendedFullViewingKey;
 use std::fmt::Display;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use tracing::instrument;
 use zcash_encoding::Vector;
 
@@ -96,6 +96,30 @@ impl Keys {
         })
     }
 
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // Write the version
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        // Write if wallet is encrypted
+        writer.write_u8(if self.encrypted { 1 } else { 0 })?;
+
+        // Write the encrypted seed bytes
+        writer.write_all(&self.enc_seed)?;
+
+        // Write the nonce
+        Vector::write(&mut writer, &self.nonce, |w, b| w.write_u8(*b))?;
+
+        // Write the seed. If the wallet is locked, this is all zeros
+        writer.write_all(&self.seed)?;
+
+        // Write all the keys
+        Vector::write(&mut writer, &self.okeys, |w, ok| ok.write(w))?;
+        Vector::write(&mut writer, &self.zkeys, |w, zk| zk.write(w))?;
+        Vector::write(&mut writer, &self.tkeys, |w, tk| tk.write(w))?;
+
+        Ok(())
+    }
+
     pub fn get_all_extfvks(&self) -> Vec<ExtendedFullViewingKey> {
         self.zkeys.iter().map(|zk| zk.extfvk.clone()).collect()
     }
@@ -1,6 +1,6 @@
 use std::{fmt, io};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use orchard_old::keys::{FullViewingKey, Scope, SpendingKey};
 use tracing::instrument;
 use zcash_encoding::{Optional, Vector};
@@ -39,6 +39,53 @@ impl WalletOKey {
         1
     }
 
+    /// Derives the HD key of ZIP 32 account `hdkey_num` from a BIP 39 seed, like ZecWallet Lite
+    /// does for its Orchard addresses.
+    pub fn new_hdkey(bip39_seed: &[u8], coin_type: u32, hdkey_num: u32) -> io::Result<Self> {
+        let sk = SpendingKey::from_zip32_seed(bip39_seed, coin_type, hdkey_num)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", e)))?;
+        let fvk = FullViewingKey::from(&sk);
+        let unified_address = unified_address(&fvk);
+
+        Ok(Self {
+            locked: false,
+            keytype: WalletOKeyType::HdKey,
+            sk: Some(sk),
+            fvk,
+            unified_address,
+            hdkey_num: Some(hdkey_num),
+            enc_key: None,
+            nonce: None,
+        })
+    }
+
+    /// An imported spending key.
+    pub fn new_imported_osk(sk: SpendingKey) -> Self {
+        let fvk = FullViewingKey::from(&sk);
+
+        Self {
+            sk: Some(sk),
+            keytype: WalletOKeyType::ImportedSpendingKey,
+            ..Self::new_imported_fullviewkey(fvk)
+        }
+    }
+
+    /// An imported full viewing key.
+    pub fn new_imported_fullviewkey(fvk: FullViewingKey) -> Self {
+        let unified_address = unified_address(&fvk);
+
+        Self {
+            locked: false,
+            keytype: WalletOKeyType::ImportedFullViewKey,
+            sk: None,
+            fvk,
+            unified_address,
+            hdkey_num: None,
+            enc_key: None,
+            nonce: None,
+        }
+    }
+
     #[instrument(level = "info", name = "WalletOKey::read", skip_all, err)]
     pub fn read<R: ReadBytesExt>(mut reader: R) -> io::Result<Self> {
         let version = reader.read_u8()?;
@@ -71,12 +118,7 @@ impl WalletOKey {
             Ok(SpendingKey::from_bytes(bytes).unwrap())
         })?;
 
-        // Derive unified address (orchard only) from fvk
-        let old_address: orchard_old::Address = fvk.address_at(0u64, Scope::External);
-
-        let new_address = NewAddress::from_old(old_address);
-        let unified_address = UnifiedAddress::from_receivers(Some(new_address), None, None)
-            .expect("Failed to construct unified address");
+        let unified_address = unified_address(&fvk);
 
         // read "possible" encrypted key
         let enc_key = Optional::read(&mut reader, |r| Vector::read(r, |r| r.read_u8()))?;
@@ -95,6 +137,54 @@ impl WalletOKey {
             nonce,
         })
     }
+
+    pub fn write<W: WriteBytesExt>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u8(Self::serialized_version())?;
+
+        writer.write_u32::<LittleEndian>(self.keytype.clone() as u32)?;
+
+        writer.write_u8(self.locked as u8)?;
+
+        Optional::write(&mut writer, self.hdkey_num, |w, n| {
+            w.write_u32::<LittleEndian>(n)
+        })?;
+
+        // Write the fvk
+        self.fvk.write(&mut writer)?;
+
+        // Write the sk, if available
+        Optional::write(&mut writer, self.sk.as_ref(), |w, sk| {
+            w.write_all(sk.to_bytes())
+        })?;
+
+        // Write enc_key
+        Optional::write(&mut writer, self.enc_key.as_ref(), |w, v| {
+            Vector::write(w, v, |w, byte| w.write_u8(*byte))
+        })?;
+
+        // Write nonce
+        Optional::write(&mut writer, self.nonce.as_ref(), |w, v| {
+            Vector::write(w, v, |w, byte| w.write_u8(*byte))
+        })
+    }
+
+    pub fn have_spending_key(&self) -> bool {
+        self.sk.is_some() || self.enc_key.is_some() || self.hdkey_num.is_some()
+    }
+}
+
+/// Derives the unified address (Orchard only) of `fvk`.
+fn unified_address(fvk: &FullViewingKey) -> UnifiedAddress {
+    let old_address: OldAddress = fvk.address_at(0u64, Scope::External);
+
+    orchard_unified_address(old_address)
+}
+
+/// Wraps an Orchard address into a unified address with no other receiver.
+pub(crate) fn orchard_unified_address(address: OldAddress) -> UnifiedAddress {
+    let new_address = NewAddress::from_old(address);
+    UnifiedAddress::from_receivers(Some(new_address), None, None)
+        .expect("Failed to construct unified address")
 }
 
 #[allow(unreachable_patterns)]
@@ -1,11 +1,12 @@
 use std::fmt;
-use std::io::{self, ErrorKind, Read};
+use std::io::{self, ErrorKind, Read, Write};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use sapling_crypto::PaymentAddress;
 use sapling_crypto::zip32::{ExtendedFullViewingKey, ExtendedSpendingKey};
 use tracing::instrument;
 use zcash_encoding::{Optional, Vector};
+use zcash_primitives::zip32::ChildIndex;
 
 #[derive(PartialEq, Debug, Clone)]
 pub enum WalletZKeyType {
@@ -35,6 +36,67 @@ impl WalletZKey {
         1
     }
 
+    /// Derives the HD key at `m/32'/coin_type'/hdkey_num'` from a BIP 39 seed, like ZecWallet
+    /// Lite does for its Sapling addresses.
+    #[allow(deprecated)]
+    pub fn new_hdkey(bip39_seed: &[u8], coin_type: u32, hdkey_num: u32) -> Self {
+        let extsk = ExtendedSpendingKey::from_path(
+            &ExtendedSpendingKey::master(bip39_seed),
+            &[
+                ChildIndex::hardened(32),
+                ChildIndex::hardened(coin_type),
+                ChildIndex::hardened(hdkey_num),
+            ],
+        );
+        let extfvk = extsk.to_extended_full_viewing_key();
+        let (_, zaddress) = extfvk.default_address();
+
+        Self {
+            keytype: WalletZKeyType::HdKey,
+            locked: false,
+            extsk: Some(extsk),
+            extfvk,
+            zaddress,
+            hdkey_num: Some(hdkey_num),
+            enc_key: None,
+            nonce: None,
+        }
+    }
+
+    /// An imported spending key.
+    #[allow(deprecated)]
+    pub fn new_imported_sk(extsk: ExtendedSpendingKey) -> Self {
+        let extfvk = extsk.to_extended_full_viewing_key();
+        let (_, zaddress) = extfvk.default_address();
+
+        Self {
+            keytype: WalletZKeyType::ImportedSpendingKey,
+            locked: false,
+            extsk: Some(extsk),
+            extfvk,
+            zaddress,
+            hdkey_num: None,
+            enc_key: None,
+            nonce: None,
+        }
+    }
+
+    /// An imported viewing key.
+    pub fn new_imported_viewkey(extfvk: ExtendedFullViewingKey) -> Self {
+        let (_, zaddress) = extfvk.default_address();
+
+        Self {
+            keytype: WalletZKeyType::ImportedViewKey,
+            locked: false,
+            extsk: None,
+            extfvk,
+            zaddress,
+            hdkey_num: None,
+            enc_key: None,
+            nonce: None,
+        }
+    }
+
     #[instrument(level = "info", name = "WalletZKey::read", skip_all, err)]
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
         let version = reader.read_u8()?;
@@ -84,6 +146,34 @@ impl WalletZKey {
         })
     }
 
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u8(Self::serialized_version())?;
+
+        writer.write_u32::<LittleEndian>(self.keytype.clone() as u32)?;
+
+        writer.write_u8(self.locked as u8)?;
+
+        Optional::write(&mut writer, self.extsk.as_ref(), |w, sk| {
+            ExtendedSpendingKey::write(sk, w)
+        })?;
+
+        ExtendedFullViewingKey::write(&self.extfvk, &mut writer)?;
+
+        Optional::write(&mut writer, self.hdkey_num, |w, n| {
+            w.write_u32::<LittleEndian>(n)
+        })?;
+
+        // Write enc_key
+        Optional::write(&mut writer, self.enc_key.as_ref(), |w, v| {
+            Vector::write(w, v, |w, byte| w.write_u8(*byte))
+        })?;
+
+        // Write nonce
+        Optional::write(&mut writer, self.nonce.as_ref(), |w, v| {
+            Vector::write(w, v, |w, byte| w.write_u8(*byte))
+        })
+    }
+
     pub fn have_spending_key(&self) -> bool {
         self.extsk.is_some() || self.enc_key.is_some() || self.hdkey_num.is_some()
     }
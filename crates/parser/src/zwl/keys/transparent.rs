@@ -1,11 +1,17 @@
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use secp256k1::SecretKey;
 use std::{
     fmt,
-    io::{self, Read},
+    io::{self, Read, Write},
 };
 use tracing::instrument;
 use zcash_encoding::{Optional, Vector};
+use zcash_keys::encoding::encode_transparent_address_p;
+use zcash_primitives::{
+    consensus::Parameters,
+    legacy::keys::{AccountPrivKey, IncomingViewingKey, NonHardenedChildIndex},
+    zip32::AccountId,
+};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum WalletTKeyType {
@@ -33,6 +39,59 @@ impl WalletTKey {
         1
     }
 
+    /// Derives the HD key at `m/44'/coin_type'/0'/0/hdkey_num` from a BIP 39 seed, like
+    /// ZecWallet Lite does for its transparent addresses.
+    pub fn new_hdkey<P: Parameters>(
+        params: &P,
+        bip39_seed: &[u8],
+        hdkey_num: u32,
+    ) -> io::Result<Self> {
+        let invalid =
+            |e: &dyn fmt::Debug| io::Error::new(io::ErrorKind::InvalidInput, format!("{:?}", e));
+
+        let index = NonHardenedChildIndex::from_index(hdkey_num)
+            .ok_or_else(|| invalid(&format!("Invalid key number {}", hdkey_num)))?;
+        let account = AccountPrivKey::from_seed(params, bip39_seed, AccountId::ZERO)
+            .map_err(|e| invalid(&e))?;
+        let pk = account
+            .derive_external_secret_key(index)
+            .map_err(|e| invalid(&e))?;
+        let taddr = account
+            .to_account_pubkey()
+            .derive_external_ivk()
+            .and_then(|ivk| ivk.derive_address(index))
+            .map_err(|e| invalid(&e))?;
+        let address = encode_transparent_address_p(params, &taddr);
+
+        Ok(Self {
+            keytype: WalletTKeyType::HdKey,
+            locked: false,
+            pk: Some(pk),
+            address,
+            hdkey_num: Some(hdkey_num),
+            enc_key: None,
+            nonce: None,
+        })
+    }
+
+    /// An imported private key.
+    #[allow(deprecated)]
+    pub fn new_imported<P: Parameters>(params: &P, pk: SecretKey) -> Self {
+        let pubkey = secp256k1::PublicKey::from_secret_key(&secp256k1::Secp256k1::new(), &pk);
+        let taddr = zcash_primitives::legacy::keys::pubkey_to_address(&pubkey);
+        let address = encode_transparent_address_p(params, &taddr);
+
+        Self {
+            keytype: WalletTKeyType::ImportedKey,
+            locked: false,
+            pk: Some(pk),
+            address,
+            hdkey_num: None,
+            enc_key: None,
+            nonce: None,
+        }
+    }
+
     #[instrument(level = "info", name = "WalletTKey::read", skip_all, err)]
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
         let version = reader.read_u8()?;
@@ -87,6 +146,40 @@ impl WalletTKey {
             address,
         })
     }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u8(Self::serialized_version())?;
+
+        writer.write_u32::<LittleEndian>(self.keytype as u32)?;
+
+        writer.write_u8(self.locked as u8)?;
+
+        Optional::write(&mut writer, self.pk, |w, pk| {
+            w.write_all(&pk.secret_bytes())
+        })?;
+
+        // Strings are written as <littleendian> len + bytes
+        writer.write_u64::<LittleEndian>(self.address.len() as u64)?;
+        writer.write_all(self.address.as_bytes())?;
+
+        Optional::write(&mut writer, self.hdkey_num, |w, n| {
+            w.write_u32::<LittleEndian>(n)
+        })?;
+
+        // Write enc_key
+        Optional::write(&mut writer, self.enc_key.as_ref(), |w, v| {
+            Vector::write(w, v, |w, byte| w.write_u8(*byte))
+        })?;
+
+        // Write nonce
+        Optional::write(&mut writer, self.nonce.as_ref(), |w, v| {
+            Vector::write(w, v, |w, byte| w.write_u8(*byte))
+        })
+    }
+
+    pub fn have_spending_key(&self) -> bool {
+        self.pk.is_some() || self.enc_key.is_some() || self.hdkey_num.is_some()
+    }
 }
 
 impl fmt::Display for WalletTKey {
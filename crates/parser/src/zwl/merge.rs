@@ -0,0 +1,521 @@
+//! # Merging wallets
+//!
+//! [`ZwlWallet::merge`] folds another wallet into this one, for users who ran ZecWallet Lite on
+//! several machines, each with its own imported keys:
+//!
+//! - keys are deduplicated by viewing key or address, and a viewing key gains the spending key
+//!   the other wallet has for it,
+//! - transactions are combined by txid, their notes and Utxos by nullifier, note commitment or
+//!   output, each keeping the most advanced spend status of the two,
+//! - the birthday becomes the lower of the two.
+//!
+//! The sync state (cached blocks and commitment trees) of this wallet is kept. Whatever can't be
+//! combined is kept from this wallet and reported as a [`MergeConflict`]. Encrypted wallets can
+//! only be merged with wallets encrypted with the same seed and password.
+
+use std::fmt;
+
+use orchard_old::note::ExtractedNoteCommitment;
+use zcash_keys::encoding::encode_payment_address_p;
+use zcash_primitives::{consensus::Network, transaction::TxId};
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        diff::SpendStatus,
+        keys::{orchard::WalletOKeyType, sapling::WalletZKeyType, transparent::WalletTKeyType},
+        scan::Pool,
+        transactions::WalletTx,
+    },
+};
+
+/// Something the wallets disagree on, resolved by keeping this wallet's side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// The wallets have different seeds, so the other wallet's HD keys were added as imported
+    /// keys.
+    Seed { hd_keys: usize },
+    WalletOption {
+        option: &'static str,
+        kept: String,
+        other: String,
+    },
+    /// The transaction was mined at different heights.
+    TxHeight { txid: TxId, kept: u32, other: u32 },
+    /// The note or Utxo was spent by different transactions.
+    Spend {
+        txid: TxId,
+        pool: Pool,
+        index: usize,
+        kept: SpendStatus,
+        other: SpendStatus,
+    },
+    /// The note came from the other wallet, whose witness for it isn't part of the kept sync
+    /// state, so it can't be spent before a rescan. The witnesses of Sapling notes are dropped.
+    Unwitnessed {
+        txid: TxId,
+        pool: Pool,
+        index: usize,
+    },
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeConflict::Seed { hd_keys } => write!(
+                f,
+                "the seeds differ, {} HD keys of the other wallet were added as imported keys",
+                hd_keys
+            ),
+            MergeConflict::WalletOption {
+                option,
+                kept,
+                other,
+            } => write!(f, "{}: kept {}, other {}", option, kept, other),
+            MergeConflict::TxHeight { txid, kept, other } => {
+                write!(f, "tx {} mined at {}: kept, other at {}", txid, kept, other)
+            }
+            MergeConflict::Spend {
+                txid,
+                pool,
+                index,
+                kept,
+                other,
+            } => write!(
+                f,
+                "{} {} #{} {}: kept, other {}",
+                pool, txid, index, kept, other
+            ),
+            MergeConflict::Unwitnessed { txid, pool, index } => write!(
+                f,
+                "{} {} #{} has no witness in the merged sync state, rescan to spend it",
+                pool, txid, index
+            ),
+        }
+    }
+}
+
+/// What [`ZwlWallet::merge`] took from the other wallet.
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    pub keys_added: Vec<(Pool, String)>,
+    /// Keys that were only viewing keys, and got their spending key from the other wallet.
+    pub keys_upgraded: Vec<(Pool, String)>,
+    pub txs_added: Vec<TxId>,
+    /// Notes and Utxos added, in new transactions or existing ones.
+    pub notes_added: usize,
+    /// Notes and Utxos whose spend status was advanced.
+    pub spends_updated: usize,
+    /// The birthday before and after, if it changed.
+    pub birthday: Option<(u64, u64)>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (pool, address) in &self.keys_added {
+            writeln!(f, "+ {} key {}", pool, address)?;
+        }
+        for (pool, address) in &self.keys_upgraded {
+            writeln!(f, "+ {} spending key for {}", pool, address)?;
+        }
+        for txid in &self.txs_added {
+            writeln!(f, "+ tx {}", txid)?;
+        }
+        writeln!(
+            f,
+            "+ {} notes, {} spends updated",
+            self.notes_added, self.spends_updated
+        )?;
+        if let Some((old, new)) = self.birthday {
+            writeln!(f, "~ birthday: {} -> {}", old, new)?;
+        }
+        for conflict in &self.conflicts {
+            writeln!(f, "! {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Merges `other` into this wallet.
+    ///
+    /// Fails without changing the wallet if the wallets are for different chains, or if their
+    /// encryption differs.
+    pub fn merge(&mut self, other: &ZwlWallet) -> Result<MergeReport, WalletError> {
+        if self.chain_name.name() != other.chain_name.name() {
+            return Err(WalletError::InvalidFormat(format!(
+                "Can't merge a {} wallet into a {} one",
+                other.chain_name, self.chain_name
+            )));
+        }
+        let (keys, other_keys) = (&self.keys, &other.keys);
+        if keys.encrypted != other_keys.encrypted
+            || (keys.encrypted
+                && (keys.enc_seed != other_keys.enc_seed || keys.nonce != other_keys.nonce))
+        {
+            return Err(WalletError::InvalidFormat(
+                "The wallets are encrypted differently, decrypt them before merging".to_string(),
+            ));
+        }
+
+        let mut report = MergeReport::default();
+        // The same encrypted seed is the same seed, even if only one of the wallets is locked.
+        let same_seed = keys.encrypted || keys.seed == other_keys.seed;
+        // Addresses only label the keys in the report.
+        let network = self.chain_name.network().unwrap_or(Network::MainNetwork);
+        self.merge_keys(other, same_seed, &network, &mut report);
+        self.merge_transactions(other, &mut report);
+
+        if other.birthday < self.birthday {
+            report.birthday = Some((self.birthday, other.birthday));
+            self.birthday = other.birthday;
+        }
+        let (options, other_options) = (&self.wallet_options, &other.wallet_options);
+        if options.download_memos() != other_options.download_memos() {
+            report.conflicts.push(MergeConflict::WalletOption {
+                option: "download_memos",
+                kept: options.download_memos().to_string(),
+                other: other_options.download_memos().to_string(),
+            });
+        }
+        if options.spam_threshold() != other_options.spam_threshold() {
+            report.conflicts.push(MergeConflict::WalletOption {
+                option: "spam_threshold",
+                kept: options.spam_threshold().to_string(),
+                other: other_options.spam_threshold().to_string(),
+            });
+        }
+
+        Ok(report)
+    }
+
+    fn merge_keys(
+        &mut self,
+        other: &ZwlWallet,
+        same_seed: bool,
+        network: &Network,
+        report: &mut MergeReport,
+    ) {
+        let keys = &mut self.keys;
+        let mut hd_keys = 0;
+
+        for zkey in &other.keys.zkeys {
+            let address = encode_payment_address_p(network, &zkey.zaddress);
+            if let Some(kept) = keys.zkeys.iter_mut().find(|k| k.extfvk == zkey.extfvk) {
+                if !kept.have_spending_key() && zkey.have_spending_key() {
+                    kept.keytype = WalletZKeyType::ImportedSpendingKey;
+                    kept.locked = zkey.locked;
+                    kept.extsk = zkey.extsk.clone();
+                    kept.enc_key = zkey.enc_key.clone();
+                    kept.nonce = zkey.nonce.clone();
+                    report.keys_upgraded.push((Pool::Sapling, address));
+                }
+                continue;
+            }
+
+            let mut zkey = zkey.clone();
+            if !same_seed && zkey.keytype == WalletZKeyType::HdKey {
+                zkey.keytype = WalletZKeyType::ImportedSpendingKey;
+                zkey.hdkey_num = None;
+                hd_keys += 1;
+            }
+            keys.zkeys.push(zkey);
+            report.keys_added.push((Pool::Sapling, address));
+        }
+
+        for okey in &other.keys.okeys {
+            let address = okey.unified_address.encode(network);
+            if let Some(kept) = keys.okeys.iter_mut().find(|k| k.fvk == okey.fvk) {
+                if !kept.have_spending_key() && okey.have_spending_key() {
+                    kept.keytype = WalletOKeyType::ImportedSpendingKey;
+                    kept.locked = okey.locked;
+                    kept.sk = okey.sk;
+                    kept.enc_key = okey.enc_key.clone();
+                    kept.nonce = okey.nonce.clone();
+                    report.keys_upgraded.push((Pool::Orchard, address));
+                }
+                continue;
+            }
+
+            let mut okey = okey.clone();
+            if !same_seed && okey.keytype == WalletOKeyType::HdKey {
+                okey.keytype = WalletOKeyType::ImportedSpendingKey;
+                okey.hdkey_num = None;
+                hd_keys += 1;
+            }
+            keys.okeys.push(okey);
+            report.keys_added.push((Pool::Orchard, address));
+        }
+
+        for tkey in &other.keys.tkeys {
+            if keys.tkeys.iter().any(|k| k.address == tkey.address) {
+                continue;
+            }
+
+            let mut tkey = tkey.clone();
+            if !same_seed && tkey.keytype == WalletTKeyType::HdKey {
+                tkey.keytype = WalletTKeyType::ImportedKey;
+                tkey.hdkey_num = None;
+                hd_keys += 1;
+            }
+            report
+                .keys_added
+                .push((Pool::Transparent, tkey.address.clone()));
+            keys.tkeys.push(tkey);
+        }
+
+        if hd_keys > 0 {
+            report.conflicts.push(MergeConflict::Seed { hd_keys });
+        }
+    }
+
+    fn merge_transactions(&mut self, other: &ZwlWallet, report: &mut MergeReport) {
+        // Sapling witnesses are only good for the height the other wallet synced to, so they are
+        // dropped, and Orchard ones are marked in the other wallet's tree.
+        let same_height = self.latest_height() == other.latest_height();
+        let tree = self.orchard_witnesses.as_ref();
+        let check_witnesses = |wtx: &mut WalletTx, new: &[Option<(Pool, usize)>]| {
+            let mut conflicts = vec![];
+            for (pool, index) in new.iter().flatten() {
+                let unwitnessed = match pool {
+                    Pool::Sapling => {
+                        let nd = &mut wtx.sapling_notes[*index];
+                        let unwitnessed =
+                            nd.spent.is_none() && !nd.witnesses.is_empty() && !same_height;
+                        if unwitnessed {
+                            nd.witnesses.clear();
+                        }
+                        unwitnessed
+                    }
+                    Pool::Orchard => {
+                        let nd = &wtx.orchard_notes[*index];
+                        nd.spent.is_none()
+                            && nd.witness_position.is_some_and(|position| {
+                                !tree.is_some_and(|t| t.witnessed_indices().contains_key(&position))
+                            })
+                    }
+                    Pool::Transparent => false,
+                };
+                if unwitnessed {
+                    conflicts.push(MergeConflict::Unwitnessed {
+                        txid: wtx.txid,
+                        pool: *pool,
+                        index: *index,
+                    });
+                }
+            }
+            conflicts
+        };
+
+        let txns = &mut self.transactions;
+        let mut txids: Vec<&TxId> = other.transactions.current.keys().collect();
+        txids.sort_by_key(|txid| (u32::from(other.transactions.current[*txid].block), **txid));
+
+        for txid in txids {
+            let theirs = &other.transactions.current[txid];
+            match txns.current.get_mut(txid) {
+                Some(kept) => {
+                    let new = merge_tx(kept, theirs, &mut report.conflicts);
+                    report.spends_updated += new.spends_updated;
+                    report.notes_added += new.notes.len();
+                    report.conflicts.extend(check_witnesses(kept, &new.notes));
+                }
+                None => {
+                    let notes: Vec<_> = (0..theirs.sapling_notes.len())
+                        .map(|i| Some((Pool::Sapling, i)))
+                        .chain((0..theirs.orchard_notes.len()).map(|i| Some((Pool::Orchard, i))))
+                        .collect();
+                    let mut theirs = theirs.clone();
+                    report
+                        .conflicts
+                        .extend(check_witnesses(&mut theirs, &notes));
+                    report.notes_added += notes.len() + theirs.utxos.len();
+                    report.txs_added.push(*txid);
+                    txns.current.insert(*txid, theirs);
+                }
+            }
+        }
+
+        for (txid, wtx) in &other.transactions.mempool {
+            if !txns.current.contains_key(txid) && !txns.mempool.contains_key(txid) {
+                txns.mempool.insert(*txid, wtx.clone());
+            }
+        }
+        if txns.last_txid.is_none() {
+            txns.last_txid = other.transactions.last_txid;
+        }
+    }
+}
+
+/// What [`merge_tx`] added to a transaction.
+#[derive(Default)]
+struct TxMerge {
+    /// The notes added, `None` for Utxos.
+    notes: Vec<Option<(Pool, usize)>>,
+    spends_updated: usize,
+}
+
+/// Combines the other wallet's copy of a transaction into the kept one.
+fn merge_tx(kept: &mut WalletTx, theirs: &WalletTx, conflicts: &mut Vec<MergeConflict>) -> TxMerge {
+    let mut merge = TxMerge::default();
+    let txid = kept.txid;
+
+    if kept.unconfirmed && !theirs.unconfirmed {
+        kept.block = theirs.block;
+        kept.datetime = theirs.datetime;
+        kept.unconfirmed = false;
+    } else if !kept.unconfirmed && !theirs.unconfirmed && kept.block != theirs.block {
+        conflicts.push(MergeConflict::TxHeight {
+            txid,
+            kept: kept.block.into(),
+            other: theirs.block.into(),
+        });
+    }
+
+    // Takes the other spend status if it's further along, and reports a conflict if both are as
+    // far along but differ.
+    let mut merge_spend = |pool: Pool, index: usize, kept: SpendStatus, other: SpendStatus| {
+        let rank = |status: SpendStatus| match status {
+            SpendStatus::Unspent => 0,
+            SpendStatus::Pending(..) => 1,
+            SpendStatus::Spent(..) => 2,
+        };
+        if kept == other || rank(other) < rank(kept) {
+            false
+        } else if rank(other) == rank(kept) {
+            conflicts.push(MergeConflict::Spend {
+                txid,
+                pool,
+                index,
+                kept,
+                other,
+            });
+            false
+        } else {
+            merge.spends_updated += 1;
+            true
+        }
+    };
+
+    for nd in &theirs.sapling_notes {
+        match kept
+            .sapling_notes
+            .iter()
+            .position(|k| k.nullifier == nd.nullifier)
+        {
+            Some(index) => {
+                let k = &mut kept.sapling_notes[index];
+                if merge_spend(
+                    Pool::Sapling,
+                    index,
+                    SpendStatus::new(k.spent, k.unconfirmed_spent),
+                    SpendStatus::new(nd.spent, nd.unconfirmed_spent),
+                ) {
+                    k.spent = nd.spent;
+                    k.unconfirmed_spent = nd.unconfirmed_spent;
+                }
+                if k.memo.is_none() {
+                    k.memo = nd.memo.clone();
+                }
+            }
+            None => {
+                merge
+                    .notes
+                    .push(Some((Pool::Sapling, kept.sapling_notes.len())));
+                kept.sapling_notes.push(nd.clone());
+            }
+        }
+    }
+
+    for nd in &theirs.orchard_notes {
+        // created_at isn't saved in the wallet file, so notes are told apart by commitment.
+        let cmx = ExtractedNoteCommitment::from(nd.note.commitment());
+        match kept
+            .orchard_notes
+            .iter()
+            .position(|k| ExtractedNoteCommitment::from(k.note.commitment()) == cmx)
+        {
+            Some(index) => {
+                let k = &mut kept.orchard_notes[index];
+                if merge_spend(
+                    Pool::Orchard,
+                    index,
+                    SpendStatus::new(k.spent, k.unconfirmed_spent),
+                    SpendStatus::new(nd.spent, nd.unconfirmed_spent),
+                ) {
+                    k.spent = nd.spent;
+                    k.unconfirmed_spent = nd.unconfirmed_spent;
+                }
+                if k.memo.is_none() {
+                    k.memo = nd.memo.clone();
+                }
+            }
+            None => {
+                merge
+                    .notes
+                    .push(Some((Pool::Orchard, kept.orchard_notes.len())));
+                kept.orchard_notes.push(nd.clone());
+            }
+        }
+    }
+
+    for utxo in &theirs.utxos {
+        match kept
+            .utxos
+            .iter()
+            .position(|k| k.output_index == utxo.output_index)
+        {
+            Some(index) => {
+                let k = &mut kept.utxos[index];
+                if merge_spend(
+                    Pool::Transparent,
+                    index,
+                    SpendStatus::of_utxo(k),
+                    SpendStatus::of_utxo(utxo),
+                ) {
+                    k.spent = utxo.spent;
+                    k.spent_at_height = utxo.spent_at_height;
+                    k.unconfirmed_spent = utxo.unconfirmed_spent;
+                }
+            }
+            None => {
+                merge.notes.push(None);
+                kept.utxos.push(utxo.clone());
+            }
+        }
+    }
+
+    for nf in &theirs.s_spent_nullifiers {
+        if !kept.s_spent_nullifiers.contains(nf) {
+            kept.s_spent_nullifiers.push(*nf);
+        }
+    }
+    for nf in &theirs.o_spent_nullifiers {
+        if !kept.o_spent_nullifiers.contains(nf) {
+            kept.o_spent_nullifiers.push(*nf);
+        }
+    }
+    for metadata in &theirs.outgoing_metadata {
+        if !kept.outgoing_metadata.contains(metadata) {
+            kept.outgoing_metadata.push(metadata.clone());
+        }
+    }
+
+    kept.total_sapling_value_spent = kept
+        .total_sapling_value_spent
+        .max(theirs.total_sapling_value_spent);
+    kept.total_orchard_value_spent = kept
+        .total_orchard_value_spent
+        .max(theirs.total_orchard_value_spent);
+    kept.total_transparent_value_spent = kept
+        .total_transparent_value_spent
+        .max(theirs.total_transparent_value_spent);
+    kept.full_tx_scanned |= theirs.full_tx_scanned;
+    if kept.zec_price.is_none() {
+        kept.zec_price = theirs.zec_price;
+    }
+
+    merge
+}
@@ -0,0 +1,272 @@
+//! # Online verification
+//!
+//! Compares what the wallet recorded with what a lightwalletd server reports, when built with
+//! the `online` feature:
+//!
+//! - the cached block hashes, to detect blocks that are no longer on the main chain,
+//! - the nullifiers of the wallet's notes, by scanning the spends and actions of every block
+//!   from the start height to the server's tip,
+//! - the transactions of each transparent address, with `GetTaddressTxids`.
+//!
+//! Spends below the start height aren't seen, so notes recorded spent below it are left out of
+//! the report. Start from the birthday to check every note.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId},
+    transaction::{Transaction, TxId},
+};
+
+use crate::{
+    error::WalletError,
+    lightwalletd::client::{LightwalletdClient, rpc_error},
+    zwl::{ZwlWallet, scan::Pool},
+};
+
+/// A cached block whose hash differs from the server's block at the same height.
+#[derive(Debug, Clone)]
+pub struct ReorgedBlock {
+    pub height: u64,
+    pub recorded_hash: String,
+    pub actual_hash: String,
+}
+
+/// Whether a note has been spent, and by which transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendState {
+    Unspent,
+    Spent { txid: TxId, height: u64 },
+}
+
+impl fmt::Display for SpendState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpendState::Unspent => write!(f, "unspent"),
+            SpendState::Spent { txid, height } => write!(f, "spent in {} at {}", txid, height),
+        }
+    }
+}
+
+/// Recorded and actual spend state of a note.
+#[derive(Debug, Clone)]
+pub struct NoteSpendCheck {
+    pub txid: TxId,
+    pub pool: Pool,
+    pub note_index: usize,
+    pub value: u64,
+    pub recorded: SpendState,
+    pub actual: SpendState,
+}
+
+impl NoteSpendCheck {
+    pub fn is_consistent(&self) -> bool {
+        self.recorded == self.actual
+    }
+}
+
+/// Recorded and actual transactions of a transparent address.
+#[derive(Debug, Clone)]
+pub struct AddressCheck {
+    pub address: String,
+    pub recorded: BTreeSet<TxId>,
+    pub actual: BTreeSet<TxId>,
+}
+
+impl AddressCheck {
+    /// Transactions on chain that the wallet didn't record.
+    pub fn missing(&self) -> impl Iterator<Item = &TxId> {
+        self.actual.difference(&self.recorded)
+    }
+
+    /// Transactions the wallet recorded that the server doesn't know about.
+    pub fn unexpected(&self) -> impl Iterator<Item = &TxId> {
+        self.recorded.difference(&self.actual)
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.recorded == self.actual
+    }
+}
+
+/// Result of verifying a wallet against a lightwalletd server.
+#[derive(Debug, Clone)]
+pub struct OnlineReport {
+    pub server_tip: u64,
+    /// Range of blocks fetched from the server, inclusive.
+    pub scanned_range: Option<(u64, u64)>,
+    pub blocks_scanned: usize,
+    pub reorged_blocks: Vec<ReorgedBlock>,
+    /// Cached blocks above the server's tip.
+    pub blocks_above_tip: Vec<u64>,
+    pub notes: Vec<NoteSpendCheck>,
+    pub addresses: Vec<AddressCheck>,
+}
+
+impl OnlineReport {
+    pub fn is_consistent(&self) -> bool {
+        self.reorged_blocks.is_empty()
+            && self.blocks_above_tip.is_empty()
+            && self.notes.iter().all(|n| n.is_consistent())
+            && self.addresses.iter().all(|a| a.is_consistent())
+    }
+}
+
+impl ZwlWallet {
+    /// Verifies the wallet against the server behind `client`, from `start_height` to the tip.
+    ///
+    /// Without a start height, the check starts at the lowest cached block, or at the birthday
+    /// if there are none.
+    pub async fn verify_online(
+        &self,
+        client: &mut LightwalletdClient,
+        start_height: Option<u64>,
+    ) -> Result<OnlineReport, WalletError> {
+        let network = self.chain_name.require_network()?;
+
+        let server_tip = client.latest_block().await?.height;
+        let start = start_height
+            .or_else(|| self.blocks.last().map(|b| b.height))
+            .unwrap_or(self.birthday)
+            .max(1);
+
+        let cached: HashMap<u64, &str> = self
+            .blocks
+            .iter()
+            .map(|b| (b.height, b.hash.as_str()))
+            .collect();
+        let mut blocks_above_tip: Vec<u64> =
+            cached.keys().copied().filter(|h| *h > server_tip).collect();
+        blocks_above_tip.sort();
+
+        // Notes to check, by nullifier. Notes spent below the start height can't be checked.
+        let mut notes = vec![];
+        let mut nullifiers = HashMap::new();
+        for wtx in self.transactions.current.values() {
+            let sapling = wtx.sapling_notes.iter().enumerate().map(|(i, nd)| {
+                (
+                    Pool::Sapling,
+                    i,
+                    nd.nullifier.0,
+                    nd.spent,
+                    nd.note.value().inner(),
+                )
+            });
+            let orchard = wtx.orchard_notes.iter().enumerate().map(|(i, nd)| {
+                let nf = nd.note.nullifier(&nd.fvk).to_bytes();
+                (Pool::Orchard, i, nf, nd.spent, nd.note.value().inner())
+            });
+
+            for (pool, note_index, nf, spent, value) in sapling.chain(orchard) {
+                let recorded = match spent {
+                    Some((_, height)) if u64::from(height) < start => continue,
+                    Some((txid, height)) => SpendState::Spent {
+                        txid,
+                        height: u64::from(height),
+                    },
+                    None => SpendState::Unspent,
+                };
+
+                nullifiers.insert(nf, notes.len());
+                notes.push(NoteSpendCheck {
+                    txid: wtx.txid,
+                    pool,
+                    note_index,
+                    value,
+                    recorded,
+                    actual: SpendState::Unspent,
+                });
+            }
+        }
+
+        let mut reorged_blocks = vec![];
+        let mut blocks_scanned = 0;
+        if start <= server_tip {
+            let mut stream = client.block_range(start, server_tip).await?;
+            while let Some(block) = stream.message().await.map_err(rpc_error)? {
+                blocks_scanned += 1;
+
+                if let Some(recorded_hash) = cached.get(&block.height) {
+                    let actual_hash = block.hash().to_string();
+                    if *recorded_hash != actual_hash {
+                        reorged_blocks.push(ReorgedBlock {
+                            height: block.height,
+                            recorded_hash: recorded_hash.to_string(),
+                            actual_hash,
+                        });
+                    }
+                }
+
+                for ctx in &block.vtx {
+                    let spent = ctx
+                        .spends
+                        .iter()
+                        .map(|s| &s.nf)
+                        .chain(ctx.actions.iter().map(|a| &a.nullifier));
+
+                    for nf in spent {
+                        if let Some(i) = nf
+                            .as_slice()
+                            .try_into()
+                            .ok()
+                            .and_then(|nf: [u8; 32]| nullifiers.get(&nf))
+                        {
+                            notes[*i].actual = SpendState::Spent {
+                                txid: ctx.txid(),
+                                height: block.height,
+                            };
+                        }
+                    }
+                }
+            }
+        }
+        reorged_blocks.sort_by_key(|b| b.height);
+        notes.sort_by_key(|n| (n.txid, n.pool, n.note_index));
+
+        let mut addresses = vec![];
+        for tkey in &self.keys.tkeys {
+            let mut recorded = BTreeSet::new();
+            for wtx in self.transactions.current.values() {
+                for utxo in wtx.utxos.iter().filter(|u| u.address == tkey.address) {
+                    recorded.insert(utxo.txid);
+                    recorded.extend(utxo.spent);
+                }
+            }
+
+            let mut actual = BTreeSet::new();
+            for raw in client
+                .taddress_txids(&tkey.address, self.birthday, server_tip)
+                .await?
+            {
+                let branch_id =
+                    BranchId::for_height(&network, BlockHeight::from_u32(raw.height as u32));
+                let tx = Transaction::read(&raw.data[..], branch_id).map_err(|e| {
+                    WalletError::Lightwalletd(format!(
+                        "Couldn't parse a transaction of {}: {}",
+                        tkey.address, e
+                    ))
+                })?;
+                actual.insert(tx.txid());
+            }
+
+            addresses.push(AddressCheck {
+                address: tkey.address.clone(),
+                recorded,
+                actual,
+            });
+        }
+
+        Ok(OnlineReport {
+            server_tip,
+            scanned_range: (start <= server_tip).then_some((start, server_tip)),
+            blocks_scanned,
+            reorged_blocks,
+            blocks_above_tip,
+            notes,
+            addresses,
+        })
+    }
+}
@@ -0,0 +1,325 @@
+//! # Orchard witness tree inspection
+//!
+//! Since version 25, ZecWallet Lite keeps the witnesses of its Orchard notes in a single legacy
+//! [`BridgeTree`] (`incrementalmerkletree` 0.3). Each [`OrchardNoteData`] only records the
+//! `witness_position` of its commitment, and the authentication path is recomputed from the tree
+//! when spending.
+//!
+//! The checkpoint serialization used by ZecWallet Lite carries no identifier, so checkpoints are
+//! reported by their index (oldest first) and the number of bridges they cover. ZecWallet Lite
+//! checkpoints the tree once per scanned block, so a checkpoint's depth from the tip is also the
+//! number of blocks behind the latest synced height.
+
+use incrementalmerkletree::{
+    Altitude, Hashable, Position, Tree,
+    bridgetree::{BridgeTree, Leaf, NonEmptyFrontier},
+};
+use orchard_old::tree::MerkleHashOrchard;
+use zcash_client_backend::proto::service::TreeState;
+use zcash_encoding::{Optional, Vector};
+use zcash_primitives::transaction::TxId;
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        orchard_data::{HashSer, MERKLE_DEPTH},
+    },
+};
+
+/// A checkpoint of the Orchard witness tree.
+#[derive(Debug, Clone)]
+pub struct OrchardCheckpoint {
+    /// Index of the checkpoint, oldest first.
+    pub index: usize,
+    /// Distance from the tip, as used by [`Tree::root`].
+    pub depth: usize,
+    pub bridges_len: usize,
+    pub is_witnessed: bool,
+    pub root: Option<[u8; 32]>,
+}
+
+/// Whether an Orchard note can produce a valid authentication path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrchardPathStatus {
+    /// The path hashes up to the current root of the tree.
+    Valid,
+    /// The note has no `witness_position`.
+    NoPosition,
+    /// The note's position isn't marked in the tree.
+    NotMarked,
+    /// The tree couldn't produce a path for the note's position.
+    NoPath,
+    /// The tree produced a path which doesn't hash up to the root.
+    Invalid,
+}
+
+/// Authentication path check for a single unspent Orchard note.
+#[derive(Debug, Clone)]
+pub struct OrchardNoteWitness {
+    pub txid: TxId,
+    pub note_index: usize,
+    pub value: u64,
+    pub position: Option<u64>,
+    pub path: Option<Vec<[u8; 32]>>,
+    pub status: OrchardPathStatus,
+}
+
+/// Comparison between the witness tree and the `orchard_tree` frontier of `verified_tree`.
+#[derive(Debug, Clone)]
+pub struct OrchardFrontierComparison {
+    pub tree_height: u64,
+    pub frontier_size: u64,
+    pub frontier_root: [u8; 32],
+    /// Checkpoint depth at which the witness tree has the frontier's root, if any.
+    pub matching_depth: Option<usize>,
+}
+
+/// Summary of the Orchard witness tree stored in a wallet.
+#[derive(Debug, Clone)]
+pub struct OrchardTreeReport {
+    pub size: u64,
+    pub root: [u8; 32],
+    pub max_checkpoints: usize,
+    pub checkpoints: Vec<OrchardCheckpoint>,
+    pub marked_positions: Vec<u64>,
+    pub notes: Vec<OrchardNoteWitness>,
+    pub frontier: Option<OrchardFrontierComparison>,
+}
+
+impl OrchardTreeReport {
+    /// Notes whose witnesses would make a spend fail.
+    pub fn problems(&self) -> impl Iterator<Item = &OrchardNoteWitness> {
+        self.notes
+            .iter()
+            .filter(|n| n.status != OrchardPathStatus::Valid)
+    }
+}
+
+/// Hashes `leaf` up the tree along `path` and checks that it arrives at `root`.
+pub fn verify_orchard_path(
+    position: Position,
+    leaf: &MerkleHashOrchard,
+    path: &[MerkleHashOrchard],
+    root: &MerkleHashOrchard,
+) -> bool {
+    let mut index = usize::from(position);
+    let mut node = *leaf;
+    for (level, sibling) in path.iter().enumerate() {
+        let altitude = Altitude::from(level as u8);
+        node = if index & 1 == 0 {
+            MerkleHashOrchard::combine(altitude, &node, sibling)
+        } else {
+            MerkleHashOrchard::combine(altitude, sibling, &node)
+        };
+        index >>= 1;
+    }
+
+    path.len() == MERKLE_DEPTH as usize && node == *root
+}
+
+/// Decodes the `orchard_tree` frontier of a lightwalletd [`TreeState`] and returns its size and root.
+pub fn decode_orchard_frontier(tree_state: &TreeState) -> Result<(u64, [u8; 32]), WalletError> {
+    let tree = tree_state
+        .orchard_tree()
+        .map_err(|e| WalletError::InvalidFormat(format!("Invalid orchard tree: {}", e)))?;
+
+    Ok((tree.size() as u64, tree.root().to_bytes()))
+}
+
+/// Encodes the frontier of the witness tree in the legacy `CommitmentTree` format used by the
+/// `orchard_tree` field of a lightwalletd [`TreeState`].
+pub fn encode_orchard_frontier(tree: &BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>) -> Vec<u8> {
+    fn write_hash<W: std::io::Write>(w: &mut W, h: &MerkleHashOrchard) -> std::io::Result<()> {
+        HashSer::write(h, w)
+    }
+
+    let mut buf = vec![];
+
+    // Writing into a Vec can't fail.
+    match tree.frontier() {
+        Some(frontier) => {
+            let (left, right) = match frontier.leaf() {
+                Leaf::Left(a) => (a, None),
+                Leaf::Right(a, b) => (a, Some(b)),
+            };
+            Optional::write(&mut buf, Some(left), write_hash).unwrap();
+            Optional::write(&mut buf, right, write_hash).unwrap();
+
+            // The ommers fill the levels above the leaves at which the position has a set bit.
+            let mut ommers = frontier.ommers().iter();
+            let mut parents = vec![];
+            let mut index = usize::from(frontier.position()) >> 1;
+            while index > 0 {
+                parents.push(if index & 1 == 1 { ommers.next() } else { None });
+                index >>= 1;
+            }
+
+            Vector::write(&mut buf, &parents, |w, p| {
+                Optional::write(w, *p, write_hash)
+            })
+            .unwrap();
+        }
+        None => {
+            Optional::write(&mut buf, None, write_hash).unwrap();
+            Optional::write(&mut buf, None, write_hash).unwrap();
+            Vector::write(&mut buf, &[] as &[Option<&MerkleHashOrchard>], |w, p| {
+                Optional::write(w, *p, write_hash)
+            })
+            .unwrap();
+        }
+    }
+
+    buf
+}
+
+/// Builds a witness tree that continues from the `orchard_tree` frontier of a lightwalletd
+/// [`TreeState`], the inverse of [`encode_orchard_frontier`].
+pub fn orchard_tree_from_frontier(
+    tree_state: &TreeState,
+    max_checkpoints: usize,
+) -> Result<BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>, WalletError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        WalletError::InvalidFormat(format!("Invalid orchard tree: {}", e))
+    };
+
+    let bytes = hex::decode(&tree_state.orchard_tree).map_err(|e| invalid(&e))?;
+    let mut reader = &bytes[..];
+    let left = Optional::read(&mut reader, MerkleHashOrchard::read)?;
+    let right = Optional::read(&mut reader, MerkleHashOrchard::read)?;
+    let parents = Vector::read(&mut reader, |r| Optional::read(r, MerkleHashOrchard::read))?;
+
+    let left = match left {
+        Some(left) => left,
+        None => return Ok(BridgeTree::new(max_checkpoints)),
+    };
+
+    // The position has a bit set for the right leaf and for each level that has a parent.
+    let mut position = usize::from(right.is_some());
+    for (level, parent) in parents.iter().enumerate() {
+        if parent.is_some() {
+            position |= 1 << (level + 1);
+        }
+    }
+    let leaf = match right {
+        Some(right) => Leaf::Right(left, right),
+        None => Leaf::Left(left),
+    };
+    let ommers = parents.into_iter().flatten().collect();
+
+    let frontier = NonEmptyFrontier::from_parts(Position::from(position), leaf, ommers)
+        .map_err(|e| invalid(&format!("{:?}", e)))?;
+
+    Ok(BridgeTree::from_frontier(max_checkpoints, frontier))
+}
+
+impl ZwlWallet {
+    /// Inspects `orchard_witnesses`, returning `None` if the wallet doesn't have one.
+    pub fn inspect_orchard_tree(&self) -> Result<Option<OrchardTreeReport>, WalletError> {
+        let tree = match &self.orchard_witnesses {
+            Some(tree) => tree,
+            None => return Ok(None),
+        };
+
+        let root = tree
+            .root(0)
+            .unwrap_or_else(|| MerkleHashOrchard::empty_root(Altitude::from(MERKLE_DEPTH)));
+
+        let checkpoint_count = tree.checkpoints().len();
+        let checkpoints = tree
+            .checkpoints()
+            .iter()
+            .enumerate()
+            .map(|(index, c)| {
+                let depth = checkpoint_count - index;
+                OrchardCheckpoint {
+                    index,
+                    depth,
+                    bridges_len: c.bridges_len(),
+                    is_witnessed: c.is_witnessed(),
+                    root: tree.root(depth).map(|r| r.to_bytes()),
+                }
+            })
+            .collect();
+
+        let marked_positions = tree
+            .witnessed_positions()
+            .into_iter()
+            .map(u64::from)
+            .collect();
+
+        let mut notes = vec![];
+        for wtx in self.transactions.current.values() {
+            for (note_index, nd) in wtx.orchard_notes.iter().enumerate() {
+                if nd.spent.is_some() || !nd.have_spending_key {
+                    continue;
+                }
+
+                let (path, status) = match nd.witness_position {
+                    Some(position) => check_path(tree, position, &root),
+                    None => (None, OrchardPathStatus::NoPosition),
+                };
+
+                notes.push(OrchardNoteWitness {
+                    txid: wtx.txid,
+                    note_index,
+                    value: nd.note.value().inner(),
+                    position: nd.witness_position.map(u64::from),
+                    path,
+                    status,
+                });
+            }
+        }
+        notes.sort_by_key(|n| (n.position, n.txid));
+
+        let frontier = match &self.verified_tree {
+            Some(tree_state) if !tree_state.orchard_tree.is_empty() => {
+                let (frontier_size, frontier_root) = decode_orchard_frontier(tree_state)?;
+                let matching_depth = (0..=checkpoint_count)
+                    .find(|depth| tree.root(*depth).map(|r| r.to_bytes()) == Some(frontier_root));
+
+                Some(OrchardFrontierComparison {
+                    tree_height: tree_state.height,
+                    frontier_size,
+                    frontier_root,
+                    matching_depth,
+                })
+            }
+            _ => None,
+        };
+
+        Ok(Some(OrchardTreeReport {
+            size: tree.current_position().map_or(0, |p| u64::from(p) + 1),
+            root: root.to_bytes(),
+            max_checkpoints: tree.max_checkpoints(),
+            checkpoints,
+            marked_positions,
+            notes,
+            frontier,
+        }))
+    }
+}
+
+fn check_path(
+    tree: &BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>,
+    position: Position,
+    root: &MerkleHashOrchard,
+) -> (Option<Vec<[u8; 32]>>, OrchardPathStatus) {
+    let leaf = match tree.get_witnessed_leaf(position) {
+        Some(leaf) => leaf,
+        None => return (None, OrchardPathStatus::NotMarked),
+    };
+
+    let path = match tree.authentication_path(position, root) {
+        Some(path) => path,
+        None => return (None, OrchardPathStatus::NoPath),
+    };
+
+    let status = if verify_orchard_path(position, leaf, &path, root) {
+        OrchardPathStatus::Valid
+    } else {
+        OrchardPathStatus::Invalid
+    };
+
+    (Some(path.iter().map(|h| h.to_bytes()).collect()), status)
+}
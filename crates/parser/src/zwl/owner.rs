@@ -0,0 +1,152 @@
+//! # Finding the owner of an address
+//!
+//! [`ZwlWallet::find_owner`] answers "is this address mine?" for any address of the wallet's
+//! network, not only the ones stored in the keys:
+//!
+//! - a Sapling address belongs to the key whose viewing key decrypts its diversifier,
+//! - an Orchard receiver belongs to the key whose `fvk` has a scope for it,
+//! - a transparent or TEX address belongs to the key with the same address.
+//!
+//! Unified addresses are matched by their Orchard, Sapling and transparent receivers, in that
+//! order.
+
+use std::fmt;
+
+use orchard_old::{Address as OldAddress, keys::Scope as OrchardScope};
+use sapling_crypto::PaymentAddress;
+use zcash_keys::{address::Address, encoding::encode_transparent_address_p};
+use zcash_primitives::{consensus::Network, legacy::TransparentAddress, zip32::Scope};
+
+use crate::{
+    error::WalletError,
+    zwl::{ZwlWallet, keys::Keys, scan::Pool},
+};
+
+/// The key an address belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressOwner {
+    pub pool: Pool,
+    /// The index of the key in its pool's keys.
+    pub key: usize,
+    /// The ZIP 32 account of an HD key, `None` for imported keys.
+    pub account: Option<u32>,
+    /// The diversifier index of a shielded address, or the address index of a transparent HD
+    /// key.
+    pub index: Option<u128>,
+    /// Whether the address is an internal one, used for change.
+    pub internal: bool,
+    pub spending: bool,
+}
+
+impl fmt::Display for AddressOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} key {}", self.pool, self.key)?;
+        match self.account {
+            Some(account) => write!(f, ", account {}", account)?,
+            None => write!(f, ", imported")?,
+        }
+        if let Some(index) = self.index {
+            write!(f, ", index {}", index)?;
+        }
+        if self.internal {
+            write!(f, ", internal")?;
+        }
+        if !self.spending {
+            write!(f, ", view only")?;
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Finds the key `address` belongs to, or `None` if it isn't one of the wallet's.
+    pub fn find_owner(&self, address: &str) -> Result<Option<AddressOwner>, WalletError> {
+        let network = self.chain_name.require_network()?;
+        let decoded = Address::decode(&network, address.trim()).ok_or_else(|| {
+            WalletError::InvalidFormat(format!(
+                "Invalid address for chain {}: {}",
+                self.chain_name, address
+            ))
+        })?;
+
+        Ok(match decoded {
+            Address::Sapling(address) => sapling_owner(&self.keys, &address),
+            Address::Transparent(address) => transparent_owner(&self.keys, &network, &address),
+            Address::Tex(data) => transparent_owner(
+                &self.keys,
+                &network,
+                &TransparentAddress::PublicKeyHash(data),
+            ),
+            Address::Unified(ua) => ua
+                .orchard()
+                .and_then(|receiver| {
+                    let address =
+                        OldAddress::from_raw_address_bytes(&receiver.to_raw_address_bytes());
+                    Option::from(address).and_then(|address| orchard_owner(&self.keys, &address))
+                })
+                .or_else(|| {
+                    ua.sapling()
+                        .and_then(|address| sapling_owner(&self.keys, address))
+                })
+                .or_else(|| {
+                    ua.transparent()
+                        .and_then(|address| transparent_owner(&self.keys, &network, address))
+                }),
+        })
+    }
+}
+
+fn sapling_owner(keys: &Keys, address: &PaymentAddress) -> Option<AddressOwner> {
+    keys.zkeys.iter().enumerate().find_map(|(i, zkey)| {
+        let (index, scope) = zkey
+            .extfvk
+            .to_diversifiable_full_viewing_key()
+            .decrypt_diversifier(address)?;
+        Some(AddressOwner {
+            pool: Pool::Sapling,
+            key: i,
+            account: zkey.hdkey_num,
+            index: Some(u128::from(index)),
+            internal: scope == Scope::Internal,
+            spending: zkey.have_spending_key(),
+        })
+    })
+}
+
+fn orchard_owner(keys: &Keys, address: &OldAddress) -> Option<AddressOwner> {
+    keys.okeys.iter().enumerate().find_map(|(i, okey)| {
+        let scope = okey.fvk.scope_for_address(address)?;
+        let index = okey.fvk.to_ivk(scope).diversifier_index(address)?;
+        let mut bytes = [0u8; 16];
+        bytes[..11].copy_from_slice(index.to_bytes());
+        Some(AddressOwner {
+            pool: Pool::Orchard,
+            key: i,
+            account: okey.hdkey_num,
+            index: Some(u128::from_le_bytes(bytes)),
+            internal: scope == OrchardScope::Internal,
+            spending: okey.have_spending_key(),
+        })
+    })
+}
+
+fn transparent_owner(
+    keys: &Keys,
+    network: &Network,
+    address: &TransparentAddress,
+) -> Option<AddressOwner> {
+    let encoded = encode_transparent_address_p(network, address);
+    keys.tkeys
+        .iter()
+        .enumerate()
+        .find(|(_, tkey)| tkey.address == encoded)
+        .map(|(i, tkey)| AddressOwner {
+            pool: Pool::Transparent,
+            key: i,
+            // ZecWallet Lite derives all its transparent keys from the first account.
+            account: tkey.hdkey_num.map(|_| 0),
+            index: tkey.hdkey_num.map(u128::from),
+            internal: false,
+            spending: tkey.have_spending_key(),
+        })
+}
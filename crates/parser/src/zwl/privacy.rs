@@ -0,0 +1,264 @@
+//! # Privacy audit
+//!
+//! [`ZwlWallet::privacy_audit`] goes through what [`WalletTxns`] records and lists what leaks
+//! information about the wallet's funds, each item with the transactions involved:
+//!
+//! - transparent addresses that received more than one Utxo, linking those payments together,
+//! - shielding transactions, which link the spent t-addresses to shielded activity, and
+//!   deshielding ones, which reveal shielded funds coming out,
+//! - sends to transparent recipients,
+//! - deshielded amounts close to an earlier shielded amount, which links the two even through
+//!   the shielded pool,
+//! - unspent Sapling notes, which could move to the Orchard pool.
+//!
+//! [`WalletTxns`]: crate::zwl::wallet_txns::WalletTxns
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use zcash_keys::address::Address;
+use zcash_primitives::{consensus::Network, transaction::TxId};
+
+use crate::zwl::{ZwlWallet, transactions::WalletTx};
+
+/// How far apart a shielded and a deshielded amount can be to still be linked, a few
+/// ZIP-317 fees.
+pub const AMOUNT_TOLERANCE: u64 = 50_000;
+
+/// What leaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivacyIssue {
+    /// A transparent address received several Utxos.
+    AddressReuse { address: String, utxos: usize },
+    /// Transparent funds of these addresses went into the shielded pool.
+    Shielding { addresses: Vec<String>, value: u64 },
+    /// Shielded funds came out to transparent outputs.
+    Deshielding { value: u64 },
+    /// A payment to a transparent address.
+    TransparentRecipient { address: String, value: u64 },
+    /// A deshielded amount is close to an amount shielded earlier.
+    AmountCorrelation { shielded: u64, deshielded: u64 },
+    /// Unspent Sapling notes, which could move to Orchard.
+    SaplingFunds { notes: usize, value: u64 },
+}
+
+impl fmt::Display for PrivacyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrivacyIssue::AddressReuse { address, utxos } => write!(
+                f,
+                "{} received {} Utxos, which anyone can link together",
+                address, utxos
+            ),
+            PrivacyIssue::Shielding { addresses, value } => write!(
+                f,
+                "{} zats shielded from {}, linking them to the wallet's shielded funds",
+                value,
+                addresses.join(", ")
+            ),
+            PrivacyIssue::Deshielding { value } => write!(
+                f,
+                "{} zats deshielded, revealing the amount leaving the shielded pool",
+                value
+            ),
+            PrivacyIssue::TransparentRecipient { address, value } => write!(
+                f,
+                "{} zats sent to the transparent address {}",
+                value, address
+            ),
+            PrivacyIssue::AmountCorrelation {
+                shielded,
+                deshielded,
+            } => write!(
+                f,
+                "{} zats deshielded after {} zats were shielded, the amounts link the two",
+                deshielded, shielded
+            ),
+            PrivacyIssue::SaplingFunds { notes, value } => write!(
+                f,
+                "{} zats in {} unspent Sapling notes, which could move to Orchard",
+                value, notes
+            ),
+        }
+    }
+}
+
+/// An issue and the transactions involved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrivacyItem {
+    pub issue: PrivacyIssue,
+    pub txids: Vec<TxId>,
+}
+
+/// What [`ZwlWallet::privacy_audit`] found.
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyReport {
+    pub items: Vec<PrivacyItem>,
+}
+
+impl PrivacyReport {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The transactions of all items, whose coins should be treated carefully.
+    pub fn txids(&self) -> BTreeSet<TxId> {
+        self.items
+            .iter()
+            .flat_map(|item| item.txids.iter().copied())
+            .collect()
+    }
+}
+
+impl fmt::Display for PrivacyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "- {}", item.issue)?;
+            for txid in &item.txids {
+                writeln!(f, "    {}", txid)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Lists the privacy leaks of the wallet's history. Recipients of a wallet for an unknown
+    /// chain are decoded as mainnet addresses.
+    pub fn privacy_audit(&self) -> PrivacyReport {
+        let network = self.chain_name.network().unwrap_or(Network::MainNetwork);
+        let is_transparent = |address: &str| {
+            matches!(
+                Address::decode(&network, address),
+                Some(Address::Transparent(_) | Address::Tex(_))
+            )
+        };
+        let mut wtxs: Vec<&WalletTx> = self.transactions.current.values().collect();
+        wtxs.sort_by_key(|wtx| (u32::from(wtx.block), wtx.txid));
+        let mut items = vec![];
+
+        let mut by_address: BTreeMap<&str, Vec<TxId>> = BTreeMap::new();
+        for wtx in &wtxs {
+            for utxo in &wtx.utxos {
+                by_address.entry(&utxo.address).or_default().push(wtx.txid);
+            }
+        }
+        for (address, mut txids) in by_address {
+            let utxos = txids.len();
+            if utxos > 1 {
+                txids.dedup();
+                items.push(PrivacyItem {
+                    issue: PrivacyIssue::AddressReuse {
+                        address: address.to_string(),
+                        utxos,
+                    },
+                    txids,
+                });
+            }
+        }
+
+        // The wallet's Utxos by the transaction that spent them.
+        let mut utxos_spent_by: HashMap<TxId, Vec<_>> = HashMap::new();
+        for utxo in wtxs.iter().flat_map(|w| &w.utxos) {
+            if let Some(txid) = utxo.spent.or(utxo.unconfirmed_spent.map(|s| s.0)) {
+                utxos_spent_by.entry(txid).or_default().push(utxo);
+            }
+        }
+
+        let mut shieldings = vec![];
+        for wtx in &wtxs {
+            let spent_utxos = utxos_spent_by.remove(&wtx.txid).unwrap_or_default();
+            let to_transparent: Vec<_> = wtx
+                .outgoing_metadata
+                .iter()
+                .filter(|o| is_transparent(&o.address))
+                .collect();
+            let shielded_out = !wtx.sapling_notes.is_empty()
+                || !wtx.orchard_notes.is_empty()
+                || wtx
+                    .outgoing_metadata
+                    .iter()
+                    .any(|o| !is_transparent(&o.address));
+            let shielded_spent = wtx.total_sapling_value_spent + wtx.total_orchard_value_spent;
+
+            if (wtx.total_transparent_value_spent > 0 || !spent_utxos.is_empty()) && shielded_out {
+                let mut addresses: Vec<String> =
+                    spent_utxos.iter().map(|u| u.address.clone()).collect();
+                addresses.sort();
+                addresses.dedup();
+                let value = wtx
+                    .total_transparent_value_spent
+                    .max(spent_utxos.iter().map(|u| u.value).sum());
+                shieldings.push((wtx.txid, value));
+                items.push(PrivacyItem {
+                    issue: PrivacyIssue::Shielding { addresses, value },
+                    txids: spent_utxos
+                        .iter()
+                        .map(|u| u.txid)
+                        .chain([wtx.txid])
+                        .collect::<BTreeSet<_>>()
+                        .into_iter()
+                        .collect(),
+                });
+            }
+
+            let deshielded = to_transparent.iter().map(|o| o.value).sum::<u64>()
+                + wtx.utxos.iter().map(|u| u.value).sum::<u64>();
+            if shielded_spent > 0 && deshielded > 0 {
+                items.push(PrivacyItem {
+                    issue: PrivacyIssue::Deshielding { value: deshielded },
+                    txids: vec![wtx.txid],
+                });
+                for (shielding_txid, shielded) in &shieldings {
+                    if *shielding_txid != wtx.txid
+                        && (*shielded).min(deshielded) > AMOUNT_TOLERANCE
+                        && shielded.abs_diff(deshielded) <= AMOUNT_TOLERANCE
+                    {
+                        items.push(PrivacyItem {
+                            issue: PrivacyIssue::AmountCorrelation {
+                                shielded: *shielded,
+                                deshielded,
+                            },
+                            txids: vec![*shielding_txid, wtx.txid],
+                        });
+                    }
+                }
+            }
+
+            for o in to_transparent {
+                items.push(PrivacyItem {
+                    issue: PrivacyIssue::TransparentRecipient {
+                        address: o.address.clone(),
+                        value: o.value,
+                    },
+                    txids: vec![wtx.txid],
+                });
+            }
+        }
+
+        let sapling: Vec<(TxId, u64)> = wtxs
+            .iter()
+            .flat_map(|wtx| {
+                wtx.sapling_notes
+                    .iter()
+                    .filter(|nd| nd.spent.is_none() && nd.unconfirmed_spent.is_none())
+                    .map(|nd| (wtx.txid, nd.note.value().inner()))
+            })
+            .collect();
+        if !sapling.is_empty() {
+            let mut txids: Vec<TxId> = sapling.iter().map(|(txid, _)| *txid).collect();
+            txids.dedup();
+            items.push(PrivacyItem {
+                issue: PrivacyIssue::SaplingFunds {
+                    notes: sapling.len(),
+                    value: sapling.iter().map(|(_, value)| value).sum(),
+                },
+                txids,
+            });
+        }
+
+        PrivacyReport { items }
+    }
+}
@@ -0,0 +1,226 @@
+//! # Stale unconfirmed spends
+//!
+//! When ZecWallet Lite broadcasts a transaction, it marks the notes and Utxos it spends with
+//! `unconfirmed_spent` until the transaction is mined. If it never is, the markers stay and the
+//! funds look unavailable. A marker is stale when:
+//!
+//! - its height is more than [`DEFAULT_TX_EXPIRY_DELTA`] blocks below the latest block, so the
+//!   spending transaction has expired, or
+//! - its transaction isn't in [`WalletTxns`] anymore.
+//!
+//! Unconfirmed transactions past their expiry height are dropped too, along with the markers
+//! that point to them.
+//!
+//! [`WalletTxns`]: crate::zwl::wallet_txns::WalletTxns
+
+use std::{collections::HashSet, fmt};
+
+use zcash_primitives::transaction::{TxId, builder::DEFAULT_TX_EXPIRY_DELTA};
+
+use crate::zwl::{ZwlWallet, scan::Pool};
+
+/// Why an `unconfirmed_spent` marker is stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaleReason {
+    /// The spending transaction is past its expiry height.
+    Expired,
+    /// The spending transaction isn't in the wallet.
+    UnknownTx,
+}
+
+impl fmt::Display for StaleReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaleReason::Expired => write!(f, "expired"),
+            StaleReason::UnknownTx => write!(f, "unknown transaction"),
+        }
+    }
+}
+
+/// An `unconfirmed_spent` marker to clear.
+#[derive(Debug, Clone)]
+pub struct StaleSpend {
+    /// Transaction that received the note or Utxo.
+    pub txid: TxId,
+    pub pool: Pool,
+    /// Index of the note in its pool, or of the Utxo, within the transaction.
+    pub index: usize,
+    pub value: u64,
+    pub spending_txid: TxId,
+    pub spending_height: u32,
+    pub reason: StaleReason,
+}
+
+/// What [`ZwlWallet::repair_stale_spends`] changes, or would change.
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub latest_height: Option<u64>,
+    pub stale_spends: Vec<StaleSpend>,
+    /// Expired unconfirmed transactions, legacy mempool ones included.
+    pub expired_txs: Vec<TxId>,
+}
+
+impl RepairReport {
+    pub fn is_empty(&self) -> bool {
+        self.stale_spends.is_empty() && self.expired_txs.is_empty()
+    }
+
+    /// Value made spendable again by clearing the markers.
+    pub fn freed_value(&self) -> u64 {
+        self.stale_spends.iter().map(|s| s.value).sum()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for txid in &self.expired_txs {
+            writeln!(f, "- tx {}", txid)?;
+        }
+        for spend in &self.stale_spends {
+            writeln!(
+                f,
+                "- {} {} #{} ({} zats): unconfirmed_spent {} at {} ({})",
+                spend.pool,
+                spend.txid,
+                spend.index,
+                spend.value,
+                spend.spending_txid,
+                spend.spending_height,
+                spend.reason
+            )?;
+            writeln!(
+                f,
+                "+ {} {} #{}: unspent",
+                spend.pool, spend.txid, spend.index
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl ZwlWallet {
+    /// Finds the stale `unconfirmed_spent` markers and expired transactions, without changing
+    /// the wallet.
+    pub fn find_stale_spends(&self) -> RepairReport {
+        let latest_height = self.latest_height();
+        let txns = &self.transactions;
+
+        let mut expired_txs: Vec<TxId> = txns
+            .current
+            .values()
+            .chain(txns.mempool.values())
+            .filter(|wtx| latest_height.is_some_and(|h| wtx.is_expired(h)))
+            .map(|wtx| wtx.txid)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        expired_txs.sort();
+
+        let known = |txid: &TxId| {
+            (txns.current.contains_key(txid) || txns.mempool.contains_key(txid))
+                && !expired_txs.contains(txid)
+        };
+        let stale_reason = |(txid, height): (TxId, u32)| {
+            if latest_height
+                .is_some_and(|h| h > u64::from(height) + u64::from(DEFAULT_TX_EXPIRY_DELTA))
+            {
+                Some(StaleReason::Expired)
+            } else if !known(&txid) {
+                Some(StaleReason::UnknownTx)
+            } else {
+                None
+            }
+        };
+
+        let mut stale_spends = vec![];
+        for wtx in txns
+            .current
+            .values()
+            .filter(|wtx| !expired_txs.contains(&wtx.txid))
+        {
+            let sapling = wtx.sapling_notes.iter().enumerate().map(|(i, nd)| {
+                (
+                    Pool::Sapling,
+                    i,
+                    nd.note.value().inner(),
+                    nd.unconfirmed_spent,
+                )
+            });
+            let orchard = wtx.orchard_notes.iter().enumerate().map(|(i, nd)| {
+                (
+                    Pool::Orchard,
+                    i,
+                    nd.note.value().inner(),
+                    nd.unconfirmed_spent,
+                )
+            });
+            let transparent = wtx
+                .utxos
+                .iter()
+                .enumerate()
+                .map(|(i, u)| (Pool::Transparent, i, u.value, u.unconfirmed_spent));
+
+            for (pool, index, value, marker) in sapling.chain(orchard).chain(transparent) {
+                let Some((spending_txid, spending_height)) = marker else {
+                    continue;
+                };
+                if let Some(reason) = stale_reason((spending_txid, spending_height)) {
+                    stale_spends.push(StaleSpend {
+                        txid: wtx.txid,
+                        pool,
+                        index,
+                        value,
+                        spending_txid,
+                        spending_height,
+                        reason,
+                    });
+                }
+            }
+        }
+        stale_spends.sort_by_key(|s| (s.txid, s.pool, s.index));
+
+        RepairReport {
+            latest_height,
+            stale_spends,
+            expired_txs,
+        }
+    }
+
+    /// Clears the stale `unconfirmed_spent` markers and drops the expired transactions.
+    ///
+    /// Returns what was changed, the same report [`ZwlWallet::find_stale_spends`] gives.
+    pub fn repair_stale_spends(&mut self) -> RepairReport {
+        let report = self.find_stale_spends();
+        let txns = &mut self.transactions;
+
+        for txid in &report.expired_txs {
+            txns.current.remove(txid);
+            txns.mempool.remove(txid);
+        }
+
+        for spend in &report.stale_spends {
+            let Some(wtx) = txns.current.get_mut(&spend.txid) else {
+                continue;
+            };
+            match spend.pool {
+                Pool::Sapling => wtx.sapling_notes[spend.index].unconfirmed_spent = None,
+                Pool::Orchard => wtx.orchard_notes[spend.index].unconfirmed_spent = None,
+                Pool::Transparent => wtx.utxos[spend.index].unconfirmed_spent = None,
+            }
+        }
+
+        // Point last_txid at the newest remaining transaction if it was dropped.
+        if txns
+            .last_txid
+            .is_some_and(|txid| report.expired_txs.contains(&txid))
+        {
+            txns.last_txid = txns
+                .current
+                .values()
+                .max_by_key(|wtx| (wtx.block, wtx.datetime))
+                .map(|wtx| wtx.txid);
+        }
+
+        report
+    }
+}
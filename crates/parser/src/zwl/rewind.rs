@@ -0,0 +1,158 @@
+//! # Rewinding
+//!
+//! Rolls the wallet back to an earlier height, the way ZecWallet Lite recovers from a reorg:
+//!
+//! - transactions mined above the height are removed, and the notes and Utxos they spent are
+//!   marked unspent again,
+//! - the Sapling witnesses of the remaining notes are popped back to the height,
+//! - the Orchard witness tree is rewound one checkpoint per removed block,
+//! - the cached blocks above the height are dropped.
+//!
+//! The wallet can only go back as far as the cached blocks and the Orchard checkpoints reach,
+//! and never more than [`MAX_REORG`] blocks. A `verified_tree` above the height is cleared, since
+//! the Sapling tree at an earlier height can't be recovered from it.
+
+use incrementalmerkletree::Tree;
+use zcash_primitives::{consensus::BlockHeight, transaction::TxId};
+
+use crate::{
+    error::WalletError,
+    zwl::{ZwlWallet, transactions::MAX_REORG},
+};
+
+/// What [`ZwlWallet::rewind_to`] changed.
+#[derive(Debug, Clone)]
+pub struct RewindReport {
+    pub from_height: u64,
+    pub to_height: u64,
+    pub blocks_removed: usize,
+    pub removed_txs: Vec<TxId>,
+    /// Notes and Utxos of the remaining transactions that are unspent again.
+    pub unspent: usize,
+    /// The height of the `verified_tree` that was cleared, if it was above the new tip.
+    pub cleared_verified_tree: Option<u64>,
+}
+
+impl ZwlWallet {
+    /// Rewinds the wallet so that `height` is its latest block.
+    ///
+    /// The wallet is left untouched if the rewind fails.
+    pub fn rewind_to(&mut self, height: u64) -> Result<RewindReport, WalletError> {
+        let tip = self.blocks.first().map(|b| b.height).ok_or_else(|| {
+            WalletError::InvalidFormat("The wallet has no cached blocks to rewind".to_string())
+        })?;
+        if height > tip {
+            return Err(WalletError::InvalidFormat(format!(
+                "Can't rewind to {}, above the latest block {}",
+                height, tip
+            )));
+        }
+
+        let depth = (tip - height) as usize;
+        if depth > MAX_REORG {
+            return Err(WalletError::InvalidFormat(format!(
+                "Can't rewind {} blocks, more than the maximum reorg of {}",
+                depth, MAX_REORG
+            )));
+        }
+        if !self.blocks.iter().any(|b| b.height == height) {
+            return Err(WalletError::InvalidFormat(format!(
+                "Block {} isn't cached, the wallet can't be rewound that far",
+                height
+            )));
+        }
+
+        let mut wallet = self.clone();
+
+        let cleared_verified_tree = wallet
+            .verified_tree
+            .take_if(|t| t.height > height)
+            .map(|t| t.height);
+
+        if let Some(tree) = wallet.orchard_witnesses.as_mut() {
+            if tree.checkpoints().len() < depth {
+                return Err(WalletError::InvalidFormat(format!(
+                    "The Orchard tree only has {} checkpoints, {} are needed",
+                    tree.checkpoints().len(),
+                    depth
+                )));
+            }
+            for _ in 0..depth {
+                tree.rewind();
+            }
+        }
+
+        wallet.blocks.retain(|b| b.height <= height);
+
+        let first_removed = BlockHeight::from_u32(height as u32 + 1);
+        let txns = &mut wallet.transactions;
+        let mut removed_txs: Vec<TxId> = txns
+            .current
+            .values()
+            .filter(|wtx| wtx.block >= first_removed)
+            .map(|wtx| wtx.txid)
+            .collect();
+        removed_txs.sort();
+        for txid in &removed_txs {
+            txns.current.remove(txid);
+        }
+
+        let removed = |txid: &TxId| removed_txs.binary_search(txid).is_ok();
+        let mut unspent = 0;
+        for wtx in txns.current.values_mut() {
+            for nd in wtx.sapling_notes.iter_mut() {
+                if nd.spent.is_some_and(|(txid, _)| removed(&txid)) {
+                    nd.spent = None;
+                    unspent += 1;
+                }
+                if nd.unconfirmed_spent.is_some_and(|(txid, _)| removed(&txid)) {
+                    nd.unconfirmed_spent = None;
+                }
+                nd.witnesses.pop(height + 1);
+            }
+
+            for nd in wtx.orchard_notes.iter_mut() {
+                if nd.spent.is_some_and(|(txid, _)| removed(&txid)) {
+                    nd.spent = None;
+                    unspent += 1;
+                }
+                if nd.unconfirmed_spent.is_some_and(|(txid, _)| removed(&txid)) {
+                    nd.unconfirmed_spent = None;
+                }
+            }
+
+            for utxo in wtx.utxos.iter_mut() {
+                if utxo.spent.is_some_and(|txid| removed(&txid)) {
+                    utxo.spent = None;
+                    utxo.spent_at_height = None;
+                    unspent += 1;
+                }
+                if utxo
+                    .unconfirmed_spent
+                    .is_some_and(|(txid, _)| removed(&txid))
+                {
+                    utxo.unconfirmed_spent = None;
+                }
+            }
+        }
+
+        if txns.last_txid.is_some_and(|txid| removed(&txid)) {
+            txns.last_txid = txns
+                .current
+                .values()
+                .max_by_key(|wtx| (wtx.block, wtx.datetime))
+                .map(|wtx| wtx.txid);
+        }
+
+        *self = wallet;
+
+        Ok(RewindReport {
+            from_height: tip,
+            to_height: height,
+            blocks_removed: depth,
+            removed_txs,
+            unspent,
+            cleared_verified_tree,
+        })
+    }
+}
@@ -138,10 +138,9 @@ impl SaplingNoteData {
                 None
             };
 
-            if spent.is_some() && spent_at_height.is_some() {
-                Some((spent.unwrap(), spent_at_height.unwrap() as u32))
-            } else {
-                None
+            match (spent, spent_at_height) {
+                (Some(spent), Some(height)) => Some((spent, height as u32)),
+                _ => None,
             }
         } else {
             Optional::read(&mut reader, |r| {
@@ -0,0 +1,258 @@
+//! # Sapling witness validation
+//!
+//! Every spendable Sapling note keeps a [`WitnessCache`] with one witness per scanned block,
+//! `witnesses.last()` being the witness at [`WitnessCache::top_height`]. When a spend is built,
+//! the anchor is taken from one of these witnesses, so a witness whose root doesn't exist on
+//! chain produces the infamous "anchor not found" error.
+//!
+//! The wallet also stores the lightwalletd [`TreeState`] it last verified against. Its
+//! `sapling_tree` is the hex-encoded commitment tree frontier at `height`, which gives us a
+//! known-good root to compare the witnesses against.
+
+use std::fmt;
+
+use sapling_crypto::{CommitmentTree, IncrementalWitness, Node};
+use zcash_client_backend::proto::service::TreeState;
+use zcash_primitives::transaction::TxId;
+
+use crate::{
+    error::WalletError,
+    zwl::{ZwlWallet, sapling_data::SaplingNoteData, transactions::WitnessCache},
+};
+
+/// Outcome of validating a single note's witnesses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WitnessStatus {
+    /// The witness at the verified tree height has the same root as the verified tree.
+    Valid,
+    /// The note is spendable but has no witnesses at all.
+    Empty,
+    /// The latest witness is older than the verified tree.
+    Stale { top_height: u64, tree_height: u64 },
+    /// The verified tree height falls outside the range covered by the cache.
+    OutOfRange { top_height: u64, tree_height: u64 },
+    /// The witness at the verified tree height has a different root.
+    RootMismatch {
+        witness_root: [u8; 32],
+        tree_root: [u8; 32],
+    },
+    /// The witnesses in the cache disagree with each other or with the note.
+    Inconsistent(String),
+    /// The wallet has no verified tree to compare against.
+    Unverified,
+}
+
+impl WitnessStatus {
+    pub fn is_valid(&self) -> bool {
+        matches!(self, WitnessStatus::Valid)
+    }
+}
+
+impl fmt::Display for WitnessStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitnessStatus::Valid => write!(f, "valid"),
+            WitnessStatus::Empty => write!(f, "empty"),
+            WitnessStatus::Stale {
+                top_height,
+                tree_height,
+            } => write!(
+                f,
+                "stale (latest witness at {}, verified tree at {})",
+                top_height, tree_height
+            ),
+            WitnessStatus::OutOfRange {
+                top_height,
+                tree_height,
+            } => write!(
+                f,
+                "verified tree at {} is not covered by the cache (latest witness at {})",
+                tree_height, top_height
+            ),
+            WitnessStatus::RootMismatch {
+                witness_root,
+                tree_root,
+            } => write!(
+                f,
+                "root mismatch (witness {}, tree {})",
+                hex::encode(witness_root),
+                hex::encode(tree_root)
+            ),
+            WitnessStatus::Inconsistent(reason) => write!(f, "inconsistent: {}", reason),
+            WitnessStatus::Unverified => write!(f, "unverified (no verified tree)"),
+        }
+    }
+}
+
+/// Validation result for a single unspent Sapling note.
+#[derive(Debug, Clone)]
+pub struct SaplingWitnessCheck {
+    pub txid: TxId,
+    pub note_index: usize,
+    pub value: u64,
+    pub witness_count: usize,
+    pub top_height: u64,
+    pub position: Option<u64>,
+    pub status: WitnessStatus,
+}
+
+/// Validation results for all unspent, spendable Sapling notes in a wallet.
+#[derive(Debug, Clone)]
+pub struct SaplingWitnessReport {
+    pub tree_height: Option<u64>,
+    pub tree_size: Option<u64>,
+    pub tree_root: Option<[u8; 32]>,
+    pub checks: Vec<SaplingWitnessCheck>,
+}
+
+impl SaplingWitnessReport {
+    /// Checks whose witnesses would make a spend fail.
+    pub fn problems(&self) -> impl Iterator<Item = &SaplingWitnessCheck> {
+        self.checks.iter().filter(|c| !c.status.is_valid())
+    }
+}
+
+/// Decodes the hex-encoded Sapling frontier of a lightwalletd [`TreeState`].
+pub fn decode_sapling_tree(tree_state: &TreeState) -> Result<CommitmentTree, WalletError> {
+    tree_state
+        .sapling_tree()
+        .map_err(|e| WalletError::InvalidFormat(format!("Invalid sapling tree: {}", e)))
+}
+
+impl ZwlWallet {
+    /// Validates the witnesses of every unspent Sapling note against `verified_tree`.
+    ///
+    /// Notes without a spending key are skipped, since ZecWallet Lite doesn't keep their
+    /// witnesses up to date.
+    pub fn check_sapling_witnesses(&self) -> Result<SaplingWitnessReport, WalletError> {
+        let tree = match &self.verified_tree {
+            Some(tree_state) => Some((tree_state.height, decode_sapling_tree(tree_state)?)),
+            None => None,
+        };
+
+        let mut checks = vec![];
+        for wtx in self.transactions.current.values() {
+            for (note_index, nd) in wtx.sapling_notes.iter().enumerate() {
+                if nd.spent.is_some() || !nd.have_spending_key {
+                    continue;
+                }
+
+                let status = match &tree {
+                    Some((height, tree)) => check_note(nd, *height, tree),
+                    None if nd.witnesses.is_empty() => WitnessStatus::Empty,
+                    None => WitnessStatus::Unverified,
+                };
+
+                checks.push(SaplingWitnessCheck {
+                    txid: wtx.txid,
+                    note_index,
+                    value: nd.note.value().inner(),
+                    witness_count: nd.witnesses.len(),
+                    top_height: nd.witnesses.top_height,
+                    position: nd
+                        .witnesses
+                        .last()
+                        .map(|w| u64::from(w.witnessed_position())),
+                    status,
+                });
+            }
+        }
+        checks.sort_by_key(|c| (c.top_height, c.txid));
+
+        Ok(SaplingWitnessReport {
+            tree_height: tree.as_ref().map(|(h, _)| *h),
+            tree_size: tree.as_ref().map(|(_, t)| t.size() as u64),
+            tree_root: tree.as_ref().map(|(_, t)| t.root().to_bytes()),
+            checks,
+        })
+    }
+}
+
+fn check_note(nd: &SaplingNoteData, tree_height: u64, tree: &CommitmentTree) -> WitnessStatus {
+    let cache = &nd.witnesses;
+    let latest = match cache.last() {
+        Some(w) => w,
+        None => return WitnessStatus::Empty,
+    };
+
+    if let Some(reason) = inconsistency(nd, cache, latest) {
+        return WitnessStatus::Inconsistent(reason);
+    }
+
+    if cache.top_height < tree_height {
+        return WitnessStatus::Stale {
+            top_height: cache.top_height,
+            tree_height,
+        };
+    }
+
+    // witnesses.last() is at top_height, and every entry before it is one block older.
+    let witness = (cache.top_height - tree_height)
+        .try_into()
+        .ok()
+        .and_then(|back: usize| cache.len().checked_sub(back + 1))
+        .and_then(|i| cache.get(i));
+
+    let witness = match witness {
+        Some(w) => w,
+        None => {
+            return WitnessStatus::OutOfRange {
+                top_height: cache.top_height,
+                tree_height,
+            };
+        }
+    };
+
+    let witness_root: Node = witness.root();
+    let tree_root: Node = tree.root();
+    if witness_root != tree_root {
+        return WitnessStatus::RootMismatch {
+            witness_root: witness_root.to_bytes(),
+            tree_root: tree_root.to_bytes(),
+        };
+    }
+
+    WitnessStatus::Valid
+}
+
+fn inconsistency(
+    nd: &SaplingNoteData,
+    cache: &WitnessCache,
+    latest: &IncrementalWitness,
+) -> Option<String> {
+    let position = latest.witnessed_position();
+
+    if cache
+        .witnesses
+        .iter()
+        .any(|w| w.witnessed_position() != position)
+    {
+        return Some("witnesses in the cache track different positions".to_string());
+    }
+
+    if cache
+        .witnesses
+        .windows(2)
+        .any(|w| w[0].tip_position() > w[1].tip_position())
+    {
+        return Some("witnesses in the cache are not ordered by tree size".to_string());
+    }
+
+    if cache.top_height < cache.len() as u64 {
+        return Some(format!(
+            "{} witnesses cannot end at height {}",
+            cache.len(),
+            cache.top_height
+        ));
+    }
+
+    let nullifier = nd.note.nf(&nd.extfvk.fvk.vk.nk, u64::from(position));
+    if nullifier != nd.nullifier {
+        return Some(format!(
+            "witnessed position {} doesn't match the note's nullifier",
+            u64::from(position)
+        ));
+    }
+
+    None
+}
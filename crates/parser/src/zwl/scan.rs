@@ -0,0 +1,305 @@
+//! # Offline trial decryption
+//!
+//! Trial-decrypts the compact blocks cached in [`ZwlWallet::blocks`] with the wallet's incoming
+//! viewing keys, and compares the notes found with the notes recorded in [`WalletTxns`]. This
+//! tells a sync bug (a note on chain the wallet never recorded, or a recorded note that isn't on
+//! chain) apart from a plain balance misunderstanding, without touching the network.
+//!
+//! Only the cached range of blocks can be checked: ZecWallet Lite keeps the last [`MAX_REORG`]
+//! blocks, so recorded notes below the lowest cached block are never reported as orphans.
+//!
+//! [`WalletTxns`]: crate::zwl::wallet_txns::WalletTxns
+//! [`MAX_REORG`]: crate::zwl::transactions::MAX_REORG
+
+use std::{collections::HashSet, fmt};
+
+use orchard_old::{
+    keys::{FullViewingKey, IncomingViewingKey, Scope},
+    note::{ExtractedNoteCommitment, Nullifier as OrchardNullifier},
+    note_encryption::{CompactAction, OrchardDomain},
+};
+use sapling_crypto::{
+    keys::PreparedIncomingViewingKey,
+    note_encryption::{CompactOutputDescription, try_sapling_compact_note_decryption},
+    zip32::ExtendedFullViewingKey,
+};
+use zcash_client_backend::proto::compact_formats::{CompactBlock, CompactOrchardAction};
+use zcash_note_encryption_old::{EphemeralKeyBytes, try_compact_note_decryption};
+use zcash_primitives::{
+    consensus::{BlockHeight, Network},
+    transaction::{TxId, components::sapling::zip212_enforcement},
+};
+
+use crate::{error::WalletError, zwl::ZwlWallet};
+
+/// The pool a note or Utxo belongs to. Trial decryption only finds Sapling and Orchard notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pool {
+    Transparent,
+    Sapling,
+    Orchard,
+}
+
+impl fmt::Display for Pool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pool::Transparent => write!(f, "Transparent"),
+            Pool::Sapling => write!(f, "Sapling"),
+            Pool::Orchard => write!(f, "Orchard"),
+        }
+    }
+}
+
+/// The wallet's incoming viewing keys, prepared for trial decryption.
+pub struct ScanningKeys {
+    /// Index into `Keys::zkeys`, and the key itself.
+    pub sapling: Vec<(usize, ExtendedFullViewingKey, PreparedIncomingViewingKey)>,
+    /// Index into `Keys::okeys`, and the key itself.
+    pub orchard: Vec<(usize, FullViewingKey, Scope, IncomingViewingKey)>,
+}
+
+impl ScanningKeys {
+    pub fn from_wallet(wallet: &ZwlWallet) -> Self {
+        let sapling = wallet
+            .keys
+            .zkeys
+            .iter()
+            .enumerate()
+            .map(|(i, zk)| {
+                let ivk = PreparedIncomingViewingKey::new(&zk.extfvk.fvk.vk.ivk());
+                (i, zk.extfvk.clone(), ivk)
+            })
+            .collect();
+
+        let orchard = wallet
+            .keys
+            .okeys
+            .iter()
+            .enumerate()
+            .flat_map(|(i, ok)| {
+                [Scope::External, Scope::Internal]
+                    .into_iter()
+                    .map(move |scope| (i, ok.fvk.clone(), scope, ok.fvk.to_ivk(scope)))
+            })
+            .collect();
+
+        Self { sapling, orchard }
+    }
+}
+
+/// A note successfully trial-decrypted from a compact block.
+#[derive(Debug, Clone)]
+pub struct DecryptedNote {
+    pub height: u64,
+    pub txid: TxId,
+    pub pool: Pool,
+    /// Index of the output (Sapling) or action (Orchard) within the transaction.
+    pub output_index: usize,
+    /// Index of the key within `Keys::zkeys` or `Keys::okeys`.
+    pub key_index: usize,
+    pub value: u64,
+    /// Note commitment (`cmu` for Sapling, `cmx` for Orchard).
+    pub commitment: [u8; 32],
+    pub note: DecryptedNoteKind,
+}
+
+/// The decrypted note itself, which is needed to add it to the wallet.
+#[derive(Debug, Clone)]
+pub enum DecryptedNoteKind {
+    Sapling { note: sapling_crypto::Note },
+    Orchard { note: orchard_old::Note },
+}
+
+/// Trial-decrypts every Sapling output and Orchard action of `block`.
+pub fn decrypt_block(
+    network: &Network,
+    keys: &ScanningKeys,
+    block: &CompactBlock,
+) -> Vec<DecryptedNote> {
+    let height = block.height;
+    let zip212 = zip212_enforcement(network, BlockHeight::from_u32(height as u32));
+    let mut found = vec![];
+
+    for ctx in &block.vtx {
+        let txid = ctx.txid();
+
+        for (output_index, output) in ctx.outputs.iter().enumerate() {
+            let output = match CompactOutputDescription::try_from(output) {
+                Ok(output) => output,
+                Err(_) => continue,
+            };
+
+            for (key_index, _, ivk) in &keys.sapling {
+                if let Some((note, _)) = try_sapling_compact_note_decryption(ivk, &output, zip212) {
+                    found.push(DecryptedNote {
+                        height,
+                        txid,
+                        pool: Pool::Sapling,
+                        output_index,
+                        key_index: *key_index,
+                        value: note.value().inner(),
+                        commitment: output.cmu.to_bytes(),
+                        note: DecryptedNoteKind::Sapling { note },
+                    });
+                    break;
+                }
+            }
+        }
+
+        for (output_index, action) in ctx.actions.iter().enumerate() {
+            let action = match to_compact_action(action) {
+                Some(action) => action,
+                None => continue,
+            };
+            let domain = OrchardDomain::for_nullifier(action.nullifier());
+
+            for (key_index, _, _, ivk) in &keys.orchard {
+                if let Some((note, _)) = try_compact_note_decryption(&domain, ivk, &action) {
+                    found.push(DecryptedNote {
+                        height,
+                        txid,
+                        pool: Pool::Orchard,
+                        output_index,
+                        key_index: *key_index,
+                        value: note.value().inner(),
+                        commitment: ExtractedNoteCommitment::from(note.commitment()).to_bytes(),
+                        note: DecryptedNoteKind::Orchard { note },
+                    });
+                    break;
+                }
+            }
+        }
+    }
+
+    found
+}
+
+/// Converts a lightwalletd action into the `orchard_old` compact action.
+pub(crate) fn to_compact_action(action: &CompactOrchardAction) -> Option<CompactAction> {
+    let nullifier = OrchardNullifier::from_bytes(action.nullifier.as_slice().try_into().ok()?);
+    let cmx = ExtractedNoteCommitment::from_bytes(action.cmx.as_slice().try_into().ok()?);
+    let ephemeral_key: [u8; 32] = action.ephemeral_key.as_slice().try_into().ok()?;
+    let ciphertext: [u8; 52] = action.ciphertext.as_slice().try_into().ok()?;
+
+    Some(CompactAction::from_parts(
+        Option::from(nullifier)?,
+        Option::from(cmx)?,
+        EphemeralKeyBytes(ephemeral_key),
+        ciphertext,
+    ))
+}
+
+/// A note recorded in the wallet that wasn't found on chain.
+#[derive(Debug, Clone)]
+pub struct OrphanNote {
+    pub height: u64,
+    pub txid: TxId,
+    pub pool: Pool,
+    pub note_index: usize,
+    pub value: u64,
+}
+
+/// Result of trial-decrypting the cached compact blocks.
+#[derive(Debug, Clone, Default)]
+pub struct ScanReport {
+    /// Range of cached block heights that were scanned, inclusive.
+    pub scanned_range: Option<(u64, u64)>,
+    pub blocks_scanned: usize,
+    pub undecodable_blocks: Vec<u64>,
+    /// Notes found on chain that are recorded in the wallet.
+    pub matched: Vec<DecryptedNote>,
+    /// Notes found on chain that the wallet never recorded.
+    pub missing: Vec<DecryptedNote>,
+    /// Notes the wallet recorded in the scanned range that weren't found on chain.
+    pub orphans: Vec<OrphanNote>,
+}
+
+impl ScanReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.orphans.is_empty()
+    }
+}
+
+impl ZwlWallet {
+    /// Trial-decrypts the cached compact blocks and compares the hits with `transactions`.
+    pub fn scan_cached_blocks(&self) -> Result<ScanReport, WalletError> {
+        let network = self.chain_name.require_network()?;
+        let keys = ScanningKeys::from_wallet(self);
+
+        let mut report = ScanReport::default();
+        let mut found = vec![];
+        for block in &self.blocks {
+            match block.compact_block() {
+                Ok(cb) => {
+                    found.extend(decrypt_block(&network, &keys, &cb));
+                    report.blocks_scanned += 1;
+                }
+                Err(_) => report.undecodable_blocks.push(block.height),
+            }
+        }
+
+        report.scanned_range = match (self.blocks.last(), self.blocks.first()) {
+            (Some(low), Some(high)) => Some((low.height, high.height)),
+            _ => None,
+        };
+
+        let mut recorded = HashSet::new();
+        for wtx in self.transactions.current.values() {
+            for nd in &wtx.sapling_notes {
+                recorded.insert((Pool::Sapling, nd.note.cmu().to_bytes()));
+            }
+            for nd in &wtx.orchard_notes {
+                let cmx = ExtractedNoteCommitment::from(nd.note.commitment()).to_bytes();
+                recorded.insert((Pool::Orchard, cmx));
+            }
+        }
+
+        let found_commitments: HashSet<_> = found.iter().map(|n| (n.pool, n.commitment)).collect();
+
+        for note in found {
+            if recorded.contains(&(note.pool, note.commitment)) {
+                report.matched.push(note);
+            } else {
+                report.missing.push(note);
+            }
+        }
+
+        if let Some((low, high)) = report.scanned_range {
+            for wtx in self.transactions.current.values() {
+                let height = u64::from(u32::from(wtx.block));
+                if wtx.unconfirmed || height < low || height > high {
+                    continue;
+                }
+
+                for (note_index, nd) in wtx.sapling_notes.iter().enumerate() {
+                    if !found_commitments.contains(&(Pool::Sapling, nd.note.cmu().to_bytes())) {
+                        report.orphans.push(OrphanNote {
+                            height,
+                            txid: wtx.txid,
+                            pool: Pool::Sapling,
+                            note_index,
+                            value: nd.note.value().inner(),
+                        });
+                    }
+                }
+
+                for (note_index, nd) in wtx.orchard_notes.iter().enumerate() {
+                    let cmx = ExtractedNoteCommitment::from(nd.note.commitment()).to_bytes();
+                    if !found_commitments.contains(&(Pool::Orchard, cmx)) {
+                        report.orphans.push(OrphanNote {
+                            height,
+                            txid: wtx.txid,
+                            pool: Pool::Orchard,
+                            note_index,
+                            value: nd.note.value().inner(),
+                        });
+                    }
+                }
+            }
+        }
+
+        report.orphans.sort_by_key(|n| (n.height, n.txid));
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,951 @@
+//! # Offline sync
+//!
+//! Advances a wallet with compact blocks read from disk instead of a lightwalletd server, so a
+//! stale wallet file can be repaired on an air-gapped machine and written back with
+//! [`WalletWriter`].
+//!
+//! A block dump is a file of length-delimited [`CompactBlock`] protobufs, which is what
+//! collecting the responses of lightwalletd's `GetBlockRange` into a file produces, or a
+//! directory of such files, read in file name order.
+//!
+//! Compact blocks carry no transparent inputs or outputs, so UTXOs are found in a second dump of
+//! length-delimited [`RawTransaction`] protobufs, which is what collecting the responses of
+//! `GetTaddressTxids` for each of the wallet's transparent addresses produces. Outputs paying
+//! those addresses are added as UTXOs, and inputs spending them mark them spent.
+//!
+//! Sapling witnesses can only be advanced from the height of `verified_tree` (or the note's
+//! latest witness) through the cached blocks, so a note whose witnesses fall behind the cache is
+//! reported as stale and left as is.
+//!
+//! [`WalletWriter`]: crate::writer::WalletWriter
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use incrementalmerkletree::{Position, Tree, bridgetree::BridgeTree};
+use orchard_old::{note::ExtractedNoteCommitment, tree::MerkleHashOrchard};
+use prost::Message;
+use sapling_crypto::{CommitmentTree, IncrementalWitness, Node};
+use tracing::instrument;
+use zcash_client_backend::proto::{
+    compact_formats::CompactBlock,
+    service::{RawTransaction, TreeState},
+};
+use zcash_keys::encoding::encode_transparent_address_p;
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId, Network},
+    merkle_tree::write_commitment_tree,
+    transaction::{Transaction, TxId},
+};
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet,
+        block::CompactBlockData,
+        orchard_data::{MERKLE_DEPTH, OrchardNoteData},
+        orchard_tree::encode_orchard_frontier,
+        sapling_data::SaplingNoteData,
+        sapling_witness::decode_sapling_tree,
+        scan::{DecryptedNote, DecryptedNoteKind, Pool, ScanningKeys, decrypt_block},
+        transactions::{MAX_REORG, Utxo, WalletTx, WitnessCache},
+    },
+};
+
+/// Reads compact blocks from a file of length-delimited protobufs, or from every file in a
+/// directory. The blocks are returned sorted by height.
+pub fn read_compact_blocks(path: impl AsRef<Path>) -> Result<Vec<CompactBlock>, WalletError> {
+    let mut blocks: Vec<CompactBlock> = read_messages(path.as_ref(), "compact block")?;
+    blocks.sort_by_key(|b| b.height);
+    Ok(blocks)
+}
+
+/// Reads raw transactions from a file of length-delimited protobufs, or from every file in a
+/// directory. The transactions are returned sorted by height.
+pub fn read_raw_transactions(path: impl AsRef<Path>) -> Result<Vec<RawTransaction>, WalletError> {
+    let mut txs: Vec<RawTransaction> = read_messages(path.as_ref(), "raw transaction")?;
+    txs.sort_by_key(|tx| tx.height);
+    Ok(txs)
+}
+
+fn read_messages<M: Message + Default>(path: &Path, what: &str) -> Result<Vec<M>, WalletError> {
+    let files = if path.is_dir() {
+        let mut files = fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        files.retain(|f| f.is_file());
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut messages = vec![];
+    for file in files {
+        let mut bytes = vec![];
+        fs::File::open(&file)?.read_to_end(&mut bytes)?;
+
+        let mut buf = &bytes[..];
+        while !buf.is_empty() {
+            let message = M::decode_length_delimited(&mut buf).map_err(|e| {
+                WalletError::InvalidFormat(format!(
+                    "Couldn't decode {} in {}: {}",
+                    what,
+                    file.display(),
+                    e
+                ))
+            })?;
+            messages.push(message);
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Writes `blocks` as length-delimited protobufs, in the format read by [`read_compact_blocks`].
+pub fn write_compact_blocks<W: Write>(mut writer: W, blocks: &[CompactBlock]) -> io::Result<()> {
+    for block in blocks {
+        writer.write_all(&block.encode_length_delimited_to_vec())?;
+    }
+    Ok(())
+}
+
+/// A note added to the wallet by a sync.
+#[derive(Debug, Clone)]
+pub struct ReceivedNote {
+    pub height: u64,
+    pub txid: TxId,
+    pub pool: Pool,
+    pub value: u64,
+    pub is_change: bool,
+}
+
+/// A wallet note marked spent by a sync.
+#[derive(Debug, Clone)]
+pub struct SpentNote {
+    pub height: u64,
+    /// The transaction spending the note.
+    pub txid: TxId,
+    /// The transaction the note was received in.
+    pub note_txid: TxId,
+    pub pool: Pool,
+    pub value: u64,
+}
+
+/// A transparent output to one of the wallet's addresses, added as a UTXO by a sync.
+#[derive(Debug, Clone)]
+pub struct ReceivedUtxo {
+    pub height: u64,
+    pub txid: TxId,
+    pub output_index: u64,
+    pub address: String,
+    pub value: u64,
+}
+
+/// A wallet UTXO marked spent by a sync.
+#[derive(Debug, Clone)]
+pub struct SpentUtxo {
+    pub height: u64,
+    /// The transaction spending the UTXO.
+    pub txid: TxId,
+    /// The transaction that created the UTXO.
+    pub utxo_txid: TxId,
+    pub output_index: u64,
+    pub value: u64,
+}
+
+/// Summary of a sync.
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    /// Height the wallet was synced to before the sync.
+    pub start_height: u64,
+    /// Height the wallet is synced to after the sync.
+    pub end_height: u64,
+    pub blocks_scanned: usize,
+    /// Blocks of the dump the wallet already had.
+    pub blocks_skipped: usize,
+    pub received: Vec<ReceivedNote>,
+    pub spent: Vec<SpentNote>,
+    pub received_utxos: Vec<ReceivedUtxo>,
+    pub spent_utxos: Vec<SpentUtxo>,
+    /// Sapling notes whose witnesses couldn't be brought up to the start height, as
+    /// `(txid, note index)`. Their witnesses weren't advanced.
+    pub stale_witnesses: Vec<(TxId, usize)>,
+}
+
+/// Position of a note inside `WalletTxns::current`.
+type NoteRef = (TxId, usize);
+
+impl ZwlWallet {
+    /// Reads the compact blocks at `blocks`, and the raw transparent transactions at
+    /// `transactions` if given, and syncs the wallet with them. See
+    /// [`ZwlWallet::sync_from_dump`].
+    pub fn sync_from_path(
+        &mut self,
+        blocks: impl AsRef<Path>,
+        transactions: Option<&Path>,
+    ) -> Result<SyncReport, WalletError> {
+        let blocks = read_compact_blocks(blocks)?;
+        let transactions = match transactions {
+            Some(path) => read_raw_transactions(path)?,
+            None => vec![],
+        };
+        self.sync_from_dump(&blocks, &transactions)
+    }
+
+    /// Scans `blocks` on top of the wallet's latest synced block.
+    ///
+    /// Blocks the wallet already has are skipped, and the remaining ones must continue the
+    /// chain of cached blocks without gaps. If the chains diverge, the wallet has to be rewound
+    /// first. On error the wallet is left untouched.
+    pub fn sync_from_blocks(&mut self, blocks: &[CompactBlock]) -> Result<SyncReport, WalletError> {
+        self.sync_from_dump(blocks, &[])
+    }
+
+    /// Scans `blocks` like [`ZwlWallet::sync_from_blocks`], then records the UTXOs that
+    /// `transactions` create and spend. The transactions must be mined at or below the synced
+    /// height, and ones the wallet already has are skipped.
+    #[instrument(level = "info", name = "ZwlWallet::sync_from_dump", skip_all, err)]
+    pub fn sync_from_dump(
+        &mut self,
+        blocks: &[CompactBlock],
+        transactions: &[RawTransaction],
+    ) -> Result<SyncReport, WalletError> {
+        let mut wallet = self.clone();
+        let mut report = wallet.sync(blocks)?;
+        wallet.sync_transparent(transactions, &mut report)?;
+        *self = wallet;
+        Ok(report)
+    }
+
+    fn sync(&mut self, blocks: &[CompactBlock]) -> Result<SyncReport, WalletError> {
+        let network = self.chain_name.require_network()?;
+
+        let (tip_height, tip_hash) = match (self.blocks.first(), &self.verified_tree) {
+            (Some(b), _) => (b.height, b.hash.clone()),
+            (None, Some(tree_state)) => (tree_state.height, tree_state.hash.clone()),
+            (None, None) => {
+                return Err(WalletError::InvalidFormat(
+                    "The wallet has neither cached blocks nor a verified tree to sync from"
+                        .to_string(),
+                ));
+            }
+        };
+
+        let mut report = SyncReport {
+            start_height: tip_height,
+            end_height: tip_height,
+            ..Default::default()
+        };
+
+        let cached = self.cached_commitments()?;
+
+        // Skip the blocks we already have, making sure they are the ones we have.
+        let mut new_blocks = vec![];
+        for block in blocks {
+            if block.height > tip_height {
+                new_blocks.push(block);
+                continue;
+            }
+
+            if let Some(cached) = self.blocks.iter().find(|b| b.height == block.height)
+                && cached.hash != block.hash().to_string()
+            {
+                return Err(WalletError::InvalidFormat(format!(
+                    "Block {} is {} in the dump but {} in the wallet. Rewind the wallet below the fork first",
+                    block.height,
+                    block.hash(),
+                    cached.hash
+                )));
+            }
+            report.blocks_skipped += 1;
+        }
+
+        let mut prev = (tip_height, tip_hash);
+        for block in &new_blocks {
+            if block.height != prev.0 + 1 {
+                return Err(WalletError::InvalidFormat(format!(
+                    "Expected block {}, but the dump continues with block {}",
+                    prev.0 + 1,
+                    block.height
+                )));
+            }
+            if block.prev_hash().to_string() != prev.1 {
+                return Err(WalletError::InvalidFormat(format!(
+                    "Block {} doesn't build on block {} ({}). Rewind the wallet below the fork first",
+                    block.height, prev.0, prev.1
+                )));
+            }
+            prev = (block.height, block.hash().to_string());
+        }
+
+        if new_blocks.is_empty() {
+            return Ok(report);
+        }
+
+        let mut sapling_tree = self.sapling_tree_at_tip(tip_height, &cached)?;
+
+        // Bring the witnesses of spendable notes up to the tip before scanning new blocks.
+        let mut tracked = vec![];
+        for wtx in self.transactions.current.values_mut() {
+            for (note_index, nd) in wtx.sapling_notes.iter_mut().enumerate() {
+                if nd.spent.is_some() || !nd.have_spending_key || nd.witnesses.is_empty() {
+                    continue;
+                }
+
+                if catch_up_witnesses(&mut nd.witnesses, tip_height, &cached) {
+                    tracked.push((wtx.txid, note_index));
+                } else {
+                    report.stale_witnesses.push((wtx.txid, note_index));
+                }
+            }
+        }
+
+        let keys = ScanningKeys::from_wallet(self);
+        for block in &new_blocks {
+            let found = decrypt_block(&network, &keys, block);
+            self.scan_block(block, found, &mut sapling_tree, &mut tracked, &mut report)?;
+            report.blocks_scanned += 1;
+            report.end_height = block.height;
+        }
+
+        if let (Some(tree_state), Some(last)) = (&mut self.verified_tree, new_blocks.last()) {
+            update_tree_state(
+                tree_state,
+                last,
+                sapling_tree.as_ref(),
+                self.orchard_witnesses.as_ref(),
+            )?;
+        }
+
+        report.stale_witnesses.sort();
+        Ok(report)
+    }
+
+    fn sync_transparent(
+        &mut self,
+        transactions: &[RawTransaction],
+        report: &mut SyncReport,
+    ) -> Result<(), WalletError> {
+        if transactions.is_empty() {
+            return Ok(());
+        }
+        let network = self.chain_name.require_network()?;
+
+        let mut txs = vec![];
+        for raw in transactions {
+            if raw.height == 0 || raw.height > report.end_height {
+                return Err(WalletError::InvalidFormat(format!(
+                    "A transaction at height {} isn't mined in the synced blocks, which end at {}",
+                    raw.height, report.end_height
+                )));
+            }
+            txs.push((raw.height, parse_transaction(&network, raw)?));
+        }
+        txs.sort_by_key(|(height, _)| *height);
+
+        // Outputs first, since a transaction may spend the output of one mined in the same block.
+        for (height, tx) in &txs {
+            let Some(bundle) = tx.transparent_bundle() else {
+                continue;
+            };
+            for (output_index, txout) in bundle.vout.iter().enumerate() {
+                let Some(address) = txout
+                    .recipient_address()
+                    .map(|a| encode_transparent_address_p(&network, &a))
+                    .filter(|a| self.keys.tkeys.iter().any(|tkey| &tkey.address == a))
+                else {
+                    continue;
+                };
+
+                let output_index = output_index as u64;
+                let time = self.block_time(*height);
+                let wtx = self.mined_tx(tx.txid(), *height, time);
+                if wtx
+                    .utxos
+                    .iter()
+                    .any(|u| u.txid == tx.txid() && u.output_index == output_index)
+                {
+                    continue;
+                }
+
+                let value = u64::from(txout.value);
+                wtx.utxos.push(Utxo {
+                    address: address.clone(),
+                    txid: tx.txid(),
+                    output_index,
+                    script: txout.script_pubkey.0.clone(),
+                    value,
+                    height: *height as i32,
+                    spent_at_height: None,
+                    spent: None,
+                    unconfirmed_spent: None,
+                });
+                report.received_utxos.push(ReceivedUtxo {
+                    height: *height,
+                    txid: tx.txid(),
+                    output_index,
+                    address,
+                    value,
+                });
+            }
+        }
+
+        for (height, tx) in &txs {
+            let Some(bundle) = tx.transparent_bundle() else {
+                continue;
+            };
+            for txin in &bundle.vin {
+                let prevout = (*txin.prevout.txid(), u64::from(txin.prevout.n()));
+                let Some(utxo) = self
+                    .transactions
+                    .current
+                    .get_mut(&prevout.0)
+                    .and_then(|wtx| wtx.utxos.iter_mut().find(|u| u.output_index == prevout.1))
+                    .filter(|u| u.spent.is_none())
+                else {
+                    continue;
+                };
+
+                utxo.spent = Some(tx.txid());
+                utxo.spent_at_height = Some(*height as i32);
+                utxo.unconfirmed_spent = None;
+                let value = utxo.value;
+
+                let time = self.block_time(*height);
+                self.mined_tx(tx.txid(), *height, time)
+                    .total_transparent_value_spent += value;
+                report.spent_utxos.push(SpentUtxo {
+                    height: *height,
+                    txid: tx.txid(),
+                    utxo_txid: prevout.0,
+                    output_index: prevout.1,
+                    value,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The time of the cached block at `height`, or 0 if it isn't cached.
+    fn block_time(&self, height: u64) -> u64 {
+        self.blocks
+            .iter()
+            .find(|b| b.height == height)
+            .and_then(|b| b.compact_block().ok())
+            .map_or(0, |cb| cb.time.into())
+    }
+
+    /// Note commitments of every decodable cached block, by height.
+    fn cached_commitments(&self) -> Result<BTreeMap<u64, Vec<Node>>, WalletError> {
+        let mut cached = BTreeMap::new();
+        for block in &self.blocks {
+            let cb = block.compact_block()?;
+            cached.insert(block.height, sapling_commitments(&cb)?);
+        }
+        Ok(cached)
+    }
+
+    /// Replays the cached blocks above `verified_tree` to get the Sapling tree at the tip.
+    fn sapling_tree_at_tip(
+        &self,
+        tip_height: u64,
+        cached: &BTreeMap<u64, Vec<Node>>,
+    ) -> Result<Option<CommitmentTree>, WalletError> {
+        let tree_state = match &self.verified_tree {
+            Some(tree_state) => tree_state,
+            None => return Ok(None),
+        };
+
+        if tree_state.height > tip_height {
+            return Err(WalletError::InvalidFormat(format!(
+                "The verified tree at {} is above the latest cached block {}",
+                tree_state.height, tip_height
+            )));
+        }
+
+        let mut tree = decode_sapling_tree(tree_state)?;
+        for height in tree_state.height + 1..=tip_height {
+            let commitments = cached.get(&height).ok_or_else(|| {
+                WalletError::InvalidFormat(format!(
+                    "Block {} between the verified tree and the latest cached block is missing",
+                    height
+                ))
+            })?;
+            for node in commitments {
+                tree.append(*node).map_err(|_| tree_full())?;
+            }
+        }
+
+        Ok(Some(tree))
+    }
+
+    fn scan_block(
+        &mut self,
+        block: &CompactBlock,
+        found: Vec<DecryptedNote>,
+        sapling_tree: &mut Option<CommitmentTree>,
+        tracked: &mut Vec<NoteRef>,
+        report: &mut SyncReport,
+    ) -> Result<(), WalletError> {
+        let height = block.height;
+        let block_height = BlockHeight::from_u32(height as u32);
+
+        let mut found: HashMap<_, _> = found
+            .into_iter()
+            .map(|n| ((n.txid, n.pool, n.output_index), n))
+            .collect();
+
+        // ZecWallet Lite checkpoints the Orchard tree before appending a block's actions, so
+        // rewinding to a checkpoint drops the whole block.
+        if let Some(tree) = self.orchard_witnesses.as_mut() {
+            tree.checkpoint();
+        }
+
+        let sapling_nullifiers = self.unspent_sapling_nullifiers();
+        let orchard_nullifiers = self.unspent_orchard_nullifiers();
+
+        // Commitments of this block, and the new Sapling notes with their witness and the number
+        // of commitments it has seen so far.
+        let mut commitments = vec![];
+        let mut new_sapling = vec![];
+
+        for ctx in &block.vtx {
+            let txid = ctx.txid();
+
+            for spend in &ctx.spends {
+                if let Some(note_ref) = sapling_nullifiers.get(spend.nf.as_slice()) {
+                    let value = self.mark_spent(Pool::Sapling, *note_ref, txid, block);
+                    report.spent.push(SpentNote {
+                        height,
+                        txid,
+                        note_txid: note_ref.0,
+                        pool: Pool::Sapling,
+                        value,
+                    });
+                    tracked.retain(|t| t != note_ref);
+                }
+            }
+
+            for action in &ctx.actions {
+                if let Some(note_ref) = orchard_nullifiers.get(action.nullifier.as_slice()) {
+                    let value = self.mark_spent(Pool::Orchard, *note_ref, txid, block);
+                    report.spent.push(SpentNote {
+                        height,
+                        txid,
+                        note_txid: note_ref.0,
+                        pool: Pool::Orchard,
+                        value,
+                    });
+                }
+            }
+
+            for (output_index, output) in ctx.outputs.iter().enumerate() {
+                let node = sapling_commitment(&output.cmu)?;
+                if let Some(tree) = sapling_tree.as_mut() {
+                    tree.append(node).map_err(|_| tree_full())?;
+                }
+                commitments.push(node);
+
+                if let Some(note) = found.remove(&(txid, Pool::Sapling, output_index)) {
+                    let witness = sapling_tree
+                        .as_ref()
+                        .map(|tree| IncrementalWitness::from_tree(tree.clone()))
+                        .ok_or_else(|| {
+                            WalletError::InvalidFormat(format!(
+                                "Found a Sapling note at {}, but the wallet has no verified tree to witness it",
+                                height
+                            ))
+                        })?;
+                    new_sapling.push((note, witness, commitments.len()));
+                }
+            }
+
+            for (action_index, action) in ctx.actions.iter().enumerate() {
+                let cmx: [u8; 32] = action.cmx.as_slice().try_into().map_err(|_| {
+                    WalletError::InvalidFormat(format!("Invalid cmx in block {}", height))
+                })?;
+                let cmx =
+                    Option::from(ExtractedNoteCommitment::from_bytes(&cmx)).ok_or_else(|| {
+                        WalletError::InvalidFormat(format!("Invalid cmx in block {}", height))
+                    })?;
+
+                let note = found.remove(&(txid, Pool::Orchard, action_index));
+                let tree = match self.orchard_witnesses.as_mut() {
+                    Some(tree) => tree,
+                    None if note.is_none() => continue,
+                    None => {
+                        return Err(WalletError::InvalidFormat(format!(
+                            "Found an Orchard note at {}, but the wallet has no Orchard witness tree",
+                            height
+                        )));
+                    }
+                };
+
+                tree.append(&MerkleHashOrchard::from_cmx(&cmx));
+
+                if let Some(note) = note {
+                    let position = tree.witness();
+                    let created_at = (height, ctx.index as usize, action_index as u32);
+                    report
+                        .received
+                        .push(self.add_orchard_note(note, block, created_at, position));
+                }
+            }
+        }
+
+        // Witnesses of notes received before this block.
+        for (txid, note_index) in tracked.iter() {
+            let nd = &mut self
+                .transactions
+                .current
+                .get_mut(txid)
+                .unwrap()
+                .sapling_notes[*note_index];
+            let mut witness = nd.witnesses.last().unwrap().clone();
+            for node in &commitments {
+                witness.append(*node).map_err(|_| tree_full())?;
+            }
+            push_witness(&mut nd.witnesses, witness, height);
+        }
+
+        // Witnesses of notes received in this block only need the commitments after their own.
+        for (note, mut witness, seen) in new_sapling {
+            for node in &commitments[seen..] {
+                witness.append(*node).map_err(|_| tree_full())?;
+            }
+            let (received, note_ref) = self.add_sapling_note(note, block, witness);
+            if let Some(note_ref) = note_ref {
+                tracked.push(note_ref);
+            }
+            report.received.push(received);
+        }
+
+        self.blocks.insert(
+            0,
+            CompactBlockData {
+                ecb: block.encode_to_vec(),
+                height,
+                hash: block.hash().to_string(),
+                tree: None,
+            },
+        );
+        self.blocks.truncate(MAX_REORG);
+
+        // Transactions sent from this wallet are mined once they are in the block, even if they
+        // paid nothing back to it.
+        for ctx in &block.vtx {
+            if let Some(wtx) = self.transactions.current.get_mut(&ctx.txid())
+                && wtx.unconfirmed
+            {
+                wtx.block = block_height;
+                wtx.unconfirmed = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn unspent_sapling_nullifiers(&self) -> HashMap<[u8; 32], NoteRef> {
+        let mut nullifiers = HashMap::new();
+        for wtx in self.transactions.current.values() {
+            for (note_index, nd) in wtx.sapling_notes.iter().enumerate() {
+                if nd.spent.is_none() {
+                    nullifiers.insert(nd.nullifier.0, (wtx.txid, note_index));
+                }
+            }
+        }
+        nullifiers
+    }
+
+    fn unspent_orchard_nullifiers(&self) -> HashMap<[u8; 32], NoteRef> {
+        let mut nullifiers = HashMap::new();
+        for wtx in self.transactions.current.values() {
+            for (note_index, nd) in wtx.orchard_notes.iter().enumerate() {
+                if nd.spent.is_none() {
+                    let nf = nd.note.nullifier(&nd.fvk).to_bytes();
+                    nullifiers.insert(nf, (wtx.txid, note_index));
+                }
+            }
+        }
+        nullifiers
+    }
+
+    /// Returns the transaction `txid` mined at `height`, creating it if needed.
+    fn mined_tx(&mut self, txid: TxId, height: u64, time: u64) -> &mut WalletTx {
+        let height = BlockHeight::from_u32(height as u32);
+        self.transactions.last_txid = Some(txid);
+
+        let wtx = self
+            .transactions
+            .current
+            .entry(txid)
+            .or_insert_with(|| WalletTx::new(height, time, &txid, false));
+
+        // The tx might have been recorded when it was sent, before it was mined.
+        wtx.block = height;
+        wtx.unconfirmed = false;
+        wtx
+    }
+
+    /// Marks a note spent by `txid` and returns its value.
+    fn mark_spent(
+        &mut self,
+        pool: Pool,
+        note_ref: NoteRef,
+        txid: TxId,
+        block: &CompactBlock,
+    ) -> u64 {
+        let spent = Some((txid, block.height as u32));
+        let note_tx = self.transactions.current.get_mut(&note_ref.0).unwrap();
+
+        let (value, sapling_nf, orchard_nf) = match pool {
+            Pool::Sapling => {
+                let nd = &mut note_tx.sapling_notes[note_ref.1];
+                nd.spent = spent;
+                nd.unconfirmed_spent = None;
+                (nd.note.value().inner(), Some(nd.nullifier), None)
+            }
+            _ => {
+                let nd = &mut note_tx.orchard_notes[note_ref.1];
+                nd.spent = spent;
+                nd.unconfirmed_spent = None;
+                (
+                    nd.note.value().inner(),
+                    None,
+                    Some(nd.note.nullifier(&nd.fvk)),
+                )
+            }
+        };
+
+        let wtx = self.mined_tx(txid, block.height, block.time.into());
+        if let Some(nf) = sapling_nf
+            && !wtx.s_spent_nullifiers.contains(&nf)
+        {
+            wtx.s_spent_nullifiers.push(nf);
+            wtx.total_sapling_value_spent += value;
+        }
+        if let Some(nf) = orchard_nf
+            && !wtx.o_spent_nullifiers.contains(&nf)
+        {
+            wtx.o_spent_nullifiers.push(nf);
+            wtx.total_orchard_value_spent += value;
+        }
+
+        value
+    }
+
+    /// Adds a received Sapling note, returning the note to track if its witnesses are kept.
+    fn add_sapling_note(
+        &mut self,
+        found: DecryptedNote,
+        block: &CompactBlock,
+        witness: IncrementalWitness,
+    ) -> (ReceivedNote, Option<NoteRef>) {
+        let note = match found.note {
+            DecryptedNoteKind::Sapling { note } => note,
+            DecryptedNoteKind::Orchard { .. } => unreachable!("not a Sapling note"),
+        };
+        let zkey = &self.keys.zkeys[found.key_index];
+        let extfvk = zkey.extfvk.clone();
+        let have_spending_key = zkey.have_spending_key();
+
+        let nullifier = note.nf(&extfvk.fvk.vk.nk, u64::from(witness.witnessed_position()));
+        let witnesses = if have_spending_key {
+            WitnessCache::new(vec![witness], block.height)
+        } else {
+            WitnessCache::empty()
+        };
+
+        let wtx = self.mined_tx(found.txid, block.height, block.time.into());
+        let is_change = wtx.total_sapling_value_spent + wtx.total_orchard_value_spent > 0;
+
+        let received = ReceivedNote {
+            height: block.height,
+            txid: found.txid,
+            pool: Pool::Sapling,
+            value: found.value,
+            is_change,
+        };
+
+        if let Some(nd) = wtx
+            .sapling_notes
+            .iter_mut()
+            .find(|n| n.nullifier == nullifier)
+        {
+            // The note was recorded by an interrupted sync, so just restart its witnesses.
+            nd.witnesses = witnesses;
+        } else {
+            wtx.sapling_notes.push(SaplingNoteData {
+                extfvk,
+                diversifier: *note.recipient().diversifier(),
+                note,
+                witnesses,
+                nullifier,
+                spent: None,
+                unconfirmed_spent: None,
+                memo: None,
+                is_change,
+                have_spending_key,
+            });
+            // Remove the pending notes recorded when the tx was sent.
+            wtx.sapling_notes.retain(|n| n.nullifier.0 != [0u8; 32]);
+        }
+
+        let note_index = wtx
+            .sapling_notes
+            .iter()
+            .position(|n| n.nullifier == nullifier)
+            .unwrap();
+        let tracked = have_spending_key.then_some((found.txid, note_index));
+
+        (received, tracked)
+    }
+
+    fn add_orchard_note(
+        &mut self,
+        found: DecryptedNote,
+        block: &CompactBlock,
+        created_at: (u64, usize, u32),
+        witness_position: Option<Position>,
+    ) -> ReceivedNote {
+        let note = match found.note {
+            DecryptedNoteKind::Orchard { note } => note,
+            DecryptedNoteKind::Sapling { .. } => unreachable!("not an Orchard note"),
+        };
+        let okey = &self.keys.okeys[found.key_index];
+        let fvk = okey.fvk.clone();
+        let have_spending_key = okey.have_spending_key();
+
+        let wtx = self.mined_tx(found.txid, block.height, block.time.into());
+        let is_change = wtx.total_sapling_value_spent + wtx.total_orchard_value_spent > 0;
+
+        let nullifier = note.nullifier(&fvk);
+        match wtx
+            .orchard_notes
+            .iter_mut()
+            .find(|n| n.note.nullifier(&n.fvk) == nullifier)
+        {
+            Some(nd) => nd.witness_position = witness_position,
+            None => wtx.orchard_notes.push(OrchardNoteData {
+                fvk,
+                note,
+                created_at,
+                witness_position,
+                spent: None,
+                unconfirmed_spent: None,
+                memo: None,
+                is_change,
+                have_spending_key,
+            }),
+        }
+
+        ReceivedNote {
+            height: block.height,
+            txid: found.txid,
+            pool: Pool::Orchard,
+            value: found.value,
+            is_change,
+        }
+    }
+}
+
+/// Advances `cache` from its top height to `tip_height` using the cached blocks. Returns false
+/// if the cache can't be brought up to the tip.
+fn catch_up_witnesses(
+    cache: &mut WitnessCache,
+    tip_height: u64,
+    cached: &BTreeMap<u64, Vec<Node>>,
+) -> bool {
+    if cache.top_height > tip_height {
+        return false;
+    }
+
+    let mut witnesses = vec![];
+    let mut witness = cache.last().unwrap().clone();
+    for height in cache.top_height + 1..=tip_height {
+        let commitments = match cached.get(&height) {
+            Some(commitments) => commitments,
+            None => return false,
+        };
+        for node in commitments {
+            if witness.append(*node).is_err() {
+                return false;
+            }
+        }
+        witnesses.push((witness.clone(), height));
+    }
+
+    for (witness, height) in witnesses {
+        push_witness(cache, witness, height);
+    }
+    true
+}
+
+fn push_witness(cache: &mut WitnessCache, witness: IncrementalWitness, height: u64) {
+    cache.witnesses.push(witness);
+    cache.top_height = height;
+
+    let len = cache.witnesses.len();
+    if len > MAX_REORG {
+        cache.witnesses.drain(..len - MAX_REORG);
+    }
+}
+
+pub(crate) fn sapling_commitments(block: &CompactBlock) -> Result<Vec<Node>, WalletError> {
+    block
+        .vtx
+        .iter()
+        .flat_map(|ctx| &ctx.outputs)
+        .map(|output| sapling_commitment(&output.cmu))
+        .collect()
+}
+
+fn sapling_commitment(cmu: &[u8]) -> Result<Node, WalletError> {
+    cmu.try_into()
+        .ok()
+        .and_then(|cmu| Option::from(Node::from_bytes(cmu)))
+        .ok_or_else(|| WalletError::InvalidFormat("Invalid Sapling note commitment".to_string()))
+}
+
+fn parse_transaction(network: &Network, raw: &RawTransaction) -> Result<Transaction, WalletError> {
+    let branch_id = BranchId::for_height(network, BlockHeight::from_u32(raw.height as u32));
+    Transaction::read(&raw.data[..], branch_id).map_err(|e| {
+        WalletError::InvalidFormat(format!(
+            "Couldn't parse a transaction at height {}: {}",
+            raw.height, e
+        ))
+    })
+}
+
+fn tree_full() -> WalletError {
+    WalletError::InvalidFormat("The Sapling commitment tree is full".to_string())
+}
+
+fn update_tree_state(
+    tree_state: &mut TreeState,
+    block: &CompactBlock,
+    sapling_tree: Option<&CommitmentTree>,
+    orchard_tree: Option<&BridgeTree<MerkleHashOrchard, MERKLE_DEPTH>>,
+) -> Result<(), WalletError> {
+    tree_state.height = block.height;
+    tree_state.hash = block.hash().to_string();
+    tree_state.time = block.time;
+
+    if let Some(tree) = sapling_tree {
+        let mut buf = vec![];
+        write_commitment_tree(tree, &mut buf)?;
+        tree_state.sapling_tree = hex::encode(buf);
+    }
+
+    if let Some(tree) = orchard_tree {
+        tree_state.orchard_tree = hex::encode(encode_orchard_frontier(tree));
+    }
+
+    Ok(())
+}
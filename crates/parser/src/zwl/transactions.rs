@@ -8,6 +8,7 @@ use zcash_primitives::{
     memo::{Memo, MemoBytes},
     transaction::{
         TxId,
+        builder::DEFAULT_TX_EXPIRY_DELTA,
         components::OutPoint,
     },
 };
@@ -71,12 +72,19 @@ impl WalletTx {
         23
     }
 
-    pub fn new_txid(txid: &Vec<u8>) -> TxId {
+    pub fn new_txid(txid: &[u8]) -> TxId {
         let mut txid_bytes = [0u8; 32];
         txid_bytes.copy_from_slice(txid);
         TxId::from_bytes(txid_bytes)
     }
 
+    /// Whether this transaction is still unmined more than [`DEFAULT_TX_EXPIRY_DELTA`] blocks
+    /// after `block`, the height at which it was sent.
+    pub fn is_expired(&self, latest_height: u64) -> bool {
+        self.unconfirmed
+            && latest_height > u64::from(self.block) + u64::from(DEFAULT_TX_EXPIRY_DELTA)
+    }
+
     pub fn new(height: BlockHeight, datetime: u64, txid: &TxId, unconfirmed: bool) -> Self {
         WalletTx {
             block: height,
@@ -0,0 +1,58 @@
+//! # Rescan-ready trimming
+//!
+//! The usual fix for a wallet whose sync state is broken: keep the keys, wipe everything that
+//! was learned from the chain, and let ZecWallet Lite rescan.
+//!
+//! [`ZwlWallet::trim_for_rescan`] keeps `keys`, `chain_name`, `wallet_options` and `birthday`
+//! (and the price info, which doesn't depend on the sync), and clears the cached blocks, the
+//! transactions and both commitment trees. Given a checkpoint [`TreeState`], the trees are reset
+//! to it instead, and the birthday is moved to the block after it: without cached blocks,
+//! ZecWallet Lite takes the block before the birthday as the last scanned one, so the rescan
+//! starts right after the checkpoint. Notes received between the old birthday and the checkpoint
+//! are then never found.
+
+use zcash_client_backend::proto::service::TreeState;
+
+use crate::{
+    error::WalletError,
+    zwl::{
+        ZwlWallet, orchard_tree::orchard_tree_from_frontier, sapling_witness::decode_sapling_tree,
+        transactions::MAX_REORG,
+    },
+};
+
+impl ZwlWallet {
+    /// Clears the sync state of the wallet, optionally resetting it to `checkpoint`.
+    ///
+    /// The wallet is left untouched if the checkpoint's trees can't be decoded.
+    pub fn trim_for_rescan(&mut self, checkpoint: Option<&TreeState>) -> Result<(), WalletError> {
+        let (verified_tree, orchard_witnesses) = match checkpoint {
+            Some(tree_state) => {
+                decode_sapling_tree(tree_state)?;
+                let max_checkpoints = self
+                    .orchard_witnesses
+                    .as_ref()
+                    .map_or(MAX_REORG, |t| t.max_checkpoints());
+                let orchard_tree = orchard_tree_from_frontier(tree_state, max_checkpoints)?;
+                (Some(tree_state.clone()), Some(orchard_tree))
+            }
+            None => (None, None),
+        };
+
+        self.blocks.clear();
+        self.transactions.clear();
+        if let Some(tree_state) = &verified_tree {
+            self.birthday = tree_state.height + 1;
+        }
+        self.verified_tree = verified_tree;
+        self.orchard_witnesses = orchard_witnesses;
+
+        Ok(())
+    }
+
+    /// The height ZecWallet Lite starts scanning at: the block after the latest cached one, or
+    /// the birthday if no blocks are cached.
+    pub fn rescan_start_height(&self) -> u64 {
+        self.blocks.first().map_or(self.birthday, |b| b.height + 1)
+    }
+}
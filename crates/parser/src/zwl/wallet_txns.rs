@@ -1,10 +1,10 @@
 use std::{
     collections::HashMap,
     fmt::{self, Display},
-    io::{self, Read},
+    io::{self, Read, Write},
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use sapling_crypto::zip32::ExtendedFullViewingKey;
 use tracing::instrument;
 use zcash_encoding::Vector;
@@ -17,6 +17,10 @@ use super::transactions::WalletTx;
 pub struct WalletTxns {
     pub current: HashMap<TxId, WalletTx>,
     pub last_txid: Option<TxId>,
+
+    // Transactions still in the mempool when a version <= 20 file was saved. Newer versions keep
+    // them in `current`, flagged `unconfirmed`.
+    pub mempool: HashMap<TxId, WalletTx>,
 }
 
 impl Default for WalletTxns {
@@ -38,6 +42,10 @@ impl Display for WalletTxns {
             }
         }
 
+        if !self.mempool.is_empty() {
+            writeln!(f, "Legacy mempool transactions: {}", self.mempool.len()).unwrap();
+        }
+
         Ok(())
     }
 }
@@ -47,6 +55,7 @@ impl WalletTxns {
         Self {
             current: HashMap::new(),
             last_txid: None,
+            mempool: HashMap::new(),
         }
     }
 
@@ -83,21 +92,61 @@ impl WalletTxns {
             })
             .map(|v| v.0);
 
-        let _mempool = if version <= 20 {
+        let mempool = if version <= 20 {
             Vector::read(&mut reader, |r| {
                 let mut txid_bytes = [0u8; 32];
                 r.read_exact(&mut txid_bytes)?;
-                let wtx = WalletTx::read(r)?;
+                let mut wtx = WalletTx::read(r)?;
+                // These versions didn't store the flag, but mempool transactions aren't mined.
+                wtx.unconfirmed = true;
 
                 Ok((TxId::from_bytes(txid_bytes), wtx))
             })?
             .into_iter()
             .collect()
         } else {
-            vec![]
+            HashMap::new()
         };
 
-        Ok(Self { current, last_txid })
+        Ok(Self {
+            current,
+            last_txid,
+            mempool,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        // Write the version
+        writer.write_u64::<LittleEndian>(Self::serialized_version())?;
+
+        // The hashmap, write as a set of tuples. Store them sorted so that wallets are
+        // deterministically saved. There's no mempool section anymore, so legacy mempool
+        // transactions are saved as unconfirmed ones.
+        {
+            let mut txns = self
+                .current
+                .iter()
+                .chain(
+                    self.mempool
+                        .iter()
+                        .filter(|(txid, _)| !self.current.contains_key(*txid)),
+                )
+                .collect::<Vec<(&TxId, &WalletTx)>>();
+            txns.sort_by(|a, b| a.0.cmp(b.0));
+
+            Vector::write(&mut writer, &txns, |w, (k, v)| {
+                w.write_all(k.as_ref())?;
+                v.write(w)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.current.clear();
+        self.last_txid = None;
+        self.mempool.clear();
     }
 
     pub fn adjust_spendable_status(&mut self, spendable_keys: Vec<ExtendedFullViewingKey>) {
@@ -0,0 +1,172 @@
+//! # Watch-only wallets
+//!
+//! [`ZwlWallet::make_watch_only`] removes every secret from a wallet so that it can be handed to
+//! an auditor: the seed (plain and encrypted), the Sapling extended spending keys, the Orchard
+//! spending keys, the transparent private keys and all encrypted key blobs.
+//!
+//! ZecWallet Lite treats a key as spendable when it is HD derived, so the keys are also turned
+//! into imported viewing keys, and the notes lose `have_spending_key`. Sapling witnesses are
+//! dropped along with it, as ZecWallet Lite only keeps them for spendable notes. Transparent
+//! keys have no viewing-only type, and are kept as imported keys without a private key.
+//!
+//! [`ZwlWallet::secrets`] lists the secret bytes of a wallet, and [`find_secrets`] looks for them
+//! in a serialized wallet, to check that none made it into the watch-only file.
+
+use crate::zwl::{
+    ZwlWallet,
+    keys::{orchard::WalletOKeyType, sapling::WalletZKeyType, transparent::WalletTKeyType},
+};
+
+/// A secret of a wallet, and what it is.
+#[derive(Clone)]
+pub struct Secret {
+    pub label: String,
+    pub bytes: Vec<u8>,
+}
+
+// Don't print the secret itself.
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Secret({}, {} bytes)", self.label, self.bytes.len())
+    }
+}
+
+/// What [`ZwlWallet::make_watch_only`] removed.
+#[derive(Debug, Clone, Default)]
+pub struct WatchOnlyReport {
+    pub seed_removed: bool,
+    pub zkeys_converted: usize,
+    pub okeys_converted: usize,
+    pub tkeys_stripped: usize,
+    pub notes_updated: usize,
+}
+
+impl ZwlWallet {
+    /// Lists the secret bytes of the wallet: the seeds, spending keys and encrypted keys.
+    ///
+    /// Only the spending parts of Sapling keys are listed, since the rest of an extended
+    /// spending key is also part of its viewing key.
+    pub fn secrets(&self) -> Vec<Secret> {
+        let mut secrets = vec![];
+        let mut push = |label: String, bytes: &[u8]| {
+            if bytes.iter().any(|b| *b != 0) {
+                secrets.push(Secret {
+                    label,
+                    bytes: bytes.to_vec(),
+                });
+            }
+        };
+
+        push("seed".to_string(), &self.keys.seed);
+        push("encrypted seed".to_string(), &self.keys.enc_seed);
+
+        for (i, zkey) in self.keys.zkeys.iter().enumerate() {
+            if let Some(extsk) = &zkey.extsk {
+                let bytes = extsk.to_bytes();
+                push(format!("zkey {} ask", i), &bytes[41..73]);
+                push(format!("zkey {} nsk", i), &bytes[73..105]);
+            }
+            if let Some(enc_key) = &zkey.enc_key {
+                push(format!("zkey {} encrypted key", i), enc_key);
+            }
+        }
+
+        for (i, okey) in self.keys.okeys.iter().enumerate() {
+            if let Some(sk) = &okey.sk {
+                push(format!("okey {} spending key", i), sk.to_bytes());
+            }
+            if let Some(enc_key) = &okey.enc_key {
+                push(format!("okey {} encrypted key", i), enc_key);
+            }
+        }
+
+        for (i, tkey) in self.keys.tkeys.iter().enumerate() {
+            if let Some(pk) = &tkey.pk {
+                push(format!("tkey {} private key", i), &pk.secret_bytes());
+            }
+            if let Some(enc_key) = &tkey.enc_key {
+                push(format!("tkey {} encrypted key", i), enc_key);
+            }
+        }
+
+        secrets
+    }
+
+    /// Removes every secret from the wallet, leaving viewing keys and addresses.
+    pub fn make_watch_only(&mut self) -> WatchOnlyReport {
+        let mut report = WatchOnlyReport::default();
+
+        let keys = &mut self.keys;
+        report.seed_removed = keys.seed != [0u8; 32] || keys.enc_seed != [0u8; 48];
+        keys.encrypted = false;
+        keys.seed = [0u8; 32];
+        keys.enc_seed = [0u8; 48];
+        keys.nonce.clear();
+
+        for zkey in keys.zkeys.iter_mut() {
+            if zkey.keytype != WalletZKeyType::ImportedViewKey || zkey.have_spending_key() {
+                report.zkeys_converted += 1;
+            }
+            zkey.keytype = WalletZKeyType::ImportedViewKey;
+            zkey.locked = false;
+            zkey.extsk = None;
+            zkey.hdkey_num = None;
+            zkey.enc_key = None;
+            zkey.nonce = None;
+        }
+
+        for okey in keys.okeys.iter_mut() {
+            if okey.keytype != WalletOKeyType::ImportedFullViewKey
+                || okey.sk.is_some()
+                || okey.enc_key.is_some()
+            {
+                report.okeys_converted += 1;
+            }
+            okey.keytype = WalletOKeyType::ImportedFullViewKey;
+            okey.locked = false;
+            okey.sk = None;
+            okey.hdkey_num = None;
+            okey.enc_key = None;
+            okey.nonce = None;
+        }
+
+        for tkey in keys.tkeys.iter_mut() {
+            if tkey.pk.is_some() || tkey.enc_key.is_some() {
+                report.tkeys_stripped += 1;
+            }
+            tkey.keytype = WalletTKeyType::ImportedKey;
+            tkey.locked = false;
+            tkey.pk = None;
+            tkey.hdkey_num = None;
+            tkey.enc_key = None;
+            tkey.nonce = None;
+        }
+
+        let txns = &mut self.transactions;
+        for wtx in txns.current.values_mut().chain(txns.mempool.values_mut()) {
+            for nd in wtx.sapling_notes.iter_mut() {
+                if nd.have_spending_key {
+                    report.notes_updated += 1;
+                }
+                nd.have_spending_key = false;
+                nd.witnesses.clear();
+            }
+            for nd in wtx.orchard_notes.iter_mut() {
+                if nd.have_spending_key {
+                    report.notes_updated += 1;
+                }
+                nd.have_spending_key = false;
+            }
+        }
+
+        report
+    }
+}
+
+/// Returns the secrets that appear in `bytes`, usually a serialized wallet.
+pub fn find_secrets<'a>(secrets: &'a [Secret], bytes: &[u8]) -> Vec<&'a Secret> {
+    secrets
+        .iter()
+        .filter(|s| bytes.windows(s.bytes.len()).any(|w| w == s.bytes))
+        .collect()
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use sha2::{Digest, Sha256};
+use xsalsa20poly1305::{KeyInit, Nonce, XSalsa20Poly1305, aead::Aead};
+use zecwallet_parser::{reader::WalletReader, zwl::scan::Pool};
+
+use common::{HEIGHT, WALLET, utxo, wtx};
+
+#[test]
+fn next_addresses_are_derived_and_flagged_when_used() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    let enumeration = wallet.enumerate_addresses(3, None).unwrap();
+    assert_eq!(enumeration.transparent_skipped, None);
+    let count = |pool| {
+        enumeration
+            .addresses
+            .iter()
+            .filter(|a| a.pool == pool)
+            .count()
+    };
+    assert_eq!(count(Pool::Sapling), 3 * wallet.keys.zkeys.len());
+    assert_eq!(count(Pool::Orchard), 3 * wallet.keys.okeys.len());
+    assert_eq!(count(Pool::Transparent), 3);
+    assert_eq!(enumeration.used().count(), 0);
+
+    // The transparent addresses continue after the stored HD keys.
+    let next = wallet.keys.tkeys.len() as u128;
+    let transparent = enumeration
+        .addresses
+        .iter()
+        .find(|a| a.pool == Pool::Transparent)
+        .unwrap()
+        .clone();
+    assert_eq!(transparent.index, next);
+    assert!(
+        wallet
+            .keys
+            .tkeys
+            .iter()
+            .all(|tkey| tkey.address != transparent.address)
+    );
+
+    // A Utxo received at it marks it as used.
+    let mut wtx = wtx(1, HEIGHT);
+    wtx.utxos
+        .push(utxo(wtx.txid, &transparent.address, 10_000, None));
+    wallet.transactions.current.insert(wtx.txid, wtx);
+
+    let enumeration = wallet.enumerate_addresses(3, None).unwrap();
+    let used: Vec<_> = enumeration.used().collect();
+    assert_eq!(used.len(), 1);
+    assert_eq!(used[0].address, transparent.address);
+}
+
+#[test]
+fn locked_wallets_need_the_password_for_transparent_addresses() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let unlocked = wallet.enumerate_addresses(2, None).unwrap();
+
+    // Encrypt and lock the wallet like ZecWallet Lite does.
+    let cipher = XSalsa20Poly1305::new(&Sha256::digest(Sha256::digest(b"password")));
+    let nonce = [7u8; 24];
+    let enc_seed = cipher
+        .encrypt(Nonce::from_slice(&nonce), &wallet.keys.seed[..])
+        .unwrap();
+    wallet.keys.encrypted = true;
+    wallet.keys.enc_seed = enc_seed.try_into().unwrap();
+    wallet.keys.nonce = nonce.to_vec();
+    wallet.keys.seed = [0u8; 32];
+
+    let skipped = wallet.enumerate_addresses(2, None).unwrap();
+    assert!(skipped.transparent_skipped.is_some());
+    assert!(
+        skipped
+            .addresses
+            .iter()
+            .all(|a| a.pool != Pool::Transparent)
+    );
+    assert!(wallet.enumerate_addresses(2, Some("wrong")).is_err());
+
+    let enumeration = wallet.enumerate_addresses(2, Some("password")).unwrap();
+    assert_eq!(enumeration.transparent_skipped, None);
+    assert_eq!(enumeration.addresses, unlocked.addresses);
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use bip0039::{English, Mnemonic};
+use incrementalmerkletree::Tree;
+use sapling_crypto::{CommitmentTree, Node};
+use zcash_client_backend::proto::service::TreeState;
+use zcash_primitives::merkle_tree::write_commitment_tree;
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, orchard_tree::encode_orchard_frontier, watch_only::find_secrets},
+};
+
+use common::WALLET;
+
+#[test]
+fn anonymized_wallet_keeps_the_structure() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let (anonymized, _) = wallet.anonymize(true).unwrap();
+
+    let original_bytes = WalletWriter::to_bytes(&wallet).unwrap();
+    let bytes = WalletWriter::to_bytes(&anonymized).unwrap();
+    assert_eq!(bytes.len(), original_bytes.len());
+    for (block, original) in anonymized.blocks.iter().zip(&wallet.blocks) {
+        assert_eq!((&block.hash, &block.ecb), (&original.hash, &original.ecb));
+    }
+
+    let read = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    assert_eq!(read.version, wallet.version);
+    assert_eq!(read.birthday, wallet.birthday);
+    assert_eq!(read.blocks.len(), wallet.blocks.len());
+    assert_eq!(read.keys.zkeys.len(), wallet.keys.zkeys.len());
+    assert_eq!(read.keys.okeys.len(), wallet.keys.okeys.len());
+    assert_eq!(read.keys.tkeys.len(), wallet.keys.tkeys.len());
+
+    let tree = wallet.orchard_witnesses.as_ref().unwrap();
+    let read_tree = read.orchard_witnesses.as_ref().unwrap();
+    assert_eq!(read_tree.prior_bridges().len(), tree.prior_bridges().len());
+    assert_eq!(read_tree.witnessed_indices(), tree.witnessed_indices());
+    assert_eq!(read_tree.checkpoints(), tree.checkpoints());
+    assert_eq!(read_tree.current_position(), tree.current_position());
+    assert_ne!(read_tree.root(0), tree.root(0));
+
+    // The HD keys still derive from the new seed.
+    let phrase = <Mnemonic<English>>::from_entropy(read.keys.seed)
+        .unwrap()
+        .phrase()
+        .to_string();
+    let created =
+        ZwlWallet::from_seed_phrase(&phrase, 2, read.chain_name, read.birthday, None).unwrap();
+    assert_eq!(created.keys.zkeys, read.keys.zkeys);
+    for (created, key) in created.keys.tkeys.iter().zip(&read.keys.tkeys) {
+        assert_eq!(created.address, key.address);
+    }
+}
+
+#[test]
+fn anonymized_wallet_has_none_of_the_secrets() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let (anonymized, map) = wallet.anonymize(false).unwrap();
+
+    let bytes = WalletWriter::to_bytes(&anonymized).unwrap();
+    assert!(find_secrets(&wallet.secrets(), &bytes).is_empty());
+
+    for key in &wallet.keys.tkeys {
+        let mapped = &map.addresses[&key.address];
+        assert_ne!(mapped, &key.address);
+        assert!(anonymized.keys.tkeys.iter().any(|k| &k.address == mapped));
+    }
+    assert_eq!(map.addresses.len(), 5);
+}
+
+#[test]
+fn cached_blocks_are_anonymized_by_default() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let mut sapling_tree = CommitmentTree::empty();
+    sapling_tree
+        .append(Node::from_bytes([7; 32]).unwrap())
+        .unwrap();
+    let mut bytes = vec![];
+    write_commitment_tree(&sapling_tree, &mut bytes).unwrap();
+    let tip = wallet.blocks.last().unwrap().clone();
+    wallet.verified_tree = Some(TreeState {
+        network: "main".to_string(),
+        height: tip.height,
+        hash: tip.hash.clone(),
+        sapling_tree: hex::encode(bytes),
+        orchard_tree: hex::encode(encode_orchard_frontier(
+            wallet.orchard_witnesses.as_ref().unwrap(),
+        )),
+        ..Default::default()
+    });
+    let (anonymized, _) = wallet.anonymize(false).unwrap();
+
+    assert_eq!(anonymized.blocks.len(), wallet.blocks.len());
+    for (block, original) in anonymized.blocks.iter().zip(&wallet.blocks) {
+        assert_eq!(block.height, original.height);
+        assert_ne!(block.hash, original.hash);
+        assert_ne!(block.ecb, original.ecb);
+        assert_eq!(block.compact_block().unwrap().height, original.height);
+    }
+    assert_eq!(anonymized.check_block_continuity(), []);
+
+    let verified_tree = anonymized.verified_tree.as_ref().unwrap();
+    let original_tree = wallet.verified_tree.as_ref().unwrap();
+    assert_eq!(verified_tree.height, original_tree.height);
+    assert_eq!(verified_tree.hash, anonymized.blocks.last().unwrap().hash);
+    assert_ne!(verified_tree.sapling_tree, original_tree.sapling_tree);
+    assert_eq!(
+        verified_tree.sapling_tree.len(),
+        original_tree.sapling_tree.len()
+    );
+    assert_ne!(verified_tree.orchard_tree, original_tree.orchard_tree);
+
+    let bytes = WalletWriter::to_bytes(&anonymized).unwrap();
+    let read = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    for (block, anonymized) in read.blocks.iter().zip(&anonymized.blocks) {
+        assert_eq!(
+            (&block.hash, &block.ecb),
+            (&anonymized.hash, &anonymized.ecb)
+        );
+    }
+    assert_eq!(read.verified_tree, anonymized.verified_tree);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use zcash_keys::encoding::encode_payment_address_p;
+use zcash_primitives::{consensus::Network, transaction::TxId};
+use zecwallet_parser::{reader::WalletReader, zwl::scan::Pool};
+
+use common::{HEIGHT, WALLET, sapling_note, utxo, wtx};
+
+#[test]
+fn notes_are_attributed_to_their_receiving_address() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let taddress = wallet.keys.tkeys[0].address.clone();
+
+    // A spent Sapling note at a diversified address, an unspent Utxo, and one spent at a height
+    // the wallet doesn't have.
+    let dfvk = extfvk.to_diversifiable_full_viewing_key();
+    let mut index = dfvk.default_address().0;
+    index.increment().unwrap();
+    let (_, zaddress) = dfvk.find_address(index).unwrap();
+    let mut wtx = wtx(1, HEIGHT);
+    let mut note = sapling_note(&extfvk, zaddress, 5_000, 1);
+    note.spent = Some((TxId::from_bytes([2; 32]), 2757950));
+    wtx.sapling_notes.push(note);
+    wtx.utxos.push(utxo(wtx.txid, &taddress, 3_000, None));
+    let mut spent = utxo(wtx.txid, &taddress, 4_000, None);
+    spent.output_index = 1;
+    spent.spent = Some(TxId::from_bytes([2; 32]));
+    spent.spent_at_height = Some(-1);
+    wtx.utxos.push(spent);
+    wallet.transactions.current.insert(wtx.txid, wtx);
+
+    let balances = wallet.address_balances();
+    // The five default addresses of the keys, and the diversified one.
+    assert_eq!(balances.len(), 6);
+
+    let diversified = encode_payment_address_p(&Network::MainNetwork, &zaddress);
+    let sapling = balances.iter().find(|b| b.address == diversified).unwrap();
+    assert_eq!(sapling.pool, Pool::Sapling);
+    assert!(!sapling.in_keys);
+    assert_eq!(
+        (sapling.received, sapling.spent, sapling.balance()),
+        (5_000, 5_000, 0)
+    );
+    assert_eq!(
+        (sapling.first_activity, sapling.last_activity),
+        (Some(2757900), Some(2757950))
+    );
+
+    let transparent = balances.iter().find(|b| b.address == taddress).unwrap();
+    assert!(transparent.in_keys);
+    assert_eq!(
+        (transparent.notes, transparent.spent, transparent.balance()),
+        (2, 4_000, 3_000)
+    );
+    assert_eq!(
+        (transparent.first_activity, transparent.last_activity),
+        (Some(2757900), Some(2757900))
+    );
+}
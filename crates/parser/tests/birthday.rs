@@ -0,0 +1,46 @@
+mod common;
+
+use zcash_primitives::consensus::Network;
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{birthday::BirthdaySource, import::ImportedKey},
+};
+
+use common::WALLET;
+
+#[test]
+fn stored_birthday_below_the_history_is_kept() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    let estimate = wallet.estimate_birthday();
+    assert_eq!(estimate.suggested, wallet.birthday);
+    assert!(!estimate.changes_birthday());
+    assert!(
+        estimate
+            .evidence
+            .contains(&(BirthdaySource::LowestCachedBlock, 2757862))
+    );
+
+    wallet.birthday = 2800000;
+    let estimate = wallet.estimate_birthday();
+    assert_eq!(estimate.suggested, 2757862);
+}
+
+#[test]
+fn missing_birthday_is_estimated_unless_keys_were_imported() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    wallet.birthday = 0;
+
+    let estimate = wallet.estimate_birthday();
+    assert!(estimate.stored_is_missing());
+    assert_eq!(estimate.suggested, 2757862);
+
+    // The example private key of the Bitcoin wiki.
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    let key = ImportedKey::decode(&Network::MainNetwork, wif).unwrap();
+    wallet.import_key(key, None).unwrap();
+
+    let estimate = wallet.estimate_birthday();
+    assert_eq!(estimate.imported_keys, 1);
+    assert_eq!(estimate.suggested, 419200);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::block::{ChainBreak, check_chain_continuity},
+};
+
+use common::WALLET;
+
+#[test]
+fn cached_blocks_decode_and_link() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    assert_eq!(wallet.blocks.len(), 100);
+
+    for block in &wallet.blocks {
+        let cb = block.compact_block().unwrap();
+        assert_eq!(cb.height, block.height);
+        assert_eq!(cb.hash().to_string(), block.hash);
+    }
+    assert_eq!(wallet.check_block_continuity(), []);
+}
+
+#[test]
+fn broken_chain_is_reported() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+
+    // A wrong hash breaks both the block's header and the link from the block above it.
+    let mut blocks = wallet.blocks.clone();
+    let (height, real_hash) = (blocks[50].height, blocks[50].hash.clone());
+    blocks[50].hash = "00".repeat(32);
+    assert_eq!(
+        check_chain_continuity(&blocks),
+        [
+            ChainBreak::HeaderMismatch {
+                height,
+                reason: format!("decoded hash is {}", real_hash),
+            },
+            ChainBreak::PrevHashMismatch {
+                height: height + 1,
+                prev_hash: real_hash,
+                lower_hash: "00".repeat(32),
+            },
+        ]
+    );
+
+    let mut blocks = wallet.blocks.clone();
+    blocks.remove(50);
+    assert_eq!(
+        check_chain_continuity(&blocks),
+        [ChainBreak::Gap {
+            height: height + 1,
+            lower_height: height - 1,
+        }]
+    );
+
+    let mut blocks = wallet.blocks.clone();
+    blocks[50].ecb = vec![0xff; 4];
+    assert!(blocks[50].compact_block().is_err());
+    assert!(matches!(
+        &check_chain_continuity(&blocks)[..],
+        [ChainBreak::Undecodable { height: h, .. }] if *h == height
+    ));
+}
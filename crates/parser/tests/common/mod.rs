@@ -0,0 +1,99 @@
+//! Fixtures shared by the integration tests.
+
+#![allow(dead_code)]
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use orchard_old::keys::{FullViewingKey, Scope};
+use sapling_crypto::{
+    Nullifier, PaymentAddress, Rseed, value::NoteValue, zip32::ExtendedFullViewingKey,
+};
+use zcash_primitives::{consensus::BlockHeight, memo::Memo, transaction::TxId};
+use zecwallet_parser::zwl::{
+    orchard_data::OrchardNoteData,
+    sapling_data::SaplingNoteData,
+    transactions::{OutgoingTxMetadata, Utxo, WalletTx, WitnessCache},
+};
+
+/// A mainnet wallet with 2 transparent, 2 Sapling and 1 Orchard HD keys, and the compact blocks
+/// 2757862 to 2757961 but no transactions.
+pub const WALLET: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../../testvectors/zecwallet-light-wallet.dat"
+);
+
+/// The height the fixtures' transactions are mined at by default.
+pub const HEIGHT: u32 = 2757900;
+
+/// An empty mined transaction with txid `[byte; 32]`.
+pub fn wtx(byte: u8, height: u32) -> WalletTx {
+    WalletTx::new(
+        BlockHeight::from_u32(height),
+        0,
+        &TxId::from_bytes([byte; 32]),
+        false,
+    )
+}
+
+/// A Utxo of `txid` received at [`HEIGHT`], spent by the given transaction and height.
+pub fn utxo(txid: TxId, address: &str, value: u64, spent: Option<(TxId, i32)>) -> Utxo {
+    Utxo {
+        address: address.to_string(),
+        txid,
+        output_index: 0,
+        script: vec![],
+        value,
+        height: HEIGHT as i32,
+        spent_at_height: spent.map(|(_, height)| height),
+        spent: spent.map(|(txid, _)| txid),
+        unconfirmed_spent: None,
+    }
+}
+
+pub fn outgoing(address: &str, value: u64) -> OutgoingTxMetadata {
+    OutgoingTxMetadata {
+        address: address.to_string(),
+        value,
+        memo: Memo::Empty,
+    }
+}
+
+/// An unspent Sapling note of `extfvk` at `address`, with nullifier `[byte; 32]`.
+pub fn sapling_note(
+    extfvk: &ExtendedFullViewingKey,
+    address: PaymentAddress,
+    value: u64,
+    byte: u8,
+) -> SaplingNoteData {
+    SaplingNoteData {
+        extfvk: extfvk.clone(),
+        diversifier: *address.diversifier(),
+        note: address.create_note(NoteValue::from_raw(value), Rseed::AfterZip212([byte; 32])),
+        witnesses: WitnessCache::empty(),
+        nullifier: Nullifier([byte; 32]),
+        spent: None,
+        unconfirmed_spent: None,
+        memo: None,
+        is_change: false,
+        have_spending_key: true,
+    }
+}
+
+/// An unspent Orchard note of `fvk` at its address of index `index`. Its key is private to the
+/// parser, so the note is read from its serialized form.
+pub fn orchard_note(fvk: &FullViewingKey, index: u64, value: u64) -> OrchardNoteData {
+    let mut bytes = vec![];
+    bytes.write_u64::<LittleEndian>(22).unwrap();
+    fvk.write(&mut bytes).unwrap();
+    bytes.extend_from_slice(
+        &fvk.address_at(index, Scope::External)
+            .to_raw_address_bytes(),
+    );
+    bytes.write_u64::<LittleEndian>(value).unwrap();
+    // rho and rseed
+    bytes.extend_from_slice(&[0u8; 32]);
+    bytes.extend_from_slice(&[7u8; 32]);
+    // No witness position, spends or memo, not change, spendable.
+    bytes.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+
+    OrchardNoteData::read(&bytes[..]).unwrap()
+}
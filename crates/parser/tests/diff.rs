@@ -0,0 +1,234 @@
+mod common;
+
+use zcash_primitives::{consensus::Network, transaction::TxId};
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{
+        diff::{DiffKind, FieldChange, NoteChange, SpendStatus},
+        import::ImportedKey,
+        scan::Pool,
+    },
+};
+
+use common::{HEIGHT, WALLET, orchard_note, outgoing, sapling_note, utxo, wtx};
+
+#[test]
+fn same_wallet_has_no_changes() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let other = WalletReader::read(WALLET).unwrap();
+
+    assert!(wallet.diff(&other).is_empty());
+}
+
+#[test]
+fn locked_copy_only_shows_public_changes() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let mut other = WalletReader::read(WALLET).unwrap();
+
+    // The example private key of the Bitcoin wiki.
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    let key = ImportedKey::decode(&Network::MainNetwork, wif).unwrap();
+    let address = other.import_key(key, None).unwrap();
+    other.birthday += 1;
+
+    // Lock the copy: the secrets are gone, but the keys can still spend once unlocked.
+    other.keys.encrypted = true;
+    other.keys.seed = [0u8; 32];
+    for zkey in &mut other.keys.zkeys {
+        zkey.extsk = None;
+        zkey.enc_key = Some(vec![0u8; 185]);
+    }
+
+    let diff = wallet.diff(&other);
+    let fields: Vec<_> = diff.fields.iter().map(|f| f.field).collect();
+    assert_eq!(fields, ["birthday", "encrypted"]);
+    assert_eq!(diff.keys.len(), 1);
+    assert_eq!(diff.keys[0].kind, DiffKind::Added);
+    assert_eq!(diff.keys[0].pool, Pool::Transparent);
+    assert_eq!(diff.keys[0].address, address);
+
+    let reverse = other.diff(&wallet);
+    assert_eq!(reverse.keys[0].kind, DiffKind::Removed);
+}
+
+#[test]
+fn transactions_and_spends_are_compared() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+    let taddress = wallet.keys.tkeys[0].address.clone();
+
+    // A transaction receiving a note in each pool, one mined again higher in the other wallet,
+    // and one the other wallet lost.
+    let mut received = wtx(1, HEIGHT);
+    received
+        .sapling_notes
+        .push(sapling_note(&extfvk, extfvk.default_address().1, 5_000, 1));
+    received.orchard_notes.push(orchard_note(&fvk, 0, 4_000));
+    received
+        .utxos
+        .push(utxo(received.txid, &taddress, 3_000, None));
+    let (reorged, lost) = (wtx(2, HEIGHT + 1), wtx(3, HEIGHT + 2));
+    let txids = (received.txid, reorged.txid, lost.txid);
+    for wtx in [received, reorged, lost] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    // The other wallet found a transaction spending the three notes, the Utxo's spend not mined
+    // yet.
+    let mut other = wallet.clone();
+    let mut spending = wtx(4, HEIGHT + 3);
+    spending
+        .outgoing_metadata
+        .push(outgoing("zs1recipient", 11_000));
+    let spent = Some((spending.txid, HEIGHT + 3));
+    let received = other.transactions.current.get_mut(&txids.0).unwrap();
+    received.sapling_notes[0].spent = spent;
+    received.orchard_notes[0].spent = spent;
+    received.utxos[0].unconfirmed_spent = spent;
+    let reorged = other.transactions.current.get_mut(&txids.1).unwrap();
+    reorged.block = (HEIGHT + 4).into();
+    reorged.unconfirmed = true;
+    other.transactions.current.remove(&txids.2);
+    other
+        .transactions
+        .current
+        .insert(spending.txid, spending.clone());
+
+    let diff = wallet.diff(&other);
+    let transactions: Vec<_> = diff
+        .transactions
+        .iter()
+        .map(|tx| (tx.kind, tx.txid, tx.height))
+        .collect();
+    assert_eq!(
+        transactions,
+        [
+            (DiffKind::Removed, txids.2, u64::from(HEIGHT + 2)),
+            (DiffKind::Added, spending.txid, u64::from(HEIGHT + 3)),
+            (DiffKind::Changed, txids.1, u64::from(HEIGHT + 4)),
+        ]
+    );
+    let change = |field, old: &str, new: &str| FieldChange {
+        field,
+        old: Some(old.to_string()),
+        new: Some(new.to_string()),
+    };
+    assert_eq!(
+        diff.transactions[2].fields,
+        [
+            change(
+                "block",
+                &(HEIGHT + 1).to_string(),
+                &(HEIGHT + 4).to_string()
+            ),
+            change("unconfirmed", "false", "true"),
+        ]
+    );
+
+    let note = |pool, value, new| NoteChange {
+        txid: txids.0,
+        pool,
+        index: 0,
+        value,
+        old: SpendStatus::Unspent,
+        new,
+    };
+    assert_eq!(
+        diff.notes,
+        [
+            note(
+                Pool::Sapling,
+                5_000,
+                SpendStatus::Spent(spending.txid, Some(HEIGHT + 3))
+            ),
+            note(
+                Pool::Orchard,
+                4_000,
+                SpendStatus::Spent(spending.txid, Some(HEIGHT + 3))
+            ),
+            note(
+                Pool::Transparent,
+                3_000,
+                SpendStatus::Pending(spending.txid, HEIGHT + 3)
+            ),
+        ]
+    );
+
+    let reverse = other.diff(&wallet);
+    let kinds: Vec<_> = reverse.transactions.iter().map(|tx| tx.kind).collect();
+    assert_eq!(
+        kinds,
+        [DiffKind::Changed, DiffKind::Added, DiffKind::Removed]
+    );
+    assert_eq!(reverse.notes[0].old, diff.notes[0].new);
+    assert_eq!(reverse.notes[0].new, SpendStatus::Unspent);
+}
+
+#[test]
+fn orchard_notes_of_a_transaction_are_compared_one_by_one() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+    let (spend_a, spend_b) = (TxId::from_bytes([2; 32]), TxId::from_bytes([3; 32]));
+
+    // Two notes received together, the second one already spent.
+    let mut received = wtx(1, HEIGHT);
+    received
+        .orchard_notes
+        .extend([orchard_note(&fvk, 0, 1_000), orchard_note(&fvk, 1, 2_000)]);
+    received.orchard_notes[1].spent = Some((spend_b, HEIGHT + 1));
+    let txid = received.txid;
+    wallet.transactions.current.insert(txid, received);
+    let bytes = WalletWriter::to_bytes(&wallet).unwrap();
+    let wallet = WalletReader::read_from_reader(&bytes[..]).unwrap();
+
+    let mut other = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    other
+        .transactions
+        .current
+        .get_mut(&txid)
+        .unwrap()
+        .orchard_notes[0]
+        .spent = Some((spend_a, HEIGHT + 2));
+
+    let diff = wallet.diff(&other);
+    assert_eq!(
+        diff.notes,
+        [NoteChange {
+            txid,
+            pool: Pool::Orchard,
+            index: 0,
+            value: 1_000,
+            old: SpendStatus::Unspent,
+            new: SpendStatus::Spent(spend_a, Some(HEIGHT + 2)),
+        }]
+    );
+}
+
+#[test]
+fn utxo_spends_without_a_height_are_compared() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let taddress = wallet.keys.tkeys[0].address.clone();
+    let mut received = wtx(1, HEIGHT);
+    received
+        .utxos
+        .push(utxo(received.txid, &taddress, 3_000, None));
+    let txid = received.txid;
+    wallet.transactions.current.insert(txid, received);
+
+    // Old wallets can have spent Utxos without the height of the spend.
+    let mut other = wallet.clone();
+    let spender = TxId::from_bytes([2; 32]);
+    let utxo = &mut other.transactions.current.get_mut(&txid).unwrap().utxos[0];
+    utxo.spent = Some(spender);
+    utxo.spent_at_height = Some(-1);
+
+    let diff = wallet.diff(&other);
+    assert_eq!(diff.notes.len(), 1);
+    assert_eq!(diff.notes[0].new, SpendStatus::Spent(spender, None));
+    assert_eq!(
+        diff.notes[0].new.to_string(),
+        format!("spent in {}", spender)
+    );
+}
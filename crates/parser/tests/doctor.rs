@@ -0,0 +1,162 @@
+mod common;
+
+use zcash_primitives::{consensus::Network, transaction::TxId};
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{
+        ZwlWallet,
+        data::ChainType,
+        doctor::{Finding, Rule, Severity, builtin_rules},
+        import::ImportedKey,
+        keys::sapling::WalletZKey,
+    },
+};
+
+use common::{HEIGHT, WALLET, sapling_note, utxo, wtx};
+
+#[test]
+fn broken_wallet_trips_the_builtin_rules() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    assert!(wallet.doctor().findings.is_empty());
+
+    wallet.keys.encrypted = true;
+    let zkey = wallet.keys.zkeys[0].clone();
+    wallet.keys.zkeys.push(zkey);
+    wallet.transactions.last_txid = Some(TxId::from_bytes([9; 32]));
+    let mut wtx = wtx(1, HEIGHT);
+    let spent = Some((TxId::from_bytes([2; 32]), 2757800));
+    let address = wallet.keys.tkeys[0].address.clone();
+    wtx.utxos.push(utxo(wtx.txid, &address, 1000, spent));
+    wallet.transactions.current.insert(wtx.txid, wtx);
+    wallet.chain_name = ChainType::Unknown;
+
+    let report = wallet.doctor();
+    let mut rules: Vec<_> = report.findings.iter().map(|f| f.rule).collect();
+    rules.sort();
+    assert_eq!(
+        rules,
+        [
+            "duplicate-key",
+            "encrypted-seed-in-clear",
+            "last-txid",
+            "unknown-chain",
+            "utxo-spent-before-mined",
+        ]
+    );
+    assert_eq!(report.max_severity(), Some(Severity::Error));
+    assert_eq!(report.findings[0].severity, Severity::Error);
+    assert_eq!(report.count(Severity::Warning), 2);
+}
+
+#[test]
+fn locked_keys_need_their_encrypted_key() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    // The example private key of the Bitcoin wiki.
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    let key = ImportedKey::decode(&Network::MainNetwork, wif).unwrap();
+    let address = wallet.import_key(key, None).unwrap();
+
+    // Locked with its encrypted key, the key can be unlocked.
+    let tkey = wallet.keys.tkeys.last_mut().unwrap();
+    tkey.locked = true;
+    tkey.enc_key = Some(vec![0; 48]);
+    tkey.nonce = Some(vec![0; 24]);
+    assert!(wallet.doctor().findings.is_empty());
+
+    let tkey = wallet.keys.tkeys.last_mut().unwrap();
+    tkey.nonce = None;
+    let report = wallet.doctor();
+    assert_eq!(report.findings.len(), 1);
+    let finding = &report.findings[0];
+    assert_eq!(finding.rule, "locked-key-without-enc-key");
+    assert_eq!(finding.severity, Severity::Error);
+    assert_eq!(finding.subject.as_deref(), Some(address.as_str()));
+    assert!(
+        finding
+            .message
+            .ends_with("no nonce, so it can never be unlocked")
+    );
+
+    let tkey = wallet.keys.tkeys.last_mut().unwrap();
+    tkey.enc_key = None;
+    let report = wallet.doctor();
+    assert!(
+        report.findings[0]
+            .message
+            .ends_with("no encrypted key, so it can never be unlocked")
+    );
+
+    // HD keys are derived from the seed instead.
+    wallet.keys.tkeys.pop();
+    wallet.keys.tkeys[0].locked = true;
+    assert!(wallet.doctor().findings.is_empty());
+}
+
+#[test]
+fn notes_need_the_key_that_received_them() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let own = wallet.keys.zkeys[0].extfvk.clone();
+    let foreign = WalletZKey::new_hdkey(&[1; 64], 133, 0).extfvk;
+
+    let mut wtx = wtx(1, HEIGHT);
+    wtx.sapling_notes
+        .push(sapling_note(&own, own.default_address().1, 1_000, 1));
+    wtx.sapling_notes.push(sapling_note(
+        &foreign,
+        foreign.default_address().1,
+        2_000,
+        2,
+    ));
+    let txid = wtx.txid;
+    wallet.transactions.last_txid = Some(txid);
+    wallet.transactions.current.insert(txid, wtx);
+
+    let report = wallet.doctor();
+    assert_eq!(report.findings.len(), 1);
+    let finding = &report.findings[0];
+    assert_eq!(finding.rule, "note-without-key");
+    assert_eq!(finding.severity, Severity::Warning);
+    assert_eq!(finding.subject, Some(format!("Sapling {} #1", txid)));
+}
+
+struct NoBlocks;
+
+impl Rule for NoBlocks {
+    fn id(&self) -> &'static str {
+        "no-blocks"
+    }
+
+    fn description(&self) -> &'static str {
+        "The wallet has cached blocks."
+    }
+
+    fn check(&self, wallet: &ZwlWallet) -> Vec<Finding> {
+        if !wallet.blocks.is_empty() {
+            return vec![];
+        }
+        vec![Finding {
+            rule: self.id(),
+            severity: Severity::Info,
+            subject: None,
+            message: "The wallet has no cached blocks".to_string(),
+            fix: "Sync the wallet".to_string(),
+        }]
+    }
+}
+
+#[test]
+fn custom_rules_run_alongside_the_builtin_ones() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let mut rules = builtin_rules();
+    rules.push(Box::new(NoBlocks));
+    assert!(wallet.run_rules(&rules).findings.is_empty());
+
+    wallet.blocks.clear();
+    wallet.transactions.last_txid = Some(TxId::from_bytes([9; 32]));
+    let report = wallet.run_rules(&rules);
+    let rules: Vec<_> = report.findings.iter().map(|f| f.rule).collect();
+    assert_eq!(rules, ["last-txid", "no-blocks"]);
+    assert_eq!(report.max_severity(), Some(Severity::Warning));
+    assert_eq!(report.count(Severity::Info), 1);
+    assert_eq!("warning".parse::<Severity>().unwrap(), Severity::Warning);
+}
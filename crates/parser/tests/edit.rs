@@ -0,0 +1,45 @@
+mod common;
+
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::data::{ChainType, MemoDownloadOption},
+};
+
+use common::WALLET;
+
+#[test]
+fn edited_settings_are_written() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    let options = &mut wallet.wallet_options;
+    options.set_download_memos("none".parse().unwrap());
+    options.set_spam_threshold(-1).unwrap();
+    wallet.set_birthday(2_752_100).unwrap();
+
+    let bytes = WalletWriter::to_bytes(&wallet).unwrap();
+    let read = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    assert_eq!(
+        read.wallet_options.download_memos(),
+        MemoDownloadOption::NoMemos
+    );
+    assert_eq!(read.wallet_options.spam_threshold(), -1);
+    assert_eq!(read.birthday, 2_752_100);
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    assert!("some".parse::<MemoDownloadOption>().is_err());
+    assert!(wallet.wallet_options.set_spam_threshold(-2).is_err());
+    // Below the Sapling activation, and above the latest synced block.
+    assert!(wallet.set_birthday(100).is_err());
+    assert!(wallet.set_birthday(3_000_000).is_err());
+    assert!(wallet.set_chain_name(ChainType::Testnet, false).is_err());
+    assert!(wallet.set_chain_name(ChainType::Unknown, true).is_err());
+    assert_eq!(wallet.chain_name.to_string(), "Mainnet");
+
+    wallet.set_chain_name(ChainType::Testnet, true).unwrap();
+    assert_eq!(wallet.chain_name.to_string(), "Testnet");
+}
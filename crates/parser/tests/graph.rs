@@ -0,0 +1,167 @@
+mod common;
+
+use zcash_primitives::transaction::TxId;
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{
+        graph::{EdgeKind, NodeKind, UNSPENT},
+        scan::Pool,
+    },
+};
+
+use common::{HEIGHT, WALLET, orchard_note, outgoing, sapling_note, utxo, wtx};
+
+#[test]
+fn notes_link_creating_and_spending_transactions() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    // A Utxo spent by a transaction of the wallet, one spent by an unknown transaction, one
+    // unspent, and a payment.
+    let (mut received, mut spending) = (wtx(1, HEIGHT), wtx(2, 2757910));
+    let unknown = TxId::from_bytes([3; 32]);
+    for (value, spender) in [(1_000, spending.txid), (2_000, unknown)] {
+        let spent = Some((spender, 2757910));
+        received
+            .utxos
+            .push(utxo(received.txid, "t1address", value, spent));
+    }
+    spending
+        .utxos
+        .push(utxo(spending.txid, "t1address", 300, None));
+    spending
+        .outgoing_metadata
+        .push(outgoing("zs1recipient", 600));
+    let txids = (received.txid, spending.txid);
+    for wtx in [received, spending] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    let graph = wallet.note_graph();
+    let ids: Vec<_> = graph.nodes.iter().map(|node| node.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        [
+            txids.0.to_string().as_str(),
+            txids.1.to_string().as_str(),
+            unknown.to_string().as_str(),
+            "zs1recipient",
+            UNSPENT,
+        ]
+    );
+    assert!(matches!(
+        graph.nodes[2].kind,
+        NodeKind::Transaction {
+            height: Some(2757910),
+            known: false,
+            ..
+        }
+    ));
+
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|edge| (edge.source.clone(), edge.target.clone(), edge.value))
+        .collect();
+    assert_eq!(
+        edges,
+        [
+            (txids.0.to_string(), txids.1.to_string(), 1_000),
+            (txids.0.to_string(), unknown.to_string(), 2_000),
+            (txids.1.to_string(), UNSPENT.to_string(), 300),
+            (txids.1.to_string(), "zs1recipient".to_string(), 600),
+        ]
+    );
+    assert_eq!(
+        graph.edges[0].kind,
+        EdgeKind::Note {
+            pool: Pool::Transparent,
+            is_change: false,
+            pending: false
+        }
+    );
+    assert_eq!(graph.edges[3].kind, EdgeKind::Outgoing);
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph notes {"));
+    assert!(dot.contains(&format!("\"{}\" -> \"zs1recipient\"", txids.1)));
+}
+
+#[test]
+fn shielded_notes_and_change_are_styled() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+
+    // A Sapling note spent by a pending transaction, whose Orchard change is unspent.
+    let (mut received, mut spending) = (wtx(1, HEIGHT), wtx(2, 2757910));
+    let mut sapling = sapling_note(&extfvk, extfvk.default_address().1, 5_000, 1);
+    sapling.unconfirmed_spent = Some((spending.txid, 2757910));
+    received.sapling_notes.push(sapling);
+    let mut change = orchard_note(&fvk, 0, 4_000);
+    change.is_change = true;
+    spending.orchard_notes.push(change);
+    let txids = (received.txid, spending.txid);
+    for wtx in [received, spending] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    let graph = wallet.note_graph();
+    assert_eq!(graph.edges.len(), 2);
+    assert_eq!(
+        (&graph.edges[0].source, &graph.edges[0].target),
+        (&txids.0.to_string(), &txids.1.to_string())
+    );
+    assert_eq!(
+        graph.edges[0].kind,
+        EdgeKind::Note {
+            pool: Pool::Sapling,
+            is_change: false,
+            pending: true
+        }
+    );
+    assert_eq!(
+        (&graph.edges[1].source, graph.edges[1].target.as_str()),
+        (&txids.1.to_string(), UNSPENT)
+    );
+    assert_eq!(
+        graph.edges[1].kind,
+        EdgeKind::Note {
+            pool: Pool::Orchard,
+            is_change: true,
+            pending: false
+        }
+    );
+
+    let dot = graph.to_dot();
+    assert!(dot.contains("label=\"Sapling 5000\", color=blue, style=dotted"));
+    assert!(dot.contains("label=\"Orchard 4000\", color=darkgreen, style=dashed"));
+}
+
+#[test]
+fn spenders_without_a_height_are_kept() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    // Old wallets can have spent Utxos without the height of the spend.
+    let mut received = wtx(1, HEIGHT);
+    let unknown = TxId::from_bytes([3; 32]);
+    let mut spent = utxo(received.txid, "t1address", 1_000, None);
+    spent.spent = Some(unknown);
+    received.utxos.push(spent);
+    wallet.transactions.current.insert(received.txid, received);
+
+    let graph = wallet.note_graph();
+    assert_eq!(graph.nodes[1].id, unknown.to_string());
+    assert!(matches!(
+        graph.nodes[1].kind,
+        NodeKind::Transaction {
+            height: None,
+            known: false,
+            ..
+        }
+    ));
+    assert_eq!(graph.edges[0].target, unknown.to_string());
+    assert!(graph.to_dot().contains(&format!(
+        "label=\"{}…\\nunknown\"",
+        &unknown.to_string()[..16]
+    )));
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::io::Write;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use zcash_encoding::Vector;
+use zcash_primitives::transaction::{TxId, builder::DEFAULT_TX_EXPIRY_DELTA};
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{history::TxStatus, transactions::WalletTx, wallet_txns::WalletTxns},
+};
+
+use common::{HEIGHT, WALLET, wtx};
+
+/// A version 20 transactions section, with its separate mempool section.
+fn legacy_section(current: &[WalletTx], mempool: &[WalletTx]) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.write_u64::<LittleEndian>(20).unwrap();
+    for txs in [current, mempool] {
+        Vector::write(&mut bytes, txs, |w, wtx| {
+            w.write_all(wtx.txid.as_ref())?;
+            wtx.write(w)
+        })
+        .unwrap();
+    }
+    bytes
+}
+
+#[test]
+fn legacy_mempool_is_written_back_as_unconfirmed_txs() {
+    let mined = wtx(1, HEIGHT);
+    // Legacy files didn't store the unconfirmed flag of mempool transactions.
+    let pending = wtx(2, HEIGHT + 5);
+    let bytes = legacy_section(
+        std::slice::from_ref(&mined),
+        &[pending.clone(), mined.clone()],
+    );
+
+    let txns = WalletTxns::read(&bytes[..]).unwrap();
+    assert_eq!(txns.current.len(), 1);
+    assert_eq!(txns.mempool.len(), 2);
+    assert!(txns.mempool[&pending.txid].unconfirmed);
+    assert_eq!(txns.last_txid, Some(mined.txid));
+
+    // The mempool section is gone, and a transaction in both sections is only written once.
+    let mut written = vec![];
+    txns.write(&mut written).unwrap();
+    let txns = WalletTxns::read(&written[..]).unwrap();
+    assert!(txns.mempool.is_empty());
+    let mut current: Vec<(TxId, bool)> = txns
+        .current
+        .values()
+        .map(|wtx| (wtx.txid, wtx.unconfirmed))
+        .collect();
+    current.sort();
+    assert_eq!(current, [(mined.txid, false), (pending.txid, true)]);
+}
+
+#[test]
+fn unconfirmed_txs_expire() {
+    let delta = u64::from(DEFAULT_TX_EXPIRY_DELTA);
+    let mut sent = wtx(1, HEIGHT);
+    let latest = u64::from(HEIGHT) + delta;
+    assert!(!sent.is_expired(latest));
+
+    sent.unconfirmed = true;
+    assert!(!sent.is_expired(latest));
+    assert!(sent.is_expired(latest + 1));
+}
+
+#[test]
+fn history_flags_pending_and_expired_txs() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let tip = wallet.latest_height().unwrap() as u32;
+    let delta = DEFAULT_TX_EXPIRY_DELTA;
+
+    let mined = wtx(1, tip - 50);
+    let mut pending = wtx(2, tip - delta);
+    pending.unconfirmed = true;
+    let mut expired = wtx(3, tip - delta - 1);
+    expired.unconfirmed = true;
+    wallet
+        .transactions
+        .current
+        .insert(mined.txid, mined.clone());
+    wallet
+        .transactions
+        .current
+        .insert(pending.txid, pending.clone());
+    wallet
+        .transactions
+        .mempool
+        .insert(expired.txid, expired.clone());
+
+    let history: Vec<_> = wallet
+        .history()
+        .into_iter()
+        .map(|e| (e.txid, e.status, e.from_mempool))
+        .collect();
+    assert_eq!(
+        history,
+        [
+            (mined.txid, TxStatus::Confirmed, false),
+            (expired.txid, TxStatus::Expired, true),
+            (pending.txid, TxStatus::Pending, false),
+        ]
+    );
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use sha2::{Digest, Sha256};
+use xsalsa20poly1305::{KeyInit, Nonce, XSalsa20Poly1305, aead::Aead};
+use zcash_keys::encoding::encode_extended_spending_key;
+use zcash_primitives::consensus::{MainNetwork, Network, NetworkConstants};
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{
+        import::ImportedKey,
+        keys::{sapling::WalletZKey, transparent::WalletTKeyType},
+    },
+};
+
+use common::WALLET;
+
+fn sapling_spending_key(seed: u8) -> String {
+    let extsk = WalletZKey::new_hdkey(&[seed; 64], 133, 0).extsk.unwrap();
+    encode_extended_spending_key(MainNetwork.hrp_sapling_extended_spending_key(), &extsk)
+}
+
+#[test]
+fn imported_keys_are_written_and_duplicates_rejected() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    let key = ImportedKey::decode(&Network::MainNetwork, &sapling_spending_key(1)).unwrap();
+    let address = wallet.import_key(key.clone(), None).unwrap();
+    assert!(address.starts_with("zs1"));
+    assert!(wallet.import_key(key, None).is_err());
+
+    // An HD key of the wallet, as an Orchard spending key.
+    let sk = wallet.keys.okeys[0].sk.unwrap();
+    let key = ImportedKey::decode(&Network::MainNetwork, &hex::encode(sk.to_bytes())).unwrap();
+    assert!(wallet.import_key(key, None).is_err());
+
+    // The example private key of the Bitcoin wiki.
+    let wif = "5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ";
+    let key = ImportedKey::decode(&Network::MainNetwork, wif).unwrap();
+    let taddress = wallet.import_key(key, None).unwrap();
+    assert!(taddress.starts_with("t1"));
+
+    assert!(ImportedKey::decode(&Network::MainNetwork, "not a key").is_err());
+    // A WIF prefix and the compressed flag, with no key in between.
+    let short = bs58::encode([0x80, 0x01]).with_check().into_string();
+    assert!(ImportedKey::decode(&Network::MainNetwork, &short).is_err());
+
+    let bytes = WalletWriter::to_bytes(&wallet).unwrap();
+    let read = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    assert_eq!(read.keys.zkeys.len(), 3);
+    assert_eq!(read.keys.tkeys.len(), 3);
+    assert_eq!(read.keys.okeys.len(), 1);
+    let tkey = read.keys.tkeys.last().unwrap();
+    assert_eq!(tkey.address, taddress);
+    assert_eq!(tkey.keytype, WalletTKeyType::ImportedKey);
+}
+
+#[test]
+fn imported_keys_are_encrypted_in_encrypted_wallets() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+
+    // Encrypt and lock the wallet like ZecWallet Lite does.
+    let cipher = XSalsa20Poly1305::new(&Sha256::digest(Sha256::digest(b"password")));
+    let nonce = [7u8; 24];
+    let enc_seed = cipher
+        .encrypt(Nonce::from_slice(&nonce), &wallet.keys.seed[..])
+        .unwrap();
+    wallet.keys.encrypted = true;
+    wallet.keys.enc_seed = enc_seed.try_into().unwrap();
+    wallet.keys.nonce = nonce.to_vec();
+    wallet.keys.seed = [0u8; 32];
+
+    let encoded = sapling_spending_key(2);
+    let key = ImportedKey::decode(&Network::MainNetwork, &encoded).unwrap();
+    assert!(wallet.import_key(key.clone(), None).is_err());
+    assert!(wallet.import_key(key.clone(), Some("wrong")).is_err());
+    wallet.import_key(key, Some("password")).unwrap();
+
+    let zkey = wallet.keys.zkeys.last().unwrap();
+    assert!(zkey.extsk.is_none());
+    let decrypted = cipher
+        .decrypt(
+            Nonce::from_slice(zkey.nonce.as_ref().unwrap()),
+            &zkey.enc_key.as_ref().unwrap()[..],
+        )
+        .unwrap();
+    let ImportedKey::SaplingSpendingKey(extsk) =
+        ImportedKey::decode(&Network::MainNetwork, &encoded).unwrap()
+    else {
+        unreachable!()
+    };
+    assert_eq!(decrypted, extsk.to_bytes());
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use bip0039::{English, Mnemonic};
+use sapling_crypto::{CommitmentTree, IncrementalWitness, Node};
+use zcash_primitives::transaction::TxId;
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{
+        ZwlWallet, diff::SpendStatus, keys::sapling::WalletZKeyType, merge::MergeConflict,
+        scan::Pool, transactions::WalletTx,
+    },
+};
+
+use common::{HEIGHT, WALLET, orchard_note, sapling_note, utxo, wtx};
+
+/// A transaction with txid `[byte; 32]` and a Utxo spent by `spent`.
+fn tx_with_utxo(byte: u8, spent: Option<TxId>) -> WalletTx {
+    let mut wtx = wtx(byte, HEIGHT);
+    let spent = spent.map(|txid| (txid, 2757910));
+    wtx.utxos.push(utxo(wtx.txid, "t1address", 1000, spent));
+    wtx
+}
+
+#[test]
+fn wallets_with_other_seeds_keep_their_keys_as_imported() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let phrase = <Mnemonic<English>>::from_entropy([1u8; 32])
+        .unwrap()
+        .phrase()
+        .to_string();
+    let other = ZwlWallet::from_seed_phrase(&phrase, 1, wallet.chain_name, 2000000, None).unwrap();
+
+    let report = wallet.merge(&other).unwrap();
+    assert_eq!(report.keys_added.len(), 3);
+    assert_eq!(
+        report.conflicts,
+        [
+            MergeConflict::Seed { hd_keys: 3 },
+            MergeConflict::WalletOption {
+                option: "spam_threshold",
+                kept: "50".to_string(),
+                other: "-1".to_string(),
+            },
+        ]
+    );
+    assert_eq!(report.birthday, Some((2752032, 2000000)));
+    assert_eq!(wallet.keys.zkeys.len(), 3);
+    let zkey = wallet.keys.zkeys.last().unwrap();
+    assert_eq!(zkey.keytype, WalletZKeyType::ImportedSpendingKey);
+    assert_eq!(zkey.hdkey_num, None);
+
+    // Merging again adds nothing.
+    let report = wallet.merge(&other).unwrap();
+    assert!(report.keys_added.is_empty());
+}
+
+#[test]
+fn transactions_are_combined_and_spend_conflicts_reported() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let mut other = WalletReader::read(WALLET).unwrap();
+
+    let (shared, conflicting, added) = (
+        TxId::from_bytes([1; 32]),
+        TxId::from_bytes([2; 32]),
+        TxId::from_bytes([3; 32]),
+    );
+    let (spend_a, spend_b) = (TxId::from_bytes([4; 32]), TxId::from_bytes([5; 32]));
+    let txns = &mut wallet.transactions.current;
+    txns.insert(shared, tx_with_utxo(1, None));
+    txns.insert(conflicting, tx_with_utxo(2, Some(spend_a)));
+    let txns = &mut other.transactions.current;
+    txns.insert(shared, tx_with_utxo(1, Some(spend_a)));
+    txns.insert(conflicting, tx_with_utxo(2, Some(spend_b)));
+    txns.insert(added, tx_with_utxo(3, None));
+
+    let report = wallet.merge(&other).unwrap();
+    assert_eq!(report.txs_added, [added]);
+    assert_eq!(report.notes_added, 1);
+    assert_eq!(report.spends_updated, 1);
+    assert_eq!(
+        wallet.transactions.current[&shared].utxos[0].spent,
+        Some(spend_a)
+    );
+    assert!(matches!(
+        report.conflicts[..],
+        [MergeConflict::Spend {
+            txid,
+            kept: SpendStatus::Spent(kept, _),
+            other: SpendStatus::Spent(theirs, _),
+            ..
+        }] if txid == conflicting && kept == spend_a && theirs == spend_b
+    ));
+}
+
+#[test]
+fn orchard_notes_of_a_transaction_are_told_apart_after_a_reload() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let mut other = WalletReader::read(WALLET).unwrap();
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+    let spend = TxId::from_bytes([4; 32]);
+
+    let mut received = wtx(1, HEIGHT);
+    received
+        .orchard_notes
+        .extend([orchard_note(&fvk, 0, 1_000), orchard_note(&fvk, 1, 2_000)]);
+    let txid = received.txid;
+    wallet.transactions.current.insert(txid, received.clone());
+    received.orchard_notes[1].spent = Some((spend, 2757910));
+    other.transactions.current.insert(txid, received);
+
+    let reload = |wallet: &ZwlWallet| {
+        WalletReader::read_from_reader(&WalletWriter::to_bytes(wallet).unwrap()[..]).unwrap()
+    };
+    let (mut wallet, other) = (reload(&wallet), reload(&other));
+
+    let report = wallet.merge(&other).unwrap();
+    assert_eq!(report.notes_added, 0);
+    assert_eq!(report.spends_updated, 1);
+    let notes = &wallet.transactions.current[&txid].orchard_notes;
+    assert_eq!(notes.len(), 2);
+    assert_eq!(notes[0].spent, None);
+    assert_eq!(notes[1].spent, Some((spend, 2757910)));
+}
+
+#[test]
+fn witnesses_synced_to_another_height_are_dropped() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let mut other = WalletReader::read(WALLET).unwrap();
+    let extfvk = other.keys.zkeys[0].extfvk.clone();
+
+    // The other wallet is a block behind, with a witnessed note.
+    other.blocks.remove(0);
+    let mut received = wtx(1, HEIGHT);
+    let mut nd = sapling_note(&extfvk, extfvk.default_address().1, 5_000, 1);
+    let mut tree = CommitmentTree::empty();
+    tree.append(Node::from_cmu(&nd.note.cmu())).unwrap();
+    nd.witnesses.witnesses = vec![IncrementalWitness::from_tree(tree)];
+    nd.witnesses.top_height = other.blocks[0].height;
+    received.sapling_notes.push(nd);
+    let txid = received.txid;
+    other.transactions.current.insert(txid, received);
+
+    let report = wallet.merge(&other).unwrap();
+    assert!(matches!(
+        report.conflicts[..],
+        [MergeConflict::Unwitnessed {
+            txid: conflict,
+            pool: Pool::Sapling,
+            index: 0,
+        }] if conflict == txid
+    ));
+    assert!(
+        wallet.transactions.current[&txid].sapling_notes[0]
+            .witnesses
+            .is_empty()
+    );
+}
@@ -0,0 +1,179 @@
+#![cfg(feature = "online")]
+
+mod common;
+
+use incrementalmerkletree::Tree;
+use zcash_client_backend::proto::{
+    compact_formats::CompactBlock,
+    service::{RawTransaction, TreeState},
+};
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId},
+    transaction::{Authorized, TransactionData, TxVersion},
+};
+use zecwallet_parser::{
+    lightwalletd::{
+        client::LightwalletdClient,
+        mock::{MockChain, MockLightwalletd},
+    },
+    reader::WalletReader,
+    zwl::{
+        ZwlWallet,
+        orchard_tree::{decode_orchard_frontier, encode_orchard_frontier},
+    },
+};
+
+use common::WALLET;
+
+/// The test wallet and its cached blocks, lowest first.
+fn fixture() -> (ZwlWallet, Vec<CompactBlock>) {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let blocks = wallet
+        .blocks
+        .iter()
+        .rev()
+        .map(|b| b.compact_block().unwrap())
+        .collect();
+    (wallet, blocks)
+}
+
+async fn connect(chain: MockChain) -> LightwalletdClient {
+    let (addr, _) = MockLightwalletd::new(chain).spawn().await.unwrap();
+    LightwalletdClient::connect(&format!("http://{}", addr))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn verify_against_same_chain() {
+    let (wallet, blocks) = fixture();
+    let tip = blocks.last().unwrap().height;
+    let mut client = connect(MockChain::new(blocks)).await;
+
+    let report = wallet.verify_online(&mut client, None).await.unwrap();
+
+    assert_eq!(report.server_tip, tip);
+    assert_eq!(report.scanned_range, Some((tip - 99, tip)));
+    assert_eq!(report.blocks_scanned, 100);
+    assert_eq!(report.addresses.len(), wallet.keys.tkeys.len());
+    assert!(report.is_consistent());
+}
+
+#[tokio::test]
+async fn verify_reports_reorged_blocks() {
+    let (wallet, mut blocks) = fixture();
+
+    // Fork the chain below the three latest blocks.
+    let len = blocks.len();
+    for i in len - 3..len {
+        blocks[i].hash[0] ^= 1;
+        if i > len - 3 {
+            blocks[i].prev_hash = blocks[i - 1].hash.clone();
+        }
+    }
+    let forked: Vec<u64> = blocks[len - 3..].iter().map(|b| b.height).collect();
+    let mut client = connect(MockChain::new(blocks)).await;
+
+    let report = wallet.verify_online(&mut client, None).await.unwrap();
+
+    let reorged: Vec<u64> = report.reorged_blocks.iter().map(|b| b.height).collect();
+    assert_eq!(reorged, forked);
+    assert!(!report.is_consistent());
+}
+
+#[tokio::test]
+async fn verify_reports_blocks_above_server_tip() {
+    let (wallet, mut blocks) = fixture();
+    let removed: Vec<u64> = blocks.drain(95..).map(|b| b.height).collect();
+    let mut client = connect(MockChain::new(blocks)).await;
+
+    let report = wallet.verify_online(&mut client, None).await.unwrap();
+
+    assert_eq!(report.blocks_above_tip, removed);
+    assert_eq!(report.blocks_scanned, 95);
+    assert!(report.reorged_blocks.is_empty());
+}
+
+#[tokio::test]
+async fn verify_reports_unrecorded_taddress_txs() {
+    let (wallet, blocks) = fixture();
+    let height = blocks[0].height;
+
+    let tx = TransactionData::<Authorized>::from_parts(
+        TxVersion::Zip225,
+        BranchId::Nu5,
+        0,
+        BlockHeight::from_u32(0),
+        None,
+        None,
+        None,
+        None,
+    )
+    .freeze()
+    .unwrap();
+    let mut data = vec![];
+    tx.write(&mut data).unwrap();
+
+    let address = wallet.keys.tkeys[0].address.clone();
+    let mut chain = MockChain::new(blocks);
+    chain
+        .taddress_txs
+        .insert(address.clone(), vec![RawTransaction { data, height }]);
+    let mut client = connect(chain).await;
+
+    let report = wallet.verify_online(&mut client, None).await.unwrap();
+
+    let check = report
+        .addresses
+        .iter()
+        .find(|a| a.address == address)
+        .unwrap();
+    assert_eq!(check.missing().collect::<Vec<_>>(), vec![&tx.txid()]);
+    assert_eq!(check.unexpected().count(), 0);
+    assert!(!report.is_consistent());
+}
+
+#[tokio::test]
+async fn serve_wallet_blocks_and_tree_states() {
+    let (mut wallet, blocks) = fixture();
+    let tip = wallet.blocks[0].height;
+    let tip_root = wallet.orchard_witnesses.as_ref().unwrap().root(0).unwrap();
+
+    // Verify against a tree 50 blocks below the tip, so the mock has to replay the rest.
+    let mut tree = wallet.orchard_witnesses.clone().unwrap();
+    for _ in 0..50 {
+        assert!(tree.rewind());
+    }
+    wallet.verified_tree = Some(TreeState {
+        network: "main".to_string(),
+        height: wallet.blocks[50].height,
+        hash: wallet.blocks[50].hash.clone(),
+        orchard_tree: hex::encode(encode_orchard_frontier(&tree)),
+        ..Default::default()
+    });
+
+    let mut client = connect(MockChain::from_wallet(&wallet).unwrap()).await;
+
+    let info = client.lightd_info().await.unwrap();
+    assert_eq!(info.chain_name, "main");
+    assert_eq!(info.block_height, tip);
+
+    let latest = client.latest_block().await.unwrap();
+    assert_eq!(latest.height, tip);
+
+    let mut stream = client.block_range(tip - 9, tip).await.unwrap();
+    let mut served = vec![];
+    while let Some(block) = stream.message().await.unwrap() {
+        served.push(block);
+    }
+    assert_eq!(served, blocks[90..]);
+
+    let state = client.tree_state(tip).await.unwrap();
+    assert_eq!(state.hash, wallet.blocks[0].hash);
+    assert_eq!(
+        decode_orchard_frontier(&state).unwrap().1,
+        tip_root.to_bytes()
+    );
+
+    assert!(client.tree_state(tip - 51).await.is_err());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use incrementalmerkletree::{Position, Tree};
+use orchard_old::tree::MerkleHashOrchard;
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::orchard_tree::{OrchardPathStatus, verify_orchard_path},
+};
+
+use common::{HEIGHT, WALLET, orchard_note, wtx};
+
+#[test]
+fn stored_tree_is_summarized() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let tree = wallet.orchard_witnesses.as_ref().unwrap();
+
+    let report = wallet.inspect_orchard_tree().unwrap().unwrap();
+    assert_eq!(
+        Some(report.size),
+        tree.current_position().map(|p| u64::from(p) + 1)
+    );
+    assert_eq!(Some(report.root), tree.root(0).map(|r| r.to_bytes()));
+    assert_eq!(report.checkpoints.len(), tree.checkpoints().len());
+    assert_eq!(report.checkpoints.last().map(|c| c.depth), Some(1));
+    assert!(report.notes.is_empty());
+    assert!(report.frontier.is_none());
+}
+
+#[test]
+fn note_paths_are_verified() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let leaf = MerkleHashOrchard::from_bytes(&[1; 32]).unwrap();
+    let tree = wallet.orchard_witnesses.as_mut().unwrap();
+    tree.append(&leaf);
+    let position = tree.witness().unwrap();
+    tree.append(&MerkleHashOrchard::from_bytes(&[2; 32]).unwrap());
+
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+    let mut wtx = wtx(1, HEIGHT);
+    let mut marked = orchard_note(&fvk, 0, 1_000);
+    marked.witness_position = Some(position);
+    let mut unmarked = orchard_note(&fvk, 0, 2_000);
+    unmarked.witness_position = Some(position + 1);
+    wtx.orchard_notes
+        .extend([marked, unmarked, orchard_note(&fvk, 0, 3_000)]);
+    wallet.transactions.current.insert(wtx.txid, wtx);
+
+    let report = wallet.inspect_orchard_tree().unwrap().unwrap();
+    let statuses: Vec<_> = report.notes.iter().map(|n| n.status.clone()).collect();
+    assert_eq!(
+        statuses,
+        [
+            OrchardPathStatus::NoPosition,
+            OrchardPathStatus::Valid,
+            OrchardPathStatus::NotMarked,
+        ]
+    );
+    assert_eq!(report.marked_positions, [u64::from(position)]);
+    assert_eq!(report.problems().count(), 2);
+
+    // The path hashes up to the root, and stops doing so once tampered with.
+    let root = MerkleHashOrchard::from_bytes(&report.root).unwrap();
+    let mut path: Vec<_> = report.notes[1]
+        .path
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|h| MerkleHashOrchard::from_bytes(h).unwrap())
+        .collect();
+    assert!(verify_orchard_path(position, &leaf, &path, &root));
+    assert!(!verify_orchard_path(Position::from(0), &leaf, &path, &root));
+    path[3] = leaf;
+    assert!(!verify_orchard_path(position, &leaf, &path, &root));
+    assert!(!verify_orchard_path(position, &leaf, &path[1..], &root));
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use zcash_keys::encoding::encode_payment_address_p;
+use zcash_primitives::consensus::Network;
+use zecwallet_parser::{reader::WalletReader, zwl::scan::Pool};
+
+use common::WALLET;
+
+#[test]
+fn stored_and_diversified_addresses_are_found() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let network = Network::MainNetwork;
+
+    let tkey = &wallet.keys.tkeys[1];
+    let owner = wallet.find_owner(&tkey.address).unwrap().unwrap();
+    assert_eq!((owner.pool, owner.key), (Pool::Transparent, 1));
+    assert_eq!(owner.index, tkey.hdkey_num.map(u128::from));
+
+    let ua = wallet.keys.okeys[0].unified_address.encode(&network);
+    let owner = wallet.find_owner(&ua).unwrap().unwrap();
+    assert_eq!(
+        (owner.pool, owner.key, owner.index),
+        (Pool::Orchard, 0, Some(0))
+    );
+
+    // A diversified Sapling address that isn't stored in the keys.
+    let dfvk = wallet.keys.zkeys[1]
+        .extfvk
+        .to_diversifiable_full_viewing_key();
+    let mut index = dfvk.default_address().0;
+    index.increment().unwrap();
+    let (index, address) = dfvk.find_address(index).unwrap();
+    let encoded = encode_payment_address_p(&network, &address);
+    let owner = wallet.find_owner(&encoded).unwrap().unwrap();
+    assert_eq!((owner.pool, owner.key), (Pool::Sapling, 1));
+    assert_eq!(owner.index, Some(u128::from(index)));
+    assert!(!owner.internal);
+
+    // Derived from the seed, but not one of the stored keys.
+    let next = wallet.enumerate_addresses(1, None).unwrap();
+    let unstored = next
+        .addresses
+        .iter()
+        .find(|a| a.pool == Pool::Transparent)
+        .unwrap();
+    assert_eq!(wallet.find_owner(&unstored.address).unwrap(), None);
+    assert!(wallet.find_owner("not an address").is_err());
+}
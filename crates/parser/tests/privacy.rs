@@ -0,0 +1,116 @@
+mod common;
+
+use zcash_keys::encoding::{encode_payment_address_p, encode_transparent_address_p};
+use zcash_primitives::{consensus::Network, legacy::TransparentAddress, transaction::TxId};
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{keys::sapling::WalletZKey, privacy::PrivacyIssue},
+};
+
+use common::{HEIGHT, WALLET, outgoing, sapling_note, utxo, wtx};
+
+#[test]
+fn shielding_round_trip_is_reported() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let address = wallet.keys.tkeys[0].address.clone();
+    let zrecipient = encode_payment_address_p(
+        &Network::MainNetwork,
+        &WalletZKey::new_hdkey(&[1; 64], 133, 0).zaddress,
+    );
+    let trecipient = encode_transparent_address_p(
+        &Network::MainNetwork,
+        &TransparentAddress::PublicKeyHash([7; 20]),
+    );
+
+    // Two payments to the same t-address, shielded together, and deshielded to a t-address.
+    let (mut first, mut second) = (wtx(1, 2757900), wtx(2, 2757910));
+    let (mut shielding, mut deshielding) = (wtx(3, 2757920), wtx(4, 2757930));
+    first.utxos.push(utxo(
+        first.txid,
+        &address,
+        1_000_000,
+        Some((shielding.txid, 2757920)),
+    ));
+    second.utxos.push(utxo(
+        second.txid,
+        &address,
+        500_000,
+        Some((shielding.txid, 2757920)),
+    ));
+    shielding.total_transparent_value_spent = 1_500_000;
+    shielding
+        .outgoing_metadata
+        .push(outgoing(&zrecipient, 1_490_000));
+    deshielding.total_sapling_value_spent = 1_490_000;
+    deshielding
+        .outgoing_metadata
+        .push(outgoing(&trecipient, 1_480_000));
+    let txids = [first.txid, second.txid, shielding.txid, deshielding.txid];
+    for wtx in [first, second, shielding, deshielding] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    let report = wallet.privacy_audit();
+    let issues: Vec<_> = report.items.iter().map(|item| &item.issue).collect();
+    assert_eq!(
+        issues,
+        [
+            &PrivacyIssue::AddressReuse {
+                address: address.clone(),
+                utxos: 2
+            },
+            &PrivacyIssue::Shielding {
+                addresses: vec![address],
+                value: 1_500_000
+            },
+            &PrivacyIssue::Deshielding { value: 1_480_000 },
+            &PrivacyIssue::AmountCorrelation {
+                shielded: 1_500_000,
+                deshielded: 1_480_000
+            },
+            &PrivacyIssue::TransparentRecipient {
+                address: trecipient,
+                value: 1_480_000
+            },
+        ]
+    );
+    assert_eq!(report.items[1].txids, txids[..3]);
+    assert_eq!(report.items[3].txids, txids[2..]);
+    assert_eq!(report.txids().len(), 4);
+}
+
+#[test]
+fn unspent_sapling_notes_are_reported() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let zaddress = extfvk.default_address().1;
+
+    // Of the four notes, one is spent and one is being spent.
+    let (mut first, mut second) = (wtx(1, HEIGHT), wtx(2, HEIGHT + 10));
+    let spending = Some((TxId::from_bytes([3; 32]), HEIGHT + 20));
+    let mut spent = sapling_note(&extfvk, zaddress, 1_000, 1);
+    spent.spent = spending;
+    let mut pending = sapling_note(&extfvk, zaddress, 2_000, 2);
+    pending.unconfirmed_spent = spending;
+    first
+        .sapling_notes
+        .extend([spent, sapling_note(&extfvk, zaddress, 3_000, 3)]);
+    second
+        .sapling_notes
+        .extend([pending, sapling_note(&extfvk, zaddress, 4_000, 4)]);
+    let txids = vec![first.txid, second.txid];
+    for wtx in [first, second] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    let report = wallet.privacy_audit();
+    assert_eq!(report.items.len(), 1);
+    assert_eq!(
+        report.items[0].issue,
+        PrivacyIssue::SaplingFunds {
+            notes: 2,
+            value: 7_000
+        }
+    );
+    assert_eq!(report.items[0].txids, txids);
+}
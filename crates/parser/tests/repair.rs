@@ -0,0 +1,111 @@
+mod common;
+
+use zcash_primitives::transaction::{TxId, builder::DEFAULT_TX_EXPIRY_DELTA};
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, repair::StaleReason, scan::Pool, transactions::WalletTx},
+};
+
+use common::{WALLET, orchard_note, sapling_note, utxo, wtx};
+
+fn unconfirmed(byte: u8, height: u32) -> WalletTx {
+    let mut wtx = wtx(byte, height);
+    wtx.unconfirmed = true;
+    wtx
+}
+
+/// A wallet with one of each defect, and a pending spend that must be kept.
+fn damaged_wallet() -> (ZwlWallet, u32) {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let tip = wallet.latest_height().unwrap() as u32;
+    let old = tip - DEFAULT_TX_EXPIRY_DELTA - 1;
+
+    let pending = unconfirmed(10, tip - 1);
+    let expired = unconfirmed(11, old);
+    let expired_in_mempool = unconfirmed(12, old);
+    let unknown = TxId::from_bytes([13; 32]);
+
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+    let taddr = wallet.keys.tkeys[0].address.clone();
+
+    let mut funds = wtx(1, tip - 60);
+    // Spent by a transaction that expired, and is gone.
+    let mut nd = sapling_note(&extfvk, extfvk.default_address().1, 1_000, 1);
+    nd.unconfirmed_spent = Some((TxId::from_bytes([14; 32]), old));
+    funds.sapling_notes.push(nd);
+    // Spent by a recent transaction the wallet doesn't have.
+    let mut nd = orchard_note(&fvk, 0, 2_000);
+    nd.unconfirmed_spent = Some((unknown, tip - 1));
+    funds.orchard_notes.push(nd);
+    // Spent by a pending transaction, which is fine.
+    let mut pending_spend = utxo(funds.txid, &taddr, 3_000, None);
+    pending_spend.unconfirmed_spent = Some((pending.txid, tip - 1));
+    // Spent by an expired legacy mempool transaction.
+    let mut mempool_spend = utxo(funds.txid, &taddr, 4_000, None);
+    mempool_spend.output_index = 1;
+    mempool_spend.unconfirmed_spent = Some((expired_in_mempool.txid, old));
+    funds.utxos.extend([pending_spend, mempool_spend]);
+
+    let txns = &mut wallet.transactions;
+    for wtx in [funds, pending, expired.clone()] {
+        txns.current.insert(wtx.txid, wtx);
+    }
+    txns.mempool
+        .insert(expired_in_mempool.txid, expired_in_mempool);
+    txns.last_txid = Some(expired.txid);
+
+    (wallet, tip)
+}
+
+#[test]
+fn stale_spends_are_found() {
+    let (wallet, tip) = damaged_wallet();
+    let report = wallet.find_stale_spends();
+
+    assert_eq!(report.latest_height, Some(u64::from(tip)));
+    assert_eq!(
+        report.expired_txs,
+        [TxId::from_bytes([11; 32]), TxId::from_bytes([12; 32])]
+    );
+    let stale: Vec<_> = report
+        .stale_spends
+        .iter()
+        .map(|s| (s.pool, s.index, s.value, s.reason))
+        .collect();
+    assert_eq!(
+        stale,
+        [
+            (Pool::Transparent, 1, 4_000, StaleReason::Expired),
+            (Pool::Sapling, 0, 1_000, StaleReason::Expired),
+            (Pool::Orchard, 0, 2_000, StaleReason::UnknownTx),
+        ]
+    );
+    assert_eq!(report.freed_value(), 7_000);
+}
+
+#[test]
+fn repair_clears_the_stale_spends() {
+    let (mut wallet, _) = damaged_wallet();
+    let found = wallet.find_stale_spends();
+    let report = wallet.repair_stale_spends();
+    assert_eq!(report.expired_txs, found.expired_txs);
+    assert_eq!(report.stale_spends.len(), found.stale_spends.len());
+
+    let txns = &wallet.transactions;
+    assert!(!txns.current.contains_key(&TxId::from_bytes([11; 32])));
+    assert!(txns.mempool.is_empty());
+    assert_eq!(txns.last_txid, Some(TxId::from_bytes([10; 32])));
+
+    let funds = &txns.current[&TxId::from_bytes([1; 32])];
+    assert_eq!(funds.sapling_notes[0].unconfirmed_spent, None);
+    assert_eq!(funds.orchard_notes[0].unconfirmed_spent, None);
+    assert_eq!(funds.utxos[1].unconfirmed_spent, None);
+    assert!(funds.utxos[0].unconfirmed_spent.is_some());
+
+    // Nothing is left to repair once written and read back.
+    let bytes = WalletWriter::to_bytes(&wallet).unwrap();
+    let wallet = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    assert!(wallet.find_stale_spends().is_empty());
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use incrementalmerkletree::Tree;
+use sapling_crypto::{CommitmentTree, IncrementalWitness, Node};
+use zcash_client_backend::proto::service::TreeState;
+use zecwallet_parser::{reader::WalletReader, zwl::ZwlWallet};
+
+use common::{WALLET, orchard_note, sapling_note, utxo, wtx};
+
+/// The wallet with a transaction at `tip - 50` whose note, Orchard note and Utxo are spent by a
+/// transaction at `tip - 2`, and a `verified_tree` at the tip.
+fn fixture() -> (ZwlWallet, u32) {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let tip = wallet.latest_height().unwrap() as u32;
+
+    let spending = wtx(2, tip - 2);
+    let spent = Some((spending.txid, tip - 2));
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let fvk = wallet.keys.okeys[0].fvk.clone();
+
+    let mut funds = wtx(1, tip - 50);
+    let mut nd = sapling_note(&extfvk, extfvk.default_address().1, 1_000, 1);
+    nd.spent = spent;
+    // A witness for each of the last 5 blocks.
+    let mut tree = CommitmentTree::empty();
+    tree.append(Node::from_cmu(&nd.note.cmu())).unwrap();
+    nd.witnesses.witnesses = vec![IncrementalWitness::from_tree(tree); 5];
+    nd.witnesses.top_height = u64::from(tip);
+    funds.sapling_notes.push(nd);
+    let mut nd = orchard_note(&fvk, 0, 2_000);
+    nd.spent = spent;
+    funds.orchard_notes.push(nd);
+    let mut pending = utxo(funds.txid, &wallet.keys.tkeys[0].address, 3_000, None);
+    pending.unconfirmed_spent = spent;
+    funds.utxos.push(pending);
+    let address = &wallet.keys.tkeys[1].address;
+    funds.utxos.push(utxo(
+        funds.txid,
+        address,
+        4_000,
+        spent.map(|(txid, h)| (txid, h as i32)),
+    ));
+
+    wallet.transactions.last_txid = Some(spending.txid);
+    for wtx in [funds, spending] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+    wallet.verified_tree = Some(TreeState {
+        height: u64::from(tip),
+        ..Default::default()
+    });
+
+    (wallet, tip)
+}
+
+#[test]
+fn rewinding_drops_the_blocks_and_txs_above() {
+    let (original, tip) = fixture();
+    let height = u64::from(tip - 3);
+
+    let mut wallet = original.clone();
+    let report = wallet.rewind_to(height).unwrap();
+    assert_eq!(
+        (report.from_height, report.to_height),
+        (u64::from(tip), height)
+    );
+    assert_eq!(report.blocks_removed, 3);
+    assert_eq!(report.removed_txs.len(), 1);
+    assert_eq!(report.unspent, 3);
+    assert_eq!(report.cleared_verified_tree, Some(u64::from(tip)));
+    assert!(wallet.verified_tree.is_none());
+
+    assert_eq!(wallet.latest_height(), Some(height));
+    assert_eq!(wallet.blocks.len(), original.blocks.len() - 3);
+    assert_eq!(wallet.blocks[0].hash, original.blocks[3].hash);
+
+    // One Orchard checkpoint per block.
+    let tree = wallet.orchard_witnesses.as_ref().unwrap();
+    let mut expected = original.orchard_witnesses.clone().unwrap();
+    for _ in 0..3 {
+        expected.rewind();
+    }
+    assert_eq!(tree.checkpoints().len(), expected.checkpoints().len());
+    assert_eq!(
+        tree.checkpoints().len() + 3,
+        original
+            .orchard_witnesses
+            .as_ref()
+            .unwrap()
+            .checkpoints()
+            .len()
+    );
+    assert_eq!(tree.root(0), expected.root(0));
+
+    let txns = &wallet.transactions;
+    assert_eq!(txns.current.len(), 1);
+    let funds = txns.current.values().next().unwrap();
+    assert_eq!(txns.last_txid, Some(funds.txid));
+    let nd = &funds.sapling_notes[0];
+    assert_eq!(nd.spent, None);
+    assert_eq!((nd.witnesses.len(), nd.witnesses.top_height), (2, height));
+    assert_eq!(funds.orchard_notes[0].spent, None);
+    assert_eq!(funds.utxos[0].unconfirmed_spent, None);
+    assert_eq!(
+        (funds.utxos[1].spent, funds.utxos[1].spent_at_height),
+        (None, None)
+    );
+}
+
+#[test]
+fn rewinding_too_far_leaves_the_wallet() {
+    let (mut wallet, tip) = fixture();
+    let lowest = wallet.blocks.last().unwrap().height;
+
+    assert!(wallet.rewind_to(u64::from(tip) + 1).is_err());
+    assert!(wallet.rewind_to(lowest - 1).is_err());
+    assert_eq!(wallet.latest_height(), Some(u64::from(tip)));
+    assert!(wallet.verified_tree.is_some());
+    assert_eq!(wallet.transactions.current.len(), 2);
+
+    // Rewinding to the tip changes nothing.
+    let report = wallet.rewind_to(u64::from(tip)).unwrap();
+    assert_eq!(report.blocks_removed, 0);
+    assert!(report.removed_txs.is_empty());
+    assert_eq!(report.cleared_verified_tree, None);
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use sapling_crypto::{CommitmentTree, IncrementalWitness, Node, Note};
+use zcash_client_backend::proto::service::TreeState;
+use zcash_primitives::merkle_tree::write_commitment_tree;
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{ZwlWallet, sapling_data::SaplingNoteData, sapling_witness::WitnessStatus},
+};
+
+use common::{HEIGHT, WALLET, sapling_note, wtx};
+
+fn leaf(note: &Note) -> Node {
+    Node::from_cmu(&note.cmu())
+}
+
+fn tree_state(height: u64, tree: &CommitmentTree) -> TreeState {
+    let mut bytes = vec![];
+    write_commitment_tree(tree, &mut bytes).unwrap();
+    TreeState {
+        network: "main".to_string(),
+        height,
+        sapling_tree: hex::encode(bytes),
+        ..Default::default()
+    }
+}
+
+/// The wallet with a note at position 1 of a tree, witnessed at [`HEIGHT`] and the block after,
+/// and the tree at these two heights.
+fn fixture() -> (ZwlWallet, [CommitmentTree; 2]) {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let extfvk = wallet.keys.zkeys[0].extfvk.clone();
+    let address = extfvk.default_address().1;
+    let other = sapling_note(&extfvk, address, 1, 1).note;
+    let mut nd = sapling_note(&extfvk, address, 5_000, 2);
+
+    let mut tree = CommitmentTree::empty();
+    tree.append(leaf(&other)).unwrap();
+    tree.append(leaf(&nd.note)).unwrap();
+    let mut witness = IncrementalWitness::from_tree(tree.clone());
+    nd.witnesses.witnesses.push(witness.clone());
+    let older = tree.clone();
+    tree.append(leaf(&other)).unwrap();
+    witness.append(leaf(&other)).unwrap();
+    nd.witnesses.witnesses.push(witness);
+    nd.witnesses.top_height = HEIGHT as u64 + 1;
+    nd.nullifier = nd.note.nf(&extfvk.fvk.vk.nk, 1);
+
+    let mut wtx = wtx(1, HEIGHT);
+    wtx.sapling_notes.push(nd);
+    wallet.transactions.current.insert(wtx.txid, wtx);
+
+    (wallet, [older, tree])
+}
+
+fn note(wallet: &mut ZwlWallet) -> &mut SaplingNoteData {
+    let wtx = wallet.transactions.current.values_mut().next().unwrap();
+    &mut wtx.sapling_notes[0]
+}
+
+fn status(wallet: &ZwlWallet) -> WitnessStatus {
+    let report = wallet.check_sapling_witnesses().unwrap();
+    assert_eq!(report.checks.len(), 1);
+    report.checks[0].status.clone()
+}
+
+#[test]
+fn witnesses_match_the_verified_tree() {
+    let (mut wallet, [older, tree]) = fixture();
+    let top = HEIGHT as u64 + 1;
+    assert_eq!(status(&wallet), WitnessStatus::Unverified);
+
+    wallet.verified_tree = Some(tree_state(top, &tree));
+    assert_eq!(status(&wallet), WitnessStatus::Valid);
+    let report = wallet.check_sapling_witnesses().unwrap();
+    assert_eq!(report.tree_size, Some(3));
+    assert_eq!(report.checks[0].position, Some(1));
+    assert_eq!(report.problems().count(), 0);
+
+    // The older witness is checked against an older tree.
+    wallet.verified_tree = Some(tree_state(top - 1, &older));
+    assert_eq!(status(&wallet), WitnessStatus::Valid);
+}
+
+#[test]
+fn broken_witnesses_are_reported() {
+    let (mut wallet, [older, tree]) = fixture();
+    let top = HEIGHT as u64 + 1;
+
+    wallet.verified_tree = Some(tree_state(top + 1, &tree));
+    assert_eq!(
+        status(&wallet),
+        WitnessStatus::Stale {
+            top_height: top,
+            tree_height: top + 1
+        }
+    );
+
+    wallet.verified_tree = Some(tree_state(top - 2, &tree));
+    assert!(matches!(status(&wallet), WitnessStatus::OutOfRange { .. }));
+
+    wallet.verified_tree = Some(tree_state(top, &older));
+    assert!(matches!(
+        status(&wallet),
+        WitnessStatus::RootMismatch { .. }
+    ));
+
+    note(&mut wallet).nullifier = sapling_crypto::Nullifier([0; 32]);
+    assert!(matches!(status(&wallet), WitnessStatus::Inconsistent(_)));
+
+    note(&mut wallet).witnesses.witnesses.clear();
+    assert_eq!(status(&wallet), WitnessStatus::Empty);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use prost::Message;
+use rand::rngs::OsRng;
+use sapling_crypto::{
+    note_encryption::{SaplingDomain, sapling_note_encryption},
+    zip32::ExtendedFullViewingKey,
+};
+use zcash_client_backend::proto::compact_formats::{CompactBlock, CompactSaplingOutput, CompactTx};
+use zcash_note_encryption::Domain;
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{data::ChainType, sapling_data::SaplingNoteData, scan::Pool},
+};
+
+use common::{WALLET, sapling_note, wtx};
+
+/// A compact block at `height` with a single transaction `[byte; 32]` paying `nd` in the clear
+/// to its recipient, as lightwalletd would serve it.
+fn block_paying(height: u64, byte: u8, nd: &SaplingNoteData) -> Vec<u8> {
+    let encryption = sapling_note_encryption(None, nd.note.clone(), [0; 512], &mut OsRng);
+    let output = CompactSaplingOutput {
+        cmu: nd.note.cmu().to_bytes().to_vec(),
+        ephemeral_key: SaplingDomain::epk_bytes(encryption.epk()).0.to_vec(),
+        ciphertext: encryption.encrypt_note_plaintext()[..52].to_vec(),
+    };
+    CompactBlock {
+        height,
+        vtx: vec![CompactTx {
+            hash: vec![byte; 32],
+            outputs: vec![output],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+    .encode_to_vec()
+}
+
+fn note(extfvk: &ExtendedFullViewingKey, value: u64, byte: u8) -> SaplingNoteData {
+    sapling_note(extfvk, extfvk.default_address().1, value, byte)
+}
+
+#[test]
+fn cached_blocks_have_no_notes_of_the_wallet() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+
+    let report = wallet.scan_cached_blocks().unwrap();
+    assert_eq!(report.blocks_scanned, 100);
+    assert_eq!(report.scanned_range, Some((2757862, 2757961)));
+    assert!(report.undecodable_blocks.is_empty());
+    assert!(report.matched.is_empty());
+    assert!(report.is_consistent());
+}
+
+#[test]
+fn notes_are_compared_with_the_transactions() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let extfvk = wallet.keys.zkeys[1].extfvk.clone();
+    let height = wallet.blocks[50].height;
+    let on_chain = note(&extfvk, 5_000, 1);
+    wallet.blocks[50].ecb = block_paying(height, 1, &on_chain);
+    wallet.blocks[60].ecb = vec![0xff; 4];
+
+    let report = wallet.scan_cached_blocks().unwrap();
+    assert_eq!(report.blocks_scanned, 99);
+    assert_eq!(report.undecodable_blocks, [wallet.blocks[60].height]);
+    let [missing] = &report.missing[..] else {
+        panic!("expected one missing note, got {:?}", report.missing);
+    };
+    assert_eq!(
+        (
+            missing.height,
+            missing.pool,
+            missing.key_index,
+            missing.value
+        ),
+        (height, Pool::Sapling, 1, 5_000)
+    );
+    assert_eq!(missing.commitment, on_chain.note.cmu().to_bytes());
+
+    // Once recorded, the note matches. A note recorded in the scanned range but not on chain is
+    // an orphan, one below the cached blocks can't be checked.
+    let mut found = wtx(1, height as u32);
+    found.sapling_notes.push(on_chain);
+    let mut orphan = wtx(2, height as u32 + 1);
+    orphan.sapling_notes.push(note(&extfvk, 6_000, 2));
+    let mut old = wtx(3, 2757000);
+    old.sapling_notes.push(note(&extfvk, 7_000, 3));
+    for wtx in [found, orphan, old] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    let report = wallet.scan_cached_blocks().unwrap();
+    assert_eq!(report.matched.len(), 1);
+    assert!(report.missing.is_empty());
+    let [orphan] = &report.orphans[..] else {
+        panic!("expected one orphan note, got {:?}", report.orphans);
+    };
+    assert_eq!(
+        (orphan.height, orphan.pool, orphan.value),
+        (height + 1, Pool::Sapling, 6_000)
+    );
+}
+
+#[test]
+fn regtest_wallets_are_refused() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    wallet.chain_name = ChainType::Regtest;
+
+    let error = wallet.scan_cached_blocks().unwrap_err();
+    assert!(error.to_string().contains("Regtest"), "{}", error);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use bip0039::{English, Mnemonic};
+use zcash_client_backend::proto::service::TreeState;
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, data::ChainType, orchard_tree::encode_orchard_frontier},
+};
+
+use common::WALLET;
+
+#[test]
+fn seed_phrase_derives_the_wallet_keys() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let phrase = <Mnemonic<English>>::from_entropy(wallet.keys.seed)
+        .unwrap()
+        .phrase()
+        .to_string();
+
+    let created =
+        ZwlWallet::from_seed_phrase(&phrase, 2, ChainType::Mainnet, wallet.birthday, None).unwrap();
+    assert_eq!(created.keys.seed, wallet.keys.seed);
+
+    for (created, key) in created.keys.tkeys.iter().zip(&wallet.keys.tkeys) {
+        assert_eq!(created.address, key.address);
+        assert_eq!(created.pk, key.pk);
+        assert_eq!(created.hdkey_num, key.hdkey_num);
+    }
+    for (created, key) in created.keys.zkeys.iter().zip(&wallet.keys.zkeys) {
+        assert_eq!(created, key);
+    }
+    for (created, key) in created.keys.okeys.iter().zip(&wallet.keys.okeys) {
+        assert_eq!(created.fvk, key.fvk);
+        assert_eq!(
+            created.sk.map(|sk| *sk.to_bytes()),
+            key.sk.map(|sk| *sk.to_bytes())
+        );
+        assert_eq!(created.hdkey_num, key.hdkey_num);
+    }
+
+    let bytes = WalletWriter::to_bytes(&created).unwrap();
+    let read = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    assert_eq!(read.birthday, wallet.birthday);
+    assert_eq!(read.keys.zkeys, created.keys.zkeys);
+    assert_eq!(read.keys.tkeys.len(), 2);
+    assert_eq!(read.keys.okeys.len(), 2);
+}
+
+#[test]
+fn seed_phrase_must_have_24_words() {
+    let phrase = <Mnemonic<English>>::from_entropy([0u8; 16])
+        .unwrap()
+        .phrase()
+        .to_string();
+
+    assert!(ZwlWallet::from_seed_phrase(&phrase, 1, ChainType::Mainnet, 1, None).is_err());
+    assert!(ZwlWallet::from_seed_phrase("not a phrase", 1, ChainType::Mainnet, 1, None).is_err());
+}
+
+#[test]
+fn tree_state_must_be_just_below_the_birthday() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let phrase = <Mnemonic<English>>::from_entropy(wallet.keys.seed)
+        .unwrap()
+        .phrase()
+        .to_string();
+    let tip = &wallet.blocks[0];
+    let tree_state = TreeState {
+        network: "main".to_string(),
+        height: tip.height,
+        hash: tip.hash.clone(),
+        time: 0,
+        // An empty Sapling tree.
+        sapling_tree: "000000".to_string(),
+        orchard_tree: hex::encode(encode_orchard_frontier(
+            wallet.orchard_witnesses.as_ref().unwrap(),
+        )),
+    };
+    let restore = |birthday| {
+        ZwlWallet::from_seed_phrase(
+            &phrase,
+            1,
+            ChainType::Mainnet,
+            birthday,
+            Some(tree_state.clone()),
+        )
+    };
+
+    let created = restore(tip.height + 1).unwrap();
+    assert_eq!(created.verified_tree.as_ref(), Some(&tree_state));
+    assert_eq!(created.rescan_start_height(), tip.height + 1);
+    // The tree state at the birthday would skip the birthday's block, and an older one the blocks
+    // in between.
+    assert!(restore(tip.height).is_err());
+    assert!(restore(tip.height + 2).is_err());
+}
@@ -0,0 +1,283 @@
+mod common;
+
+use std::{fs, process};
+
+use incrementalmerkletree::Tree;
+use zcash_client_backend::proto::{compact_formats::CompactBlock, service::RawTransaction};
+use zcash_keys::address::Address;
+use zcash_primitives::{
+    consensus::{BlockHeight, BranchId, MAIN_NETWORK},
+    legacy::Script,
+    transaction::{
+        Authorized, TransactionData, TxId, TxVersion,
+        components::{
+            amount::NonNegativeAmount,
+            transparent::{self, OutPoint, TxIn, TxOut},
+        },
+    },
+};
+use zecwallet_parser::{
+    reader::WalletReader,
+    zwl::{
+        ZwlWallet,
+        sync::{read_compact_blocks, write_compact_blocks},
+    },
+};
+
+use common::{WALLET, wtx};
+
+fn cached_blocks(wallet: &ZwlWallet) -> Vec<CompactBlock> {
+    let mut blocks: Vec<_> = wallet
+        .blocks
+        .iter()
+        .map(|b| b.compact_block().unwrap())
+        .collect();
+    blocks.reverse();
+    blocks
+}
+
+/// A transparent transaction mined at `height`, spending `inputs` and paying `outputs`.
+fn raw_tx(
+    height: u64,
+    inputs: &[(TxId, u32)],
+    outputs: &[(Script, u64)],
+) -> (TxId, RawTransaction) {
+    let branch_id = BranchId::for_height(&MAIN_NETWORK, BlockHeight::from_u32(height as u32));
+    let bundle = transparent::Bundle {
+        vin: inputs
+            .iter()
+            .map(|(txid, n)| TxIn {
+                prevout: OutPoint::new(*txid.as_ref(), *n),
+                script_sig: Script(vec![]),
+                sequence: u32::MAX,
+            })
+            .collect(),
+        vout: outputs
+            .iter()
+            .map(|(script, value)| TxOut {
+                value: NonNegativeAmount::const_from_u64(*value),
+                script_pubkey: script.clone(),
+            })
+            .collect(),
+        authorization: transparent::Authorized,
+    };
+    let tx = TransactionData::<Authorized>::from_parts(
+        TxVersion::suggested_for_branch(branch_id),
+        branch_id,
+        0,
+        BlockHeight::from_u32(height as u32 + 40),
+        Some(bundle),
+        None,
+        None,
+        None,
+    )
+    .freeze()
+    .unwrap();
+
+    let mut data = vec![];
+    tx.write(&mut data).unwrap();
+    (tx.txid(), RawTransaction { data, height })
+}
+
+fn script(address: &str) -> Script {
+    match Address::decode(&MAIN_NETWORK, address) {
+        Some(Address::Transparent(address)) => address.script(),
+        _ => panic!("not a transparent address: {}", address),
+    }
+}
+
+fn block_ids(wallet: &ZwlWallet) -> Vec<(u64, String)> {
+    wallet
+        .blocks
+        .iter()
+        .map(|b| (b.height, b.hash.clone()))
+        .collect()
+}
+
+#[test]
+fn resyncing_the_cached_blocks_restores_the_wallet() {
+    let original = WalletReader::read(WALLET).unwrap();
+    let blocks = cached_blocks(&original);
+    let tip = original.blocks[0].height;
+
+    let mut wallet = original.clone();
+    wallet.rewind_to(tip - 40).unwrap();
+    let report = wallet.sync_from_blocks(&blocks).unwrap();
+
+    assert_eq!((report.start_height, report.end_height), (tip - 40, tip));
+    assert_eq!((report.blocks_skipped, report.blocks_scanned), (60, 40));
+    assert!(report.received.is_empty() && report.spent.is_empty());
+    assert_eq!(block_ids(&wallet), block_ids(&original));
+
+    let (tree, original_tree) = (
+        wallet.orchard_witnesses.as_ref().unwrap(),
+        original.orchard_witnesses.as_ref().unwrap(),
+    );
+    assert_eq!(tree.root(0), original_tree.root(0));
+    assert_eq!(tree.current_position(), original_tree.current_position());
+    assert_eq!(tree.checkpoints().len(), original_tree.checkpoints().len());
+
+    // Syncing again changes nothing.
+    let report = wallet.sync_from_blocks(&blocks).unwrap();
+    assert_eq!((report.blocks_skipped, report.blocks_scanned), (100, 0));
+    assert_eq!(block_ids(&wallet), block_ids(&original));
+}
+
+#[test]
+fn blocks_are_read_from_a_dump() {
+    let original = WalletReader::read(WALLET).unwrap();
+    let blocks = cached_blocks(&original);
+    let tip = original.blocks[0].height;
+
+    let dir = std::env::temp_dir().join(format!("zwl-sync-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    // Files are read in name order, and the blocks sorted by height.
+    let mut first = vec![];
+    write_compact_blocks(&mut first, &blocks[50..]).unwrap();
+    fs::write(dir.join("1.bin"), first).unwrap();
+    let mut second = vec![];
+    write_compact_blocks(&mut second, &blocks[..50]).unwrap();
+    fs::write(dir.join("2.bin"), second).unwrap();
+
+    assert_eq!(read_compact_blocks(&dir).unwrap(), blocks);
+
+    let mut wallet = original.clone();
+    wallet.rewind_to(tip - 10).unwrap();
+    let report = wallet.sync_from_path(&dir, None).unwrap();
+    assert_eq!(report.end_height, tip);
+    assert_eq!(block_ids(&wallet), block_ids(&original));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn only_sent_transactions_in_a_block_are_mined() {
+    let original = WalletReader::read(WALLET).unwrap();
+    let blocks = cached_blocks(&original);
+    let tip = original.blocks[0].height;
+    let mut wallet = original.clone();
+    wallet.rewind_to(tip - 10).unwrap();
+
+    // Two transactions sent at the same height, only one of them mined in a later block.
+    let block = blocks[91..].iter().find(|b| !b.vtx.is_empty()).unwrap();
+    let mined = block.vtx[0].txid();
+    let mut pending = wtx(1, block.height as u32);
+    pending.unconfirmed = true;
+    let mut sent = pending.clone();
+    sent.txid = mined;
+    for wtx in [pending.clone(), sent] {
+        wallet.transactions.current.insert(wtx.txid, wtx);
+    }
+
+    wallet.sync_from_blocks(&blocks).unwrap();
+    let mined = &wallet.transactions.current[&mined];
+    assert!(!mined.unconfirmed);
+    assert_eq!(u64::from(u32::from(mined.block)), block.height);
+    assert!(wallet.transactions.current[&pending.txid].unconfirmed);
+}
+
+#[test]
+fn diverging_blocks_are_refused() {
+    let original = WalletReader::read(WALLET).unwrap();
+    let tip = original.blocks[0].height;
+    let mut wallet = original.clone();
+    wallet.rewind_to(tip - 10).unwrap();
+    let rewound = block_ids(&wallet);
+    let root = wallet.orchard_witnesses.as_ref().unwrap().root(0);
+
+    // A known block that differs from the cached one.
+    let mut blocks = cached_blocks(&original);
+    blocks[50].hash = vec![0; 32];
+    let error = wallet.sync_from_blocks(&blocks).unwrap_err();
+    assert!(error.to_string().contains("Rewind the wallet"), "{}", error);
+
+    // A new block that doesn't build on the wallet's tip.
+    let mut blocks = cached_blocks(&original);
+    blocks[95].prev_hash = vec![0; 32];
+    assert!(wallet.sync_from_blocks(&blocks).is_err());
+
+    // A gap after the tip.
+    let mut blocks = cached_blocks(&original);
+    blocks.remove(92);
+    assert!(wallet.sync_from_blocks(&blocks).is_err());
+
+    assert_eq!(block_ids(&wallet), rewound);
+    assert_eq!(wallet.orchard_witnesses.unwrap().root(0), root);
+}
+
+#[test]
+fn utxos_are_found_in_the_raw_transactions() {
+    let original = WalletReader::read(WALLET).unwrap();
+    let blocks = cached_blocks(&original);
+    let tip = original.blocks[0].height;
+    let taddrs: Vec<_> = original
+        .keys
+        .tkeys
+        .iter()
+        .map(|t| t.address.clone())
+        .collect();
+
+    // A pays the first address and someone else, B spends that and pays the second address.
+    let (a, raw_a) = raw_tx(
+        tip - 5,
+        &[],
+        &[(Script(vec![]), 1_000), (script(&taddrs[0]), 20_000)],
+    );
+    let (b, raw_b) = raw_tx(tip - 2, &[(a, 1)], &[(script(&taddrs[1]), 15_000)]);
+
+    let mut wallet = original.clone();
+    wallet.rewind_to(tip - 10).unwrap();
+    // Transactions can't be mined above the synced blocks.
+    assert!(
+        wallet
+            .sync_from_dump(&blocks[..95], &[raw_a.clone(), raw_b.clone()])
+            .is_err()
+    );
+
+    let report = wallet
+        .sync_from_dump(&blocks, &[raw_b.clone(), raw_a.clone()])
+        .unwrap();
+    let received: Vec<_> = report
+        .received_utxos
+        .iter()
+        .map(|u| (u.height, u.txid, u.output_index, u.address.clone(), u.value))
+        .collect();
+    assert_eq!(
+        received,
+        [
+            (tip - 5, a, 1, taddrs[0].clone(), 20_000),
+            (tip - 2, b, 0, taddrs[1].clone(), 15_000),
+        ]
+    );
+    let [spent] = &report.spent_utxos[..] else {
+        panic!("expected one spent UTXO, got {:?}", report.spent_utxos);
+    };
+    assert_eq!(
+        (
+            spent.height,
+            spent.txid,
+            spent.utxo_txid,
+            spent.output_index,
+            spent.value
+        ),
+        (tip - 2, b, a, 1, 20_000)
+    );
+
+    let utxo = &wallet.transactions.current[&a].utxos[0];
+    assert_eq!(
+        (utxo.spent, utxo.spent_at_height),
+        (Some(b), Some((tip - 2) as i32))
+    );
+    let wtx_b = &wallet.transactions.current[&b];
+    assert_eq!(wtx_b.total_transparent_value_spent, 20_000);
+    assert_eq!(wtx_b.utxos[0].spent, None);
+
+    // Syncing the same transactions again changes nothing.
+    let synced = wallet.clone();
+    let report = wallet.sync_from_dump(&blocks, &[raw_a, raw_b]).unwrap();
+    assert!(report.received_utxos.is_empty() && report.spent_utxos.is_empty());
+    assert_eq!(
+        wallet.transactions.current[&b].total_transparent_value_spent,
+        synced.transactions.current[&b].total_transparent_value_spent
+    );
+}
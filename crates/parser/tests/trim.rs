@@ -0,0 +1,90 @@
+mod common;
+
+use incrementalmerkletree::Tree;
+use zcash_client_backend::proto::service::TreeState;
+use zecwallet_parser::{
+    reader::WalletReader,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, orchard_tree::encode_orchard_frontier},
+};
+
+use common::WALLET;
+
+fn roundtrip(wallet: &ZwlWallet) -> ZwlWallet {
+    let bytes = WalletWriter::to_bytes(wallet).unwrap();
+    WalletReader::read_from_reader(&bytes[..]).unwrap()
+}
+
+fn taddresses(wallet: &ZwlWallet) -> Vec<String> {
+    wallet
+        .keys
+        .tkeys
+        .iter()
+        .map(|k| k.address.clone())
+        .collect()
+}
+
+#[test]
+fn trimmed_wallet_roundtrips() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    assert_eq!(wallet.rescan_start_height(), wallet.blocks[0].height + 1);
+    let mut trimmed = wallet.clone();
+    trimmed.trim_for_rescan(None).unwrap();
+
+    let read = roundtrip(&trimmed);
+    assert!(read.blocks.is_empty());
+    assert!(read.transactions.current.is_empty());
+    assert!(read.verified_tree.is_none());
+    assert!(read.orchard_witnesses.is_none());
+
+    assert_eq!(read.chain_name.to_string(), wallet.chain_name.to_string());
+    assert_eq!(read.birthday, wallet.birthday);
+    assert_eq!(read.rescan_start_height(), wallet.birthday);
+    assert_eq!(taddresses(&read), taddresses(&wallet));
+    assert_eq!(read.keys.zkeys.len(), wallet.keys.zkeys.len());
+    assert_eq!(read.keys.okeys.len(), wallet.keys.okeys.len());
+}
+
+#[test]
+fn trimmed_wallet_resets_to_checkpoint() {
+    let wallet = WalletReader::read(WALLET).unwrap();
+    let tree = wallet.orchard_witnesses.as_ref().unwrap();
+    let tip = &wallet.blocks[0];
+    let checkpoint = TreeState {
+        network: "main".to_string(),
+        height: tip.height,
+        hash: tip.hash.clone(),
+        time: 0,
+        // An empty Sapling tree.
+        sapling_tree: "000000".to_string(),
+        orchard_tree: hex::encode(encode_orchard_frontier(tree)),
+    };
+
+    let mut trimmed = wallet.clone();
+    trimmed.trim_for_rescan(Some(&checkpoint)).unwrap();
+
+    let read = roundtrip(&trimmed);
+    assert!(read.blocks.is_empty());
+    assert!(read.transactions.current.is_empty());
+    assert_eq!(read.verified_tree, Some(checkpoint));
+    // The rescan starts right after the checkpoint.
+    assert_eq!(read.birthday, tip.height + 1);
+    assert_eq!(read.rescan_start_height(), tip.height + 1);
+
+    let read_tree = read.orchard_witnesses.as_ref().unwrap();
+    assert_eq!(read_tree.root(0), tree.root(0));
+    assert_eq!(read_tree.checkpoints().len(), 0);
+}
+
+#[test]
+fn invalid_checkpoint_leaves_wallet_untouched() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let checkpoint = TreeState {
+        sapling_tree: "not hex".to_string(),
+        ..Default::default()
+    };
+
+    assert!(wallet.trim_for_rescan(Some(&checkpoint)).is_err());
+    assert!(!wallet.blocks.is_empty());
+    assert!(wallet.orchard_witnesses.is_some());
+}
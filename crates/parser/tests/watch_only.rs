@@ -0,0 +1,32 @@
+mod common;
+
+use zecwallet_parser::{reader::WalletReader, writer::WalletWriter, zwl::watch_only::find_secrets};
+
+use common::WALLET;
+
+#[test]
+fn watch_only_wallet_has_no_secrets() {
+    let mut wallet = WalletReader::read(WALLET).unwrap();
+    let secrets = wallet.secrets();
+    // The seed and the spending keys of the 5 HD keys.
+    assert_eq!(secrets.len(), 1 + 2 * 2 + 1 + 2);
+    assert_eq!(
+        find_secrets(&secrets, &WalletWriter::to_bytes(&wallet).unwrap()).len(),
+        secrets.len()
+    );
+
+    let report = wallet.make_watch_only();
+    assert!(report.seed_removed);
+    assert_eq!(report.zkeys_converted, 2);
+    assert_eq!(report.okeys_converted, 1);
+    assert_eq!(report.tkeys_stripped, 2);
+
+    let bytes = WalletWriter::to_bytes(&wallet).unwrap();
+    assert!(find_secrets(&secrets, &bytes).is_empty());
+
+    let read = WalletReader::read_from_reader(&bytes[..]).unwrap();
+    assert!(read.secrets().is_empty());
+    assert!(read.keys.zkeys.iter().all(|k| !k.have_spending_key()));
+    assert_eq!(read.keys.tkeys.len(), 2);
+    assert_eq!(read.keys.okeys.len(), 1);
+}
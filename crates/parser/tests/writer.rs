@@ -0,0 +1,29 @@
+mod common;
+
+use std::{fs, process};
+
+use zecwallet_parser::{reader::WalletReader, writer::WalletWriter, zwl::data::ChainType};
+
+use common::WALLET;
+
+#[test]
+fn failed_writes_keep_the_wallet() {
+    let dir = std::env::temp_dir().join(format!("zwl-writer-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("zecwallet-light-wallet.dat");
+    fs::copy(WALLET, &path).unwrap();
+
+    let mut wallet = WalletReader::read(&path).unwrap();
+    wallet.birthday += 1;
+    WalletWriter::write(&path, &wallet).unwrap();
+    assert_eq!(WalletReader::read(&path).unwrap().birthday, wallet.birthday);
+    let written = fs::read(&path).unwrap();
+
+    // A wallet for an unknown chain can't be serialized, which must not touch the file.
+    wallet.chain_name = ChainType::Unknown;
+    assert!(WalletWriter::write(&path, &wallet).is_err());
+    assert_eq!(fs::read(&path).unwrap(), written);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
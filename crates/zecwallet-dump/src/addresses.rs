@@ -0,0 +1,47 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::{error::WalletError, zwl::ZwlWallet};
+
+use crate::password::read_password;
+
+/// Prints the next `count` addresses of the wallet's keys, and which of them were used. The
+/// password of an encrypted wallet is read from stdin, to derive the transparent addresses.
+pub fn print_addresses(wallet: &ZwlWallet, count: u32) -> Result<(), WalletError> {
+    let password = if wallet.keys.encrypted {
+        let password = read_password(
+            "Enter the wallet password, or nothing to skip the transparent addresses:",
+        )?;
+        (!password.is_empty()).then_some(password)
+    } else {
+        None
+    };
+    let enumeration = wallet.enumerate_addresses(count, password.as_deref())?;
+
+    for address in &enumeration.addresses {
+        let key = match address.key {
+            Some(key) => format!("{} key {} #{}", address.pool, key, address.index),
+            None => format!("{} #{}", address.pool, address.index),
+        };
+        if address.used {
+            println!(
+                "{} {} {}",
+                key.cyan(),
+                address.address.bold(),
+                "(used)".green()
+            );
+        } else {
+            println!("{} {}", key.cyan(), address.address.dimmed());
+        }
+    }
+    if let Some(reason) = &enumeration.transparent_skipped {
+        println!("{} {}", "Transparent addresses skipped:".yellow(), reason);
+    }
+
+    println!(
+        "\n{} {} {}",
+        "Found".bold(),
+        enumeration.used().count().bold(),
+        "used addresses beyond the stored ones.".bold()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::{fs, path::Path};
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, watch_only::find_secrets},
+};
+
+/// Writes an anonymized copy of the wallet to `output`, and the map back to
+/// the original wallet to `map_path`. With `keep_blocks`, the cached blocks and
+/// the verified tree are copied as they are.
+pub fn write_anonymized(
+    wallet: &ZwlWallet,
+    output: &Path,
+    map_path: &Path,
+    keep_blocks: bool,
+) -> Result<(), WalletError> {
+    let (anonymized, map) = wallet.anonymize(keep_blocks)?;
+
+    let bytes = WalletWriter::to_bytes(&anonymized)?;
+    let secrets = wallet.secrets();
+    let leaked = find_secrets(&secrets, &bytes);
+    if !leaked.is_empty() {
+        let labels: Vec<&str> = leaked.iter().map(|s| s.label.as_str()).collect();
+        return Err(WalletError::InvalidFormat(format!(
+            "Secrets remain in the anonymized wallet: {}",
+            labels.join(", ")
+        )));
+    }
+
+    println!(
+        "{} {} addresses, {} transactions and {} amounts",
+        "Replaced".bold(),
+        map.addresses.len(),
+        map.txids.len(),
+        map.values.len()
+    );
+
+    fs::write(map_path, map.to_string())?;
+    fs::write(output, bytes)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+    println!(
+        "{} {} {}",
+        "Wrote".bold(),
+        map_path.display(),
+        "(keep it private)".yellow()
+    );
+
+    Ok(())
+}
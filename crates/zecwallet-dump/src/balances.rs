@@ -0,0 +1,33 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::zwl::ZwlWallet;
+
+/// Prints the balance and activity of each address of the wallet.
+pub fn print_balances(wallet: &ZwlWallet) {
+    let balances = wallet.address_balances();
+
+    for balance in &balances {
+        let address = if balance.in_keys {
+            balance.address.bold().to_string()
+        } else {
+            format!("{} {}", balance.address.bold(), "(not in keys)".yellow())
+        };
+        println!("{} {}", balance.pool.cyan(), address);
+        println!(
+            "    Balance: {} zats ({} received, {} spent in {} notes)",
+            balance.balance().green(),
+            balance.received,
+            balance.spent,
+            balance.notes
+        );
+        match (balance.first_activity, balance.last_activity) {
+            (Some(first), Some(last)) => println!("    Activity: {} to {}", first, last),
+            _ => println!("    Activity: {}", "none".dimmed()),
+        }
+    }
+
+    println!(
+        "\n{} {} zats",
+        "Total:".bold(),
+        balances.iter().map(|b| b.balance()).sum::<u64>().green()
+    );
+}
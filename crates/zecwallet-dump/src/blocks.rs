@@ -0,0 +1,55 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::zwl::ZwlWallet;
+
+/// Prints the cached compact blocks, highest first, followed by the result of
+/// the chain-continuity check.
+pub fn print_blocks(wallet: &ZwlWallet) {
+    if wallet.blocks.is_empty() {
+        println!("No cached blocks found in wallet.");
+        return;
+    }
+
+    println!(
+        "{} {} {}\n",
+        "Found".bold(),
+        wallet.blocks.len().bold().red(),
+        "cached blocks:".bold()
+    );
+
+    for block in &wallet.blocks {
+        match block.compact_block() {
+            Ok(cb) => {
+                let outputs: usize = cb.vtx.iter().map(|tx| tx.outputs.len()).sum();
+                let actions: usize = cb.vtx.iter().map(|tx| tx.actions.len()).sum();
+                println!(
+                    "- {} {} prev {} time {} txs {} outputs {} actions {}",
+                    block.height.bright_green(),
+                    block.hash,
+                    cb.prev_hash(),
+                    cb.time,
+                    cb.vtx.len(),
+                    outputs,
+                    actions
+                );
+            }
+            Err(e) => {
+                println!(
+                    "- {} {} {}",
+                    block.height.bright_green(),
+                    block.hash,
+                    e.red()
+                );
+            }
+        }
+    }
+
+    let breaks = wallet.check_block_continuity();
+    if breaks.is_empty() {
+        println!("\n{}", "Cached blocks form a continuous chain.".green());
+    } else {
+        println!("\n{} {}", "Chain breaks:".bold(), breaks.len().red().bold());
+        for chain_break in breaks {
+            println!("- {}", chain_break);
+        }
+    }
+}
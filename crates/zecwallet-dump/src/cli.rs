@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use zecwallet_parser::zwl::data::{ChainType, MemoDownloadOption};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -24,4 +25,283 @@ pub struct Cli {
 pub enum Commands {
     /// Summarizes the contents of the specified ZecWallet Lite wallet file.
     Summarize,
+    /// Lists the cached compact blocks and checks that they form a chain.
+    Blocks,
+    /// Lists the wallet's transactions, with legacy mempool ones shown as pending.
+    History,
+    /// Trial-decrypts the cached compact blocks to find notes the wallet missed.
+    Scan,
+    /// Validates the Sapling note witnesses against the verified tree state.
+    Witnesses,
+    /// Inspects the Orchard witness tree and verifies the notes' authentication paths.
+    OrchardTree,
+    /// Syncs the wallet with a local dump of compact blocks and writes the result to a new file.
+    Sync {
+        /// A file of length-delimited compact blocks, or a directory of such files.
+        #[arg(short, long, value_name = "PATH")]
+        blocks: PathBuf,
+
+        /// A file of length-delimited raw transactions of the wallet's transparent addresses,
+        /// as returned by `GetTaddressTxids`, or a directory of such files.
+        #[arg(short, long, value_name = "PATH")]
+        transactions: Option<PathBuf>,
+
+        /// Where to write the synced wallet.
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Clears stale unconfirmed spends and drops expired unconfirmed transactions.
+    Repair {
+        /// Where to write the repaired wallet. Defaults to the wallet file itself.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Only print what would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Creates the wallet file from a seed phrase read from stdin.
+    Restore {
+        /// Number of HD addresses to derive in each pool.
+        #[arg(short, long, default_value_t = 1)]
+        addresses: u32,
+
+        /// The chain the wallet is for.
+        #[arg(long, value_enum, default_value_t = Chain::Main)]
+        chain: Chain,
+
+        /// The wallet birthday, the height to rescan from.
+        #[arg(short, long, value_name = "HEIGHT")]
+        birthday: u64,
+
+        /// A lightwalletd endpoint to fetch the tree state below the birthday from.
+        #[arg(short, long, value_name = "URL")]
+        server: Option<String>,
+    },
+    /// Rewinds the wallet to an earlier height and writes the result to a new file.
+    Rewind {
+        /// The height to rewind to, which becomes the latest block.
+        #[arg(long, value_name = "HEIGHT")]
+        height: u64,
+
+        /// Where to write the rewound wallet.
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Keeps the keys and wipes the sync state, so that ZecWallet Lite rescans the wallet.
+    Trim {
+        /// Where to write the trimmed wallet.
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Reset to the tree state at this height instead of wiping the trees.
+        #[cfg(feature = "online")]
+        #[arg(long, value_name = "HEIGHT", requires = "server")]
+        checkpoint: Option<u64>,
+
+        /// The lightwalletd endpoint to fetch the checkpoint's tree state from.
+        #[cfg(feature = "online")]
+        #[arg(short, long, value_name = "URL", requires = "checkpoint")]
+        server: Option<String>,
+    },
+    /// Verifies the wallet against a lightwalletd server.
+    #[cfg(feature = "online")]
+    Verify {
+        /// The lightwalletd endpoint, e.g. https://mainnet.lightwalletd.com:9067
+        #[arg(short, long, value_name = "URL")]
+        server: String,
+
+        /// Scan from this height instead of the lowest cached block.
+        #[arg(long, value_name = "HEIGHT", conflicts_with = "from_birthday")]
+        start: Option<u64>,
+
+        /// Scan from the wallet birthday instead of the lowest cached block.
+        #[arg(long)]
+        from_birthday: bool,
+    },
+    /// Strips every secret from the wallet and writes a watch-only copy.
+    WatchOnly {
+        /// Where to write the watch-only wallet.
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Changes the wallet options, the birthday or the chain.
+    Edit {
+        /// Which memos to download.
+        #[arg(long, value_enum)]
+        download_memos: Option<DownloadMemos>,
+
+        /// Transactions with more outputs than this are treated as spam, -1 turns it off.
+        #[arg(long, value_name = "OUTPUTS", allow_negative_numbers = true)]
+        spam_threshold: Option<i64>,
+
+        /// The height rescans start from.
+        #[arg(short, long)]
+        birthday: Option<u64>,
+
+        /// The chain the wallet is for.
+        #[arg(long, value_enum)]
+        chain: Option<Chain>,
+
+        /// Switch the chain even though the wallet has keys or blocks for another one.
+        #[arg(long, requires = "chain")]
+        force_chain: bool,
+
+        /// Where to write the wallet. Defaults to the wallet file itself.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Imports Sapling, Orchard or transparent keys into the wallet.
+    Import {
+        /// The keys to import.
+        keys: Vec<String>,
+
+        /// A file with more keys to import, one per line.
+        #[arg(short, long, value_name = "FILE")]
+        file: Option<PathBuf>,
+
+        /// Where to write the wallet. Defaults to the wallet file itself.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Writes an anonymized copy of the wallet, for attaching to bug reports.
+    Anonymize {
+        /// Where to write the anonymized wallet.
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+        /// Where to write the map back to the original wallet, which should be kept private.
+        #[arg(short, long, value_name = "FILE")]
+        map: PathBuf,
+        /// Keep the cached blocks and the verified tree, which link the wallet's notes to the
+        /// chain.
+        #[arg(long)]
+        keep_blocks: bool,
+    },
+    /// Compares the wallet with another file of it, only looking at their public parts.
+    Diff {
+        /// The wallet file to compare with, taken as the newer one.
+        other: PathBuf,
+
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Checks the wallet for problems. Exits with 2 if a finding is at least as severe as
+    /// `--fail-on`.
+    Doctor {
+        #[arg(short, long, value_enum, default_value_t = DoctorFormat::Text)]
+        format: DoctorFormat,
+
+        /// The least severe finding that fails the check.
+        #[arg(long, value_enum, default_value_t = FailOn::Warning)]
+        fail_on: FailOn,
+    },
+    /// Lists what the wallet's history leaks, with the transactions involved.
+    Privacy,
+    /// Shows the balance and activity of each address, including diversified ones.
+    Balances,
+    /// Derives the next addresses of the wallet's keys, and shows which of them were used.
+    Addresses {
+        /// How many addresses to derive per shielded key, and in total for transparent ones.
+        #[arg(short = 'n', long, default_value_t = 20)]
+        count: u32,
+    },
+    /// Finds which key of the wallet an address belongs to, including diversified addresses.
+    Whose {
+        /// A transparent, Sapling or unified address.
+        address: String,
+    },
+    /// Exports how notes and Utxos flowed between the wallet's transactions, as a graph.
+    Graph {
+        #[arg(short, long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// Where to write the graph. Without it, the graph is printed.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Merges other wallet files into the wallet, reporting what they disagree on.
+    Merge {
+        /// The wallet files to merge, in order.
+        #[arg(required = true)]
+        others: Vec<PathBuf>,
+
+        /// Where to write the merged wallet. Without it, only the report is printed.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Serves the wallet's cached blocks as a mock lightwalletd server.
+    #[cfg(feature = "online")]
+    ServeLwd {
+        /// The address to listen on.
+        #[arg(short, long, value_name = "ADDR", default_value = "127.0.0.1:9067")]
+        listen: String,
+    },
+}
+
+/// How reports are printed.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// The chain a wallet is for.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Chain {
+    Main,
+    Test,
+    Regtest,
+}
+
+impl From<Chain> for ChainType {
+    fn from(chain: Chain) -> Self {
+        match chain {
+            Chain::Main => ChainType::Mainnet,
+            Chain::Test => ChainType::Testnet,
+            Chain::Regtest => ChainType::Regtest,
+        }
+    }
+}
+
+/// Which memos ZecWallet Lite downloads.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DownloadMemos {
+    None,
+    Wallet,
+    All,
+}
+
+impl From<DownloadMemos> for MemoDownloadOption {
+    fn from(option: DownloadMemos) -> Self {
+        match option {
+            DownloadMemos::None => MemoDownloadOption::NoMemos,
+            DownloadMemos::Wallet => MemoDownloadOption::WalletMemos,
+            DownloadMemos::All => MemoDownloadOption::AllMemos,
+        }
+    }
+}
+
+/// How the findings of `doctor` are printed.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DoctorFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+/// The least severe finding that fails `doctor`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FailOn {
+    Info,
+    Warning,
+    Error,
+}
+
+/// How `graph` is exported.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// JSON node-link.
+    Json,
 }
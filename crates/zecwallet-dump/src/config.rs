@@ -1,3 +1,4 @@
+#[allow(dead_code)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Verbosity {
     Basic,
@@ -5,6 +6,7 @@ pub enum Verbosity {
     Debug,
 }
 
+#[allow(dead_code)]
 #[derive(Copy, Clone, Debug)]
 pub struct OutputOptions {
     pub verbosity: Verbosity,
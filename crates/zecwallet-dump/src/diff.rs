@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+use zecwallet_parser::{
+    error::WalletError,
+    reader::WalletReader,
+    zwl::{
+        ZwlWallet,
+        diff::{DiffKind, FieldChange, SpendStatus, WalletDiff},
+    },
+};
+
+use crate::cli::OutputFormat;
+
+/// Prints what changed from the wallet to the one at `other`.
+pub fn print_diff(
+    wallet: &ZwlWallet,
+    other: &Path,
+    format: OutputFormat,
+) -> Result<(), WalletError> {
+    let other = WalletReader::read(other)?;
+    let diff = wallet.diff(&other);
+
+    match format {
+        OutputFormat::Text => print_text(&diff),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&to_json(&diff)).expect("JSON values serialize")
+        ),
+    }
+
+    Ok(())
+}
+
+fn print_text(diff: &WalletDiff) {
+    if diff.is_empty() {
+        println!("{}", "The wallets are the same.".green());
+        return;
+    }
+
+    println!(
+        "{} {} fields, {} keys, {} transactions and {} notes changed\n",
+        "Found".bold(),
+        diff.fields.len().red(),
+        diff.keys.len().red(),
+        diff.transactions.len().red(),
+        diff.notes.len().red()
+    );
+    for line in diff.to_string().lines() {
+        if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line.yellow());
+        }
+    }
+}
+
+fn to_json(diff: &WalletDiff) -> Value {
+    let kind = |kind: DiffKind| match kind {
+        DiffKind::Added => "added",
+        DiffKind::Removed => "removed",
+        DiffKind::Changed => "changed",
+    };
+    let field = |change: &FieldChange| {
+        json!({
+            "field": change.field,
+            "old": change.old,
+            "new": change.new,
+        })
+    };
+    let status = |status: SpendStatus| match status {
+        SpendStatus::Unspent => json!({ "status": "unspent" }),
+        SpendStatus::Pending(txid, height) => {
+            json!({ "status": "pending", "txid": txid.to_string(), "height": height })
+        }
+        SpendStatus::Spent(txid, height) => {
+            json!({ "status": "spent", "txid": txid.to_string(), "height": height })
+        }
+    };
+
+    json!({
+        "fields": diff.fields.iter().map(field).collect::<Vec<_>>(),
+        "keys": diff.keys.iter().map(|key| json!({
+            "change": kind(key.kind),
+            "pool": key.pool.to_string(),
+            "address": key.address,
+            "spending": key.spending,
+        })).collect::<Vec<_>>(),
+        "transactions": diff.transactions.iter().map(|tx| json!({
+            "change": kind(tx.kind),
+            "txid": tx.txid.to_string(),
+            "height": tx.height,
+            "fields": tx.fields.iter().map(field).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+        "notes": diff.notes.iter().map(|note| json!({
+            "txid": note.txid.to_string(),
+            "pool": note.pool.to_string(),
+            "index": note.index,
+            "value": note.value,
+            "old": status(note.old),
+            "new": status(note.new),
+        })).collect::<Vec<_>>(),
+    })
+}
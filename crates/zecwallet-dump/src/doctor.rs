@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+use zecwallet_parser::zwl::{
+    ZwlWallet,
+    doctor::{DoctorReport, Finding, Severity, builtin_rules},
+};
+
+use crate::{
+    cli::{DoctorFormat, FailOn},
+    summary::birthday_json,
+};
+
+/// Runs the built-in rules on the wallet and prints the findings, along with the birthday
+/// estimate in the JSON formats. Returns whether the wallet passed, that is, had no finding at
+/// least as severe as `fail_on`.
+pub fn print_doctor(
+    wallet: &ZwlWallet,
+    wallet_file: &Path,
+    format: DoctorFormat,
+    fail_on: FailOn,
+) -> bool {
+    let fail_on = match fail_on {
+        FailOn::Info => Severity::Info,
+        FailOn::Warning => Severity::Warning,
+        FailOn::Error => Severity::Error,
+    };
+    let report = wallet.doctor();
+    let birthday = birthday_json(&wallet.estimate_birthday());
+
+    match format {
+        DoctorFormat::Text => print_text(&report),
+        DoctorFormat::Json => print_json(&json!({
+            "findings": report.findings.iter().map(finding_json).collect::<Vec<_>>(),
+            "birthday": birthday,
+        })),
+        DoctorFormat::Sarif => print_json(&sarif(&report, wallet_file, birthday)),
+    }
+
+    report.max_severity().is_none_or(|s| s < fail_on)
+}
+
+fn print_text(report: &DoctorReport) {
+    if report.findings.is_empty() {
+        println!("{}", "No problems found.".green());
+        return;
+    }
+
+    println!(
+        "{} {} errors, {} warnings, {} notes\n",
+        "Found".bold(),
+        report.count(Severity::Error).red(),
+        report.count(Severity::Warning).yellow(),
+        report.count(Severity::Info)
+    );
+    for finding in &report.findings {
+        let line = finding.to_string();
+        match finding.severity {
+            Severity::Error => println!("{}", line.red()),
+            Severity::Warning => println!("{}", line.yellow()),
+            Severity::Info => println!("{}", line),
+        }
+    }
+}
+
+fn print_json(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).expect("JSON values serialize")
+    );
+}
+
+fn finding_json(finding: &Finding) -> Value {
+    json!({
+        "rule": finding.rule,
+        "severity": finding.severity.to_string(),
+        "subject": finding.subject,
+        "message": finding.message,
+        "fix": finding.fix,
+    })
+}
+
+/// The report as a SARIF 2.1.0 log, with the wallet file as the location of every result, and
+/// the birthday estimate in the run's properties.
+fn sarif(report: &DoctorReport, wallet_file: &Path, birthday: Value) -> Value {
+    let rules: Vec<Value> = builtin_rules()
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id(),
+                "shortDescription": { "text": rule.description() },
+            })
+        })
+        .collect();
+    let results: Vec<Value> = report
+        .findings
+        .iter()
+        .map(|finding| {
+            let level = match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "note",
+            };
+            let mut location = json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": wallet_file.display().to_string() },
+                },
+            });
+            if let Some(subject) = &finding.subject {
+                location["logicalLocations"] = json!([{ "fullyQualifiedName": subject }]);
+            }
+            json!({
+                "ruleId": finding.rule,
+                "level": level,
+                "message": { "text": format!("{}. Fix: {}.", finding.message, finding.fix) },
+                "locations": [location],
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+            "properties": { "birthday": birthday },
+        }],
+    })
+}
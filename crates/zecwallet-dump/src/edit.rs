@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    writer::WalletWriter,
+    zwl::{
+        ZwlWallet,
+        data::{ChainType, MemoDownloadOption},
+    },
+};
+
+/// The changes asked for on the command line.
+pub struct Edits {
+    pub download_memos: Option<MemoDownloadOption>,
+    pub spam_threshold: Option<i64>,
+    pub birthday: Option<u64>,
+    pub chain: Option<ChainType>,
+    pub force_chain: bool,
+}
+
+/// Applies `edits` to the wallet, prints what changed and writes it to
+/// `output`. Nothing is written if any of the values is invalid.
+pub fn edit_wallet(
+    wallet: &mut ZwlWallet,
+    edits: &Edits,
+    output: &Path,
+) -> Result<(), WalletError> {
+    let mut changes = vec![];
+
+    if let Some(download_memos) = edits.download_memos {
+        let options = &mut wallet.wallet_options;
+        changes.push((
+            "download_memos",
+            options.download_memos().to_string(),
+            download_memos.to_string(),
+        ));
+        options.set_download_memos(download_memos);
+    }
+    if let Some(spam_threshold) = edits.spam_threshold {
+        let options = &mut wallet.wallet_options;
+        changes.push((
+            "spam_threshold",
+            options.spam_threshold().to_string(),
+            spam_threshold.to_string(),
+        ));
+        options.set_spam_threshold(spam_threshold)?;
+    }
+    // The chain goes before the birthday, which is checked against its activation heights.
+    if let Some(chain) = edits.chain {
+        changes.push((
+            "chain_name",
+            wallet.chain_name.to_string(),
+            chain.to_string(),
+        ));
+        wallet.set_chain_name(chain, edits.force_chain)?;
+    }
+    if let Some(birthday) = edits.birthday {
+        changes.push((
+            "birthday",
+            wallet.birthday.to_string(),
+            birthday.to_string(),
+        ));
+        wallet.set_birthday(birthday)?;
+    }
+
+    if changes.is_empty() {
+        return Err(WalletError::InvalidFormat("Nothing to edit".to_string()));
+    }
+    for (name, old, new) in changes {
+        if old == new {
+            println!("{}: {} (unchanged)", name.bold(), old);
+        } else {
+            println!("{}: {} -> {}", name.bold(), old.red(), new.green());
+        }
+    }
+
+    WalletWriter::write(output, wallet)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::{fs, io, path::Path};
+
+use serde_json::{Value, json};
+use zecwallet_parser::zwl::{
+    ZwlWallet,
+    graph::{EdgeKind, NodeKind, NoteGraph},
+};
+
+use crate::cli::GraphFormat;
+
+/// Exports the note-flow graph of the wallet to `output`, or prints it.
+pub fn export_graph(
+    wallet: &ZwlWallet,
+    format: GraphFormat,
+    output: Option<&Path>,
+) -> io::Result<()> {
+    let graph = wallet.note_graph();
+    let rendered = match format {
+        GraphFormat::Dot => graph.to_dot(),
+        GraphFormat::Json => {
+            serde_json::to_string_pretty(&to_json(&graph)).expect("JSON values serialize") + "\n"
+        }
+    };
+
+    match output {
+        Some(path) => fs::write(path, rendered),
+        None => {
+            print!("{}", rendered);
+            Ok(())
+        }
+    }
+}
+
+/// The graph in the node-link format of d3 and networkx.
+fn to_json(graph: &NoteGraph) -> Value {
+    let nodes: Vec<Value> = graph
+        .nodes
+        .iter()
+        .map(|node| match &node.kind {
+            NodeKind::Transaction {
+                height,
+                unconfirmed,
+                known,
+            } => json!({
+                "id": node.id,
+                "kind": "transaction",
+                "height": height,
+                "unconfirmed": unconfirmed,
+                "known": known,
+            }),
+            NodeKind::Recipient => json!({ "id": node.id, "kind": "recipient" }),
+            NodeKind::Unspent => json!({ "id": node.id, "kind": "unspent" }),
+        })
+        .collect();
+    let links: Vec<Value> = graph
+        .edges
+        .iter()
+        .map(|edge| match edge.kind {
+            EdgeKind::Note {
+                pool,
+                is_change,
+                pending,
+            } => json!({
+                "source": edge.source,
+                "target": edge.target,
+                "value": edge.value,
+                "kind": "note",
+                "pool": pool.to_string(),
+                "is_change": is_change,
+                "pending": pending,
+            }),
+            EdgeKind::Outgoing => json!({
+                "source": edge.source,
+                "target": edge.target,
+                "value": edge.value,
+                "kind": "outgoing",
+            }),
+        })
+        .collect();
+
+    json!({
+        "directed": true,
+        "multigraph": true,
+        "nodes": nodes,
+        "links": links,
+    })
+}
@@ -0,0 +1,44 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::zwl::{ZwlWallet, history::TxStatus};
+
+/// Prints the wallet's transactions, oldest first, legacy mempool ones included.
+pub fn print_history(wallet: &ZwlWallet) {
+    let history = wallet.history();
+    if history.is_empty() {
+        println!("No transactions found in wallet.");
+        return;
+    }
+
+    match wallet.latest_height() {
+        Some(height) => println!("{} {}", "Latest block:".bold(), height),
+        None => println!("{}", "No latest block; expiry can't be checked.".yellow()),
+    }
+    println!(
+        "{} {} {}\n",
+        "Found".bold(),
+        history.len().bold().red(),
+        "transactions:".bold()
+    );
+
+    for entry in &history {
+        let status = match entry.status {
+            TxStatus::Confirmed => entry.status.green().to_string(),
+            TxStatus::Pending => entry.status.yellow().to_string(),
+            TxStatus::Expired => entry.status.red().to_string(),
+        };
+        println!(
+            "- {} {} {} net {} (received {}, spent {}){}",
+            entry.height.bright_green(),
+            entry.txid,
+            status,
+            entry.net(),
+            entry.received,
+            entry.spent,
+            if entry.from_mempool {
+                " [legacy mempool]"
+            } else {
+                ""
+            }
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fs, path::Path};
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    writer::WalletWriter,
+    zwl::{
+        ZwlWallet,
+        import::{ImportedKey, decrypt_seed},
+    },
+};
+
+use crate::password::read_password;
+
+/// Imports `keys` and the keys in `file`, one per line, and writes the wallet
+/// to `output`. The password of an encrypted wallet is read from stdin. Keys
+/// that can't be imported are reported, and the others are still written.
+pub fn import_keys(
+    wallet: &mut ZwlWallet,
+    keys: &[String],
+    file: Option<&Path>,
+    output: &Path,
+) -> Result<(), WalletError> {
+    let mut encoded = keys.to_vec();
+    if let Some(file) = file {
+        encoded.extend(
+            fs::read_to_string(file)?
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from),
+        );
+    }
+    if encoded.is_empty() {
+        return Err(WalletError::InvalidFormat("No keys to import".to_string()));
+    }
+
+    let network = wallet.chain_name.require_network()?;
+    let password = if wallet.keys.encrypted {
+        let password = read_password("Enter the wallet password:")?;
+        decrypt_seed(&wallet.keys, Some(&password))?;
+        Some(password)
+    } else {
+        None
+    };
+
+    let mut rejected = 0;
+    for (i, key) in encoded.iter().enumerate() {
+        let imported = ImportedKey::decode(&network, key)
+            .and_then(|key| wallet.import_key(key, password.as_deref()));
+        match imported {
+            Ok(address) => println!("{} {}", "Imported".bold(), address),
+            Err(e) => {
+                rejected += 1;
+                println!("{} key {}: {}", "Rejected".red().bold(), i + 1, e);
+            }
+        }
+    }
+
+    if rejected < encoded.len() {
+        WalletWriter::write(output, wallet)?;
+        println!("\n{} {}", "Wrote".bold(), output.display());
+        println!("Rescan the wallet to find the transactions of the imported keys.");
+    }
+    if rejected > 0 {
+        return Err(WalletError::InvalidFormat(format!(
+            "{} of {} keys were not imported",
+            rejected,
+            encoded.len()
+        )));
+    }
+
+    Ok(())
+}
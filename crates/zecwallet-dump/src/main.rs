@@ -1,7 +1,34 @@
+mod addresses;
+mod anonymize;
+mod balances;
+mod blocks;
 mod cli;
 mod config;
+mod diff;
+mod doctor;
+mod edit;
+mod graph;
+mod history;
+mod import;
+mod merge;
+mod orchard_tree;
+mod password;
+mod privacy;
+mod repair;
+mod restore;
+mod rewind;
+mod scan;
+#[cfg(feature = "online")]
+mod serve;
 mod summary;
+mod sync;
 mod tracing;
+mod trim;
+#[cfg(feature = "online")]
+mod verify;
+mod watch_only;
+mod whose;
+mod witnesses;
 
 use std::process;
 
@@ -22,7 +49,28 @@ fn main() {
         println!("Value for config: {}", config_path.display());
     }
 
-    let wallet = match WalletReader::read(&cli.wallet_file) {
+    // Restoring creates the wallet file instead of reading it.
+    if let Some(Commands::Restore {
+        addresses,
+        chain,
+        birthday,
+        server,
+    }) = &cli.command
+    {
+        if let Err(e) = restore::restore_wallet(
+            &cli.wallet_file,
+            *addresses,
+            *chain,
+            *birthday,
+            server.as_deref(),
+        ) {
+            eprintln!("Error restoring wallet: {e}");
+            process::exit(1);
+        }
+        return;
+    }
+
+    let mut wallet = match WalletReader::read(&cli.wallet_file) {
         Ok(w) => w,
         Err(e) => {
             eprintln!("Error reading wallet: {e:?}");
@@ -34,5 +82,179 @@ fn main() {
         Some(Commands::Summarize) | None => {
             summary::print_summary(&wallet, cli.debug);
         }
+        Some(Commands::Blocks) => {
+            blocks::print_blocks(&wallet);
+        }
+        Some(Commands::History) => {
+            history::print_history(&wallet);
+        }
+        Some(Commands::Scan) => {
+            if let Err(e) = scan::print_scan(&wallet) {
+                eprintln!("Error scanning blocks: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Witnesses) => {
+            if let Err(e) = witnesses::print_witnesses(&wallet) {
+                eprintln!("Error checking witnesses: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::OrchardTree) => {
+            if let Err(e) = orchard_tree::print_orchard_tree(&wallet) {
+                eprintln!("Error inspecting orchard tree: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Sync {
+            blocks,
+            transactions,
+            output,
+        }) => {
+            if let Err(e) = sync::sync_wallet(&mut wallet, blocks, transactions.as_deref(), output)
+            {
+                eprintln!("Error syncing wallet: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Repair { output, dry_run }) => {
+            let output = output.as_deref().unwrap_or(&cli.wallet_file);
+            if let Err(e) = repair::repair_wallet(&mut wallet, output, *dry_run) {
+                eprintln!("Error repairing wallet: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Restore { .. }) => unreachable!(),
+        Some(Commands::Rewind { height, output }) => {
+            if let Err(e) = rewind::rewind_wallet(&mut wallet, *height, output) {
+                eprintln!("Error rewinding wallet: {e}");
+                process::exit(1);
+            }
+        }
+        #[cfg(feature = "online")]
+        Some(Commands::Trim {
+            output,
+            checkpoint: Some(height),
+            server: Some(server),
+        }) => {
+            if let Err(e) = trim::trim_wallet_to_checkpoint(&mut wallet, output, server, *height) {
+                eprintln!("Error trimming wallet: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Trim { output, .. }) => {
+            if let Err(e) = trim::trim_wallet(&mut wallet, output) {
+                eprintln!("Error trimming wallet: {e}");
+                process::exit(1);
+            }
+        }
+        #[cfg(feature = "online")]
+        Some(Commands::Verify {
+            server,
+            start,
+            from_birthday,
+        }) => {
+            let start = if *from_birthday {
+                Some(wallet.birthday)
+            } else {
+                *start
+            };
+            if let Err(e) = verify::print_verify(&wallet, server, start) {
+                eprintln!("Error verifying wallet: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::WatchOnly { output }) => {
+            if let Err(e) = watch_only::write_watch_only(&mut wallet, output) {
+                eprintln!("Error making wallet watch-only: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Edit {
+            download_memos,
+            spam_threshold,
+            birthday,
+            chain,
+            force_chain,
+            output,
+        }) => {
+            let output = output.as_deref().unwrap_or(&cli.wallet_file);
+            let edits = edit::Edits {
+                download_memos: download_memos.map(Into::into),
+                spam_threshold: *spam_threshold,
+                birthday: *birthday,
+                chain: chain.map(Into::into),
+                force_chain: *force_chain,
+            };
+            if let Err(e) = edit::edit_wallet(&mut wallet, &edits, output) {
+                eprintln!("Error editing wallet: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Import { keys, file, output }) => {
+            let output = output.as_deref().unwrap_or(&cli.wallet_file);
+            if let Err(e) = import::import_keys(&mut wallet, keys, file.as_deref(), output) {
+                eprintln!("Error importing keys: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Anonymize {
+            output,
+            map,
+            keep_blocks,
+        }) => {
+            if let Err(e) = anonymize::write_anonymized(&wallet, output, map, *keep_blocks) {
+                eprintln!("Error anonymizing wallet: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Diff { other, format }) => {
+            if let Err(e) = diff::print_diff(&wallet, other, *format) {
+                eprintln!("Error comparing wallets: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Doctor { format, fail_on }) => {
+            if !doctor::print_doctor(&wallet, &cli.wallet_file, *format, *fail_on) {
+                process::exit(2);
+            }
+        }
+        Some(Commands::Privacy) => {
+            privacy::print_privacy(&wallet);
+        }
+        Some(Commands::Balances) => {
+            balances::print_balances(&wallet);
+        }
+        Some(Commands::Addresses { count }) => {
+            if let Err(e) = addresses::print_addresses(&wallet, *count) {
+                eprintln!("Error deriving addresses: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Whose { address }) => {
+            if let Err(e) = whose::print_owner(&wallet, address) {
+                eprintln!("Error finding the owner: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Graph { format, output }) => {
+            if let Err(e) = graph::export_graph(&wallet, *format, output.as_deref()) {
+                eprintln!("Error exporting the graph: {e}");
+                process::exit(1);
+            }
+        }
+        Some(Commands::Merge { others, output }) => {
+            if let Err(e) = merge::merge_wallets(&mut wallet, others, output.as_deref()) {
+                eprintln!("Error merging wallets: {e}");
+                process::exit(1);
+            }
+        }
+        #[cfg(feature = "online")]
+        Some(Commands::ServeLwd { listen }) => {
+            if let Err(e) = serve::serve_lwd(&wallet, listen) {
+                eprintln!("Error serving wallet: {e}");
+                process::exit(1);
+            }
+        }
     }
 }
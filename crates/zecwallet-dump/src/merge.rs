@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError, reader::WalletReader, writer::WalletWriter, zwl::ZwlWallet,
+};
+
+/// Merges the wallets at `others` into `wallet`, one after the other, and writes the result to
+/// `output` if given.
+pub fn merge_wallets(
+    wallet: &mut ZwlWallet,
+    others: &[PathBuf],
+    output: Option<&Path>,
+) -> Result<(), WalletError> {
+    let mut conflicts = 0;
+    for path in others {
+        let other = WalletReader::read(path)?;
+        let report = wallet.merge(&other)?;
+        conflicts += report.conflicts.len();
+
+        println!("{} {}\n", "Merged".bold(), path.display());
+        for line in report.to_string().lines() {
+            if line.starts_with('!') {
+                println!("{}", line.red());
+            } else if line.starts_with('~') {
+                println!("{}", line.yellow());
+            } else {
+                println!("{}", line.green());
+            }
+        }
+        println!();
+    }
+
+    if conflicts == 0 {
+        println!("{}", "No conflicts.".green());
+    } else {
+        println!(
+            "{} {} conflicts, resolved by keeping the first wallet's side.",
+            "Found".bold(),
+            conflicts.red()
+        );
+    }
+
+    match output {
+        Some(output) => {
+            WalletWriter::write(output, wallet)?;
+            println!("\n{} {}", "Wrote".bold(), output.display());
+        }
+        None => println!("\n{}", "No output given, nothing written.".yellow()),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,94 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    zwl::{ZwlWallet, orchard_tree::OrchardPathStatus},
+};
+
+/// Prints the size, root, checkpoints and marked positions of the wallet's
+/// Orchard witness tree, along with the authentication path status of every
+/// unspent Orchard note.
+pub fn print_orchard_tree(wallet: &ZwlWallet) -> Result<(), WalletError> {
+    let report = match wallet.inspect_orchard_tree()? {
+        Some(report) => report,
+        None => {
+            println!("Wallet has no Orchard witness tree.");
+            return Ok(());
+        }
+    };
+
+    println!("{} {}", "Size:".bold(), report.size.bright_green());
+    println!("{} {}", "Root:".bold(), hex::encode(report.root));
+    println!(
+        "{} {} (max {})",
+        "Checkpoints:".bold(),
+        report.checkpoints.len().red().bold(),
+        report.max_checkpoints
+    );
+    for checkpoint in &report.checkpoints {
+        println!(
+            "- #{} depth {}, bridges {}, witnessed {}, root {}",
+            checkpoint.index,
+            checkpoint.depth,
+            checkpoint.bridges_len,
+            checkpoint.is_witnessed,
+            checkpoint
+                .root
+                .map(hex::encode)
+                .unwrap_or_else(|| "-".to_string())
+        );
+    }
+
+    println!(
+        "{} {:?}\n",
+        "Marked positions:".bold(),
+        report.marked_positions
+    );
+
+    match &report.frontier {
+        Some(frontier) => {
+            let matching = match frontier.matching_depth {
+                Some(depth) => format!("matches checkpoint depth {}", depth)
+                    .green()
+                    .to_string(),
+                None => "does not match any checkpoint".red().to_string(),
+            };
+            println!(
+                "{} height {}, size {}, root {} {}\n",
+                "Verified Orchard frontier:".bold(),
+                frontier.tree_height,
+                frontier.frontier_size,
+                hex::encode(frontier.frontier_root),
+                matching
+            );
+        }
+        None => println!("{}\n", "Wallet has no verified Orchard frontier.".yellow()),
+    }
+
+    if report.notes.is_empty() {
+        println!("No unspent Orchard notes with a spending key.");
+        return Ok(());
+    }
+
+    for note in &report.notes {
+        let status = match note.status {
+            OrchardPathStatus::Valid => "valid path".green().to_string(),
+            OrchardPathStatus::NoPosition => "no witness position".red().to_string(),
+            OrchardPathStatus::NotMarked => "position not marked".red().to_string(),
+            OrchardPathStatus::NoPath => "no authentication path".red().to_string(),
+            OrchardPathStatus::Invalid => "path doesn't match root".red().to_string(),
+        };
+
+        println!(
+            "- {} #{} ({} zats, position {}): {}",
+            note.txid,
+            note.note_index,
+            note.value,
+            note.position
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            status
+        );
+    }
+
+    Ok(())
+}
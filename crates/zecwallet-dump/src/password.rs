@@ -0,0 +1,59 @@
+use std::io::{self, BufRead, IsTerminal};
+
+/// Prints `prompt` and reads the wallet password from stdin, without echoing it if stdin is a
+/// terminal.
+pub fn read_password(prompt: &str) -> io::Result<String> {
+    eprintln!("{}", prompt);
+    let mut password = String::new();
+    {
+        let _echo = EchoOff::new()?;
+        io::stdin().lock().read_line(&mut password)?;
+    }
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Turns the terminal echo off until dropped, keeping the newline.
+#[cfg(unix)]
+struct EchoOff(Option<rustix::termios::Termios>);
+
+#[cfg(unix)]
+impl EchoOff {
+    fn new() -> io::Result<Self> {
+        use rustix::termios::{LocalModes, OptionalActions, tcgetattr, tcsetattr};
+
+        let stdin = io::stdin();
+        if !stdin.is_terminal() {
+            return Ok(EchoOff(None));
+        }
+        let termios = tcgetattr(&stdin)?;
+        let mut silent = termios.clone();
+        silent.local_modes.remove(LocalModes::ECHO);
+        silent.local_modes.insert(LocalModes::ECHONL);
+        tcsetattr(&stdin, OptionalActions::Now, &silent)?;
+        Ok(EchoOff(Some(termios)))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for EchoOff {
+    fn drop(&mut self) {
+        if let Some(termios) = &self.0 {
+            let _ = rustix::termios::tcsetattr(
+                io::stdin(),
+                rustix::termios::OptionalActions::Now,
+                termios,
+            );
+        }
+    }
+}
+
+/// Other platforms keep the echo.
+#[cfg(not(unix))]
+struct EchoOff;
+
+#[cfg(not(unix))]
+impl EchoOff {
+    fn new() -> io::Result<Self> {
+        Ok(EchoOff)
+    }
+}
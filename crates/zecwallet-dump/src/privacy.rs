@@ -0,0 +1,31 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::zwl::ZwlWallet;
+
+/// Prints the privacy leaks of the wallet's history, and the transactions whose coins should be
+/// treated carefully.
+pub fn print_privacy(wallet: &ZwlWallet) {
+    let report = wallet.privacy_audit();
+    if report.is_empty() {
+        println!("{}", "No privacy leaks found.".green());
+        return;
+    }
+
+    println!(
+        "{} {} {}\n",
+        "Found".bold(),
+        report.items.len().bold().red(),
+        "privacy leaks:".bold()
+    );
+    for item in &report.items {
+        println!("- {}", item.issue.yellow());
+        for txid in &item.txids {
+            println!("    {}", txid);
+        }
+    }
+
+    println!(
+        "\n{} {}",
+        report.txids().len().bold().red(),
+        "transactions involved, treat their coins carefully.".bold()
+    );
+}
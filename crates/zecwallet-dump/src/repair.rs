@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{error::WalletError, writer::WalletWriter, zwl::ZwlWallet};
+
+/// Clears the wallet's stale unconfirmed spends and writes the repaired wallet
+/// to `output`. With `dry_run`, only prints what would change.
+pub fn repair_wallet(
+    wallet: &mut ZwlWallet,
+    output: &Path,
+    dry_run: bool,
+) -> Result<(), WalletError> {
+    let report = if dry_run {
+        wallet.find_stale_spends()
+    } else {
+        wallet.repair_stale_spends()
+    };
+
+    match report.latest_height {
+        Some(height) => println!("{} {}", "Latest block:".bold(), height),
+        None => println!(
+            "{}",
+            "No latest block; only markers of unknown transactions are checked.".yellow()
+        ),
+    }
+
+    if report.is_empty() {
+        println!("{}", "No stale unconfirmed spends found.".green());
+        return Ok(());
+    }
+
+    println!(
+        "{} {} stale spends ({} zats), {} expired transactions\n",
+        "Found".bold(),
+        report.stale_spends.len().red(),
+        report.freed_value(),
+        report.expired_txs.len().red()
+    );
+    for line in report.to_string().lines() {
+        if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line.green());
+        }
+    }
+
+    if dry_run {
+        println!("\n{}", "Dry run, nothing written.".yellow());
+        return Ok(());
+    }
+
+    WalletWriter::write(output, wallet)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::{
+    io::{self, BufRead},
+    path::Path,
+};
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, data::ChainType},
+};
+
+use crate::cli::Chain;
+
+/// Reads a seed phrase from stdin, creates a wallet from it and writes it to
+/// `output`, which must not exist yet. With `server`, the wallet starts from
+/// the tree state the lightwalletd server reports just below its birthday.
+pub fn restore_wallet(
+    output: &Path,
+    addresses: u32,
+    chain: Chain,
+    birthday: u64,
+    server: Option<&str>,
+) -> Result<(), WalletError> {
+    // Checked before asking for the seed phrase, the wallet is then written
+    // atomically.
+    if output.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", output.display()),
+        )
+        .into());
+    }
+    let wallet = create_wallet(addresses, chain, birthday, server)?;
+    WalletWriter::write(output, &wallet)?;
+
+    println!(
+        "{} a {} wallet with {} addresses per pool, birthday {}",
+        "Created".bold(),
+        wallet.chain_name.bright_green(),
+        addresses,
+        wallet.birthday
+    );
+    for key in &wallet.keys.tkeys {
+        println!("- {}", key.address);
+    }
+    if let Some(tree_state) = &wallet.verified_tree {
+        println!("Starts from the tree state at {}", tree_state.height);
+    }
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
+
+fn create_wallet(
+    addresses: u32,
+    chain: Chain,
+    birthday: u64,
+    server: Option<&str>,
+) -> Result<ZwlWallet, WalletError> {
+    eprintln!("Enter the seed phrase:");
+    let mut phrase = String::new();
+    io::stdin().lock().read_line(&mut phrase)?;
+
+    let chain_name = ChainType::from(chain);
+
+    #[cfg(feature = "online")]
+    let tree_state = match server {
+        Some(server) => {
+            use zecwallet_parser::lightwalletd::client::LightwalletdClient;
+
+            let runtime = tokio::runtime::Runtime::new()?;
+            Some(runtime.block_on(async {
+                let mut client = LightwalletdClient::connect(server).await?;
+                // The wallet scans from the birthday, so it needs the tree before it.
+                client.tree_state(birthday.saturating_sub(1)).await
+            })?)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "online"))]
+    let tree_state = match server {
+        Some(_) => {
+            return Err(WalletError::InvalidFormat(
+                "Fetching a tree state needs the online feature".to_string(),
+            ));
+        }
+        None => None,
+    };
+
+    ZwlWallet::from_seed_phrase(phrase.trim(), addresses, chain_name, birthday, tree_state)
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{error::WalletError, writer::WalletWriter, zwl::ZwlWallet};
+
+/// Rewinds the wallet to `height`, prints what was undone and writes the
+/// rewound wallet to `output`.
+pub fn rewind_wallet(
+    wallet: &mut ZwlWallet,
+    height: u64,
+    output: &Path,
+) -> Result<(), WalletError> {
+    let report = wallet.rewind_to(height)?;
+
+    println!(
+        "{} {} blocks ({} -> {})",
+        "Rewound".bold(),
+        report.blocks_removed.bright_green(),
+        report.from_height,
+        report.to_height
+    );
+
+    println!(
+        "\n{} {}",
+        "Removed transactions:".bold(),
+        report.removed_txs.len().red()
+    );
+    for txid in &report.removed_txs {
+        println!("- {}", txid);
+    }
+    println!(
+        "{} {}",
+        "Notes and Utxos unspent again:".bold(),
+        report.unspent
+    );
+    if let Some(tree_height) = report.cleared_verified_tree {
+        println!(
+            "{} {}",
+            "Cleared the verified tree at".bold(),
+            tree_height.yellow()
+        );
+    }
+
+    WalletWriter::write(output, wallet)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    zwl::{ZwlWallet, scan::DecryptedNote},
+};
+
+/// Prints the notes found by trial-decrypting the cached compact blocks, and
+/// how they compare with the notes recorded in the wallet.
+pub fn print_scan(wallet: &ZwlWallet) -> Result<(), WalletError> {
+    let report = wallet.scan_cached_blocks()?;
+
+    match report.scanned_range {
+        Some((low, high)) => println!(
+            "{} {} blocks ({} - {})",
+            "Scanned".bold(),
+            report.blocks_scanned.bright_green(),
+            low,
+            high
+        ),
+        None => {
+            println!("No cached blocks found in wallet.");
+            return Ok(());
+        }
+    }
+
+    if !report.undecodable_blocks.is_empty() {
+        println!(
+            "{} {:?}",
+            "Undecodable blocks:".bold().red(),
+            report.undecodable_blocks
+        );
+    }
+
+    println!(
+        "\n{} {}",
+        "Matched notes:".bold(),
+        report.matched.len().green()
+    );
+    report.matched.iter().for_each(print_note);
+
+    println!(
+        "\n{} {}",
+        "Missing notes:".bold(),
+        report.missing.len().red().bold()
+    );
+    report.missing.iter().for_each(print_note);
+
+    println!(
+        "\n{} {}",
+        "Orphan notes:".bold(),
+        report.orphans.len().red().bold()
+    );
+    for orphan in &report.orphans {
+        println!(
+            "- {} {} {} #{}: {} zats",
+            orphan.height, orphan.pool, orphan.txid, orphan.note_index, orphan.value
+        );
+    }
+
+    if report.is_consistent() {
+        println!("\n{}", "Wallet agrees with the cached blocks.".green());
+    }
+
+    Ok(())
+}
+
+fn print_note(note: &DecryptedNote) {
+    println!(
+        "- {} {} {} #{} (key {}): {} zats",
+        note.height, note.pool, note.txid, note.output_index, note.key_index, note.value
+    );
+}
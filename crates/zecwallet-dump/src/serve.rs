@@ -0,0 +1,39 @@
+use owo_colors::OwoColorize;
+use tokio::net::TcpListener;
+use zecwallet_parser::{
+    error::WalletError,
+    lightwalletd::mock::{MockChain, MockLightwalletd},
+    zwl::ZwlWallet,
+};
+
+/// Serves the wallet's cached blocks and tree states as a lightwalletd
+/// `CompactTxStreamer` on `listen`, until interrupted.
+pub fn serve_lwd(wallet: &ZwlWallet, listen: &str) -> Result<(), WalletError> {
+    let chain = MockChain::from_wallet(wallet)?;
+
+    match (chain.blocks.keys().next(), chain.blocks.keys().next_back()) {
+        (Some(low), Some(high)) => println!("{} blocks {} - {}", "Serving".bold(), low, high),
+        _ => println!("{}", "The wallet has no cached blocks to serve.".yellow()),
+    }
+    match (
+        chain.tree_states.keys().next(),
+        chain.tree_states.keys().next_back(),
+    ) {
+        (Some(low), Some(high)) => println!("{} {} - {}", "Tree states:".bold(), low, high),
+        _ => println!(
+            "{}",
+            "No tree states: the wallet has no verified tree.".yellow()
+        ),
+    }
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(async {
+        let listener = TcpListener::bind(listen).await?;
+        println!(
+            "{} {}",
+            "Listening on".bold(),
+            listener.local_addr()?.bright_green()
+        );
+        MockLightwalletd::new(chain).serve(listener).await
+    })
+}
@@ -1,5 +1,9 @@
 use owo_colors::OwoColorize;
-use zecwallet_parser::zwl::ZwlWallet;
+use serde_json::{Value, json};
+use zecwallet_parser::zwl::{
+    ZwlWallet,
+    birthday::{BirthdayEstimate, BirthdaySource},
+};
 
 /// Prints the summary of the given wallet.
 /// Output varies whether in `debug` mode or `standard` mode.
@@ -25,6 +29,7 @@ use zecwallet_parser::zwl::ZwlWallet;
 ///
 pub fn print_summary(wallet: &ZwlWallet, _debug: u8) {
     print_header(wallet);
+    print_birthday(wallet);
     print_key_summary(wallet);
 }
 
@@ -37,6 +42,43 @@ fn print_header(wallet: &ZwlWallet) {
     );
 }
 
+fn print_birthday(wallet: &ZwlWallet) {
+    let estimate = wallet.estimate_birthday();
+
+    println!("{} {}", "Birthday:".bold(), estimate.stored.red().bold());
+    if estimate.changes_birthday() {
+        println!(
+            "{} {}",
+            "Suggested birthday:".bold(),
+            estimate.suggested.bright_green().bold()
+        );
+    }
+    for (source, height) in &estimate.evidence {
+        println!("- {} {}", format!("{}:", source).green(), height);
+    }
+    println!("{}\n", estimate.explanation.italic());
+}
+
+/// The birthday estimate, for the JSON outputs.
+pub fn birthday_json(estimate: &BirthdayEstimate) -> Value {
+    json!({
+        "stored": estimate.stored,
+        "suggested": estimate.suggested,
+        "sapling_activation": estimate.sapling_activation,
+        "evidence": estimate.evidence.iter().map(|(source, height)| json!({
+            "source": match source {
+                BirthdaySource::FirstTransaction => "first-transaction",
+                BirthdaySource::FirstSaplingWitness => "first-sapling-witness",
+                BirthdaySource::LowestCachedBlock => "lowest-cached-block",
+                BirthdaySource::VerifiedTree => "verified-tree",
+            },
+            "height": height,
+        })).collect::<Vec<_>>(),
+        "imported_keys": estimate.imported_keys,
+        "explanation": estimate.explanation,
+    })
+}
+
 fn print_key_summary(wallet: &ZwlWallet) {
     let key_count = wallet.keys.okeys.len() + wallet.keys.zkeys.len() + wallet.keys.tkeys.len();
 
@@ -54,8 +96,7 @@ fn print_key_summary(wallet: &ZwlWallet) {
 
     if !wallet.keys.okeys.is_empty() {
         println!(
-            "{} {} {}",
-            "-",
+            "- {} {}",
             "Orchard:".bold().green(),
             wallet.keys.okeys.len().red().bold()
         );
@@ -63,8 +104,7 @@ fn print_key_summary(wallet: &ZwlWallet) {
 
     if !wallet.keys.zkeys.is_empty() {
         println!(
-            "{} {} {}",
-            "-",
+            "- {} {}",
             "Sapling:".bold().green(),
             wallet.keys.zkeys.len().red().bold()
         );
@@ -72,8 +112,7 @@ fn print_key_summary(wallet: &ZwlWallet) {
 
     if !wallet.keys.tkeys.is_empty() {
         println!(
-            "{} {} {}",
-            "-",
+            "- {} {}",
             "Transparent:".bold().green(),
             wallet.keys.tkeys.len().red().bold()
         );
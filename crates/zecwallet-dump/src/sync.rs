@@ -0,0 +1,89 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{error::WalletError, writer::WalletWriter, zwl::ZwlWallet};
+
+/// Syncs the wallet with the compact blocks at `blocks` and the raw transparent
+/// transactions at `transactions`, prints what changed and writes the synced wallet
+/// to `output`.
+pub fn sync_wallet(
+    wallet: &mut ZwlWallet,
+    blocks: &Path,
+    transactions: Option<&Path>,
+    output: &Path,
+) -> Result<(), WalletError> {
+    let report = wallet.sync_from_path(blocks, transactions)?;
+
+    println!(
+        "{} {} blocks ({} - {}), skipped {} known blocks",
+        "Synced".bold(),
+        report.blocks_scanned.bright_green(),
+        report.start_height,
+        report.end_height,
+        report.blocks_skipped
+    );
+
+    println!(
+        "\n{} {}",
+        "Received notes:".bold(),
+        report.received.len().green()
+    );
+    for note in &report.received {
+        println!(
+            "- {} {} {}: {} zats{}",
+            note.height,
+            note.pool,
+            note.txid,
+            note.value,
+            if note.is_change { " (change)" } else { "" }
+        );
+    }
+
+    println!("\n{} {}", "Spent notes:".bold(), report.spent.len().green());
+    for note in &report.spent {
+        println!(
+            "- {} {} {} spent note from {}: {} zats",
+            note.height, note.pool, note.txid, note.note_txid, note.value
+        );
+    }
+
+    println!(
+        "\n{} {}",
+        "Received UTXOs:".bold(),
+        report.received_utxos.len().green()
+    );
+    for utxo in &report.received_utxos {
+        println!(
+            "- {} {}:{} to {}: {} zats",
+            utxo.height, utxo.txid, utxo.output_index, utxo.address, utxo.value
+        );
+    }
+
+    println!(
+        "\n{} {}",
+        "Spent UTXOs:".bold(),
+        report.spent_utxos.len().green()
+    );
+    for utxo in &report.spent_utxos {
+        println!(
+            "- {} {} spent {}:{}: {} zats",
+            utxo.height, utxo.txid, utxo.utxo_txid, utxo.output_index, utxo.value
+        );
+    }
+
+    if !report.stale_witnesses.is_empty() {
+        println!(
+            "\n{} {}",
+            "Stale Sapling witnesses:".bold().red(),
+            report.stale_witnesses.len().red()
+        );
+        for (txid, note_index) in &report.stale_witnesses {
+            println!("- {} #{}", txid, note_index);
+        }
+    }
+
+    WalletWriter::write(output, wallet)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
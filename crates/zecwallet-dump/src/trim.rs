@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{error::WalletError, writer::WalletWriter, zwl::ZwlWallet};
+
+/// Wipes the wallet's sync state, keeping its keys, and writes the result to
+/// `output`.
+pub fn trim_wallet(wallet: &mut ZwlWallet, output: &Path) -> Result<(), WalletError> {
+    wallet.trim_for_rescan(None)?;
+
+    println!(
+        "{} the wallet will rescan from its birthday {}",
+        "Cleared sync state,".bold(),
+        wallet.birthday.bright_green()
+    );
+
+    write_trimmed(wallet, output)
+}
+
+/// Resets the wallet's sync state to the tree state the lightwalletd `server`
+/// reports at `height`, keeping its keys, and writes the result to `output`.
+#[cfg(feature = "online")]
+pub fn trim_wallet_to_checkpoint(
+    wallet: &mut ZwlWallet,
+    output: &Path,
+    server: &str,
+    height: u64,
+) -> Result<(), WalletError> {
+    use zecwallet_parser::lightwalletd::client::LightwalletdClient;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let tree_state = runtime.block_on(async {
+        let mut client = LightwalletdClient::connect(server).await?;
+        client.tree_state(height).await
+    })?;
+
+    let birthday = wallet.birthday;
+    wallet.trim_for_rescan(Some(&tree_state))?;
+
+    println!(
+        "{} at checkpoint {} ({}), the wallet will rescan from {}",
+        "Reset sync state".bold(),
+        tree_state.height.bright_green(),
+        tree_state.hash,
+        wallet.rescan_start_height().bright_green()
+    );
+    if tree_state.height >= birthday {
+        println!(
+            "{}",
+            "Notes received between the birthday and the checkpoint won't be found.".yellow()
+        );
+    }
+
+    write_trimmed(wallet, output)
+}
+
+fn write_trimmed(wallet: &ZwlWallet, output: &Path) -> Result<(), WalletError> {
+    WalletWriter::write(output, wallet)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError, lightwalletd::client::LightwalletdClient, zwl::ZwlWallet,
+};
+
+/// Verifies the wallet against the lightwalletd server at `server` and prints
+/// what differs between the recorded and the actual state.
+pub fn print_verify(
+    wallet: &ZwlWallet,
+    server: &str,
+    start: Option<u64>,
+) -> Result<(), WalletError> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    let report = runtime.block_on(async {
+        let mut client = LightwalletdClient::connect(server).await?;
+        wallet.verify_online(&mut client, start).await
+    })?;
+
+    println!("{} {}", "Server tip:".bold(), report.server_tip);
+    match report.scanned_range {
+        Some((low, high)) => println!(
+            "{} {} blocks ({} - {})",
+            "Scanned".bold(),
+            report.blocks_scanned.bright_green(),
+            low,
+            high
+        ),
+        None => println!("Nothing to scan below the server tip."),
+    }
+
+    println!(
+        "\n{} {}",
+        "Reorged blocks:".bold(),
+        report.reorged_blocks.len().red().bold()
+    );
+    for block in &report.reorged_blocks {
+        println!(
+            "- {}: recorded {}, server has {}",
+            block.height, block.recorded_hash, block.actual_hash
+        );
+    }
+
+    if !report.blocks_above_tip.is_empty() {
+        println!(
+            "\n{} {:?}",
+            "Cached blocks above the server tip:".bold().red(),
+            report.blocks_above_tip
+        );
+    }
+
+    println!("\n{} {}", "Notes checked:".bold(), report.notes.len());
+    for note in report.notes.iter().filter(|n| !n.is_consistent()) {
+        println!(
+            "- {} {} #{} ({} zats): recorded {}, actually {}",
+            note.pool,
+            note.txid,
+            note.note_index,
+            note.value,
+            note.recorded.yellow(),
+            note.actual.red()
+        );
+    }
+
+    println!("\n{}", "Transparent addresses:".bold());
+    for address in &report.addresses {
+        println!(
+            "- {}: {} recorded, {} on chain",
+            address.address,
+            address.recorded.len(),
+            address.actual.len()
+        );
+        for txid in address.missing() {
+            println!("  {} {}", "missing".red(), txid);
+        }
+        for txid in address.unexpected() {
+            println!("  {} {}", "not on chain".yellow(), txid);
+        }
+    }
+
+    if report.is_consistent() {
+        println!("\n{}", "Wallet agrees with the server.".green());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    writer::WalletWriter,
+    zwl::{ZwlWallet, watch_only::find_secrets},
+};
+
+/// Strips every secret from the wallet and writes the watch-only result to
+/// `output`, after checking that none of the original secret bytes remain.
+pub fn write_watch_only(wallet: &mut ZwlWallet, output: &Path) -> Result<(), WalletError> {
+    let secrets = wallet.secrets();
+    let report = wallet.make_watch_only();
+
+    println!(
+        "{} {} Sapling keys, {} Orchard keys, stripped {} transparent keys{}",
+        "Converted".bold(),
+        report.zkeys_converted,
+        report.okeys_converted,
+        report.tkeys_stripped,
+        if report.seed_removed {
+            ", removed the seed"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "{} {} notes as not spendable",
+        "Marked".bold(),
+        report.notes_updated
+    );
+
+    let bytes = WalletWriter::to_bytes(wallet)?;
+    let leaked = find_secrets(&secrets, &bytes);
+    if !leaked.is_empty() || !wallet.secrets().is_empty() {
+        let labels: Vec<&str> = leaked.iter().map(|s| s.label.as_str()).collect();
+        return Err(WalletError::InvalidFormat(format!(
+            "Secrets remain in the watch-only wallet: {}",
+            labels.join(", ")
+        )));
+    }
+    println!(
+        "{} none of the {} secrets remain in the file.",
+        "Checked:".bold(),
+        secrets.len().green()
+    );
+
+    WalletWriter::write(output, wallet)?;
+    println!("\n{} {}", "Wrote".bold(), output.display());
+
+    Ok(())
+}
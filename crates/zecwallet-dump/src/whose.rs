@@ -0,0 +1,23 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::{
+    error::WalletError,
+    zwl::{ZwlWallet, scan::Pool},
+};
+
+/// Prints which key of the wallet `address` belongs to, if any.
+pub fn print_owner(wallet: &ZwlWallet, address: &str) -> Result<(), WalletError> {
+    match wallet.find_owner(address)? {
+        Some(owner) => {
+            println!("{} {}", "Owned by".green().bold(), owner);
+            let keys = match owner.pool {
+                Pool::Transparent => "tkeys",
+                Pool::Sapling => "zkeys",
+                Pool::Orchard => "okeys",
+            };
+            println!("    Key: {}[{}]", keys, owner.key);
+        }
+        None => println!("{}", "The address doesn't belong to this wallet.".red()),
+    }
+
+    Ok(())
+}
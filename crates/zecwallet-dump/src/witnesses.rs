@@ -0,0 +1,52 @@
+use owo_colors::OwoColorize;
+use zecwallet_parser::{error::WalletError, zwl::ZwlWallet};
+
+/// Prints the result of validating every unspent Sapling note's witnesses
+/// against the wallet's verified tree.
+pub fn print_witnesses(wallet: &ZwlWallet) -> Result<(), WalletError> {
+    let report = wallet.check_sapling_witnesses()?;
+
+    match (report.tree_height, report.tree_size, report.tree_root) {
+        (Some(height), Some(size), Some(root)) => println!(
+            "{} height {}, size {}, root {}\n",
+            "Verified Sapling tree:".bold(),
+            height.bright_green(),
+            size,
+            hex::encode(root)
+        ),
+        _ => println!("{}\n", "Wallet has no verified tree.".yellow()),
+    }
+
+    if report.checks.is_empty() {
+        println!("No unspent Sapling notes with a spending key.");
+        return Ok(());
+    }
+
+    for check in &report.checks {
+        let status = if check.status.is_valid() {
+            check.status.to_string().green().to_string()
+        } else {
+            check.status.to_string().red().to_string()
+        };
+
+        println!(
+            "- {} #{} ({} zats, {} witnesses, top height {}): {}",
+            check.txid,
+            check.note_index,
+            check.value,
+            check.witness_count,
+            check.top_height,
+            status
+        );
+    }
+
+    let problems = report.problems().count();
+    println!(
+        "\n{} {} of {} notes",
+        "Problems:".bold(),
+        problems.red().bold(),
+        report.checks.len()
+    );
+
+    Ok(())
+}